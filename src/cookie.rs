@@ -0,0 +1,613 @@
+//! HTTP cookies, as described in RFC 6265.
+//!
+//! A handler reads the cookies a client sent with the [`CookieJar`] extractor, and sets or removes
+//! cookies by returning the changed jar alongside its body:
+//!
+//! ```
+//! use cairo::cookie::{Cookie, CookieJar};
+//!
+//! fn login(jar: CookieJar) -> (CookieJar, &'static str) {
+//!     let cookie = Cookie::new("session", "abc123")
+//!         .with_path("/")
+//!         .with_http_only(true);
+//!     (jar.insert(cookie), "Logged in")
+//! }
+//! ```
+//!
+//! When a client must not be able to forge or read a cookie, use a [`SignedCookieJar`] or a
+//! [`PrivateCookieJar`] with a secret [`Key`] instead.
+mod key;
+mod private;
+mod signed;
+
+use std::{error, fmt, str::FromStr, time::Duration};
+
+use crate::{
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    http::{is_valid_name, HeaderMap, HttpDate, Parts, Request, Response},
+    response::IntoResponseParts,
+};
+
+pub use key::Key;
+pub use private::PrivateCookieJar;
+pub use signed::SignedCookieJar;
+
+/// Error type for a cookie name, value or attribute which cannot be sent in a `Set-Cookie` header.
+#[derive(Debug, PartialEq)]
+pub struct InvalidCookieError;
+
+impl fmt::Display for InvalidCookieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid cookie")
+    }
+}
+
+impl error::Error for InvalidCookieError {}
+
+/// Return `true` if `value` is a valid cookie value. This is any visible ASCII character other
+/// than `"`, `,`, `;` and `\`, so a value cannot be mistaken for the start of another attribute.
+fn is_valid_value(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b.is_ascii_graphic() && !matches!(b, b'"' | b',' | b';' | b'\\'))
+}
+
+/// Return `true` if `value` can be used for an attribute such as `Path`. These may contain spaces,
+/// but not a `;` or any control characters.
+fn is_valid_attribute(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b != b';' && (b == b' ' || b.is_ascii_graphic()))
+}
+
+/// The `SameSite` attribute, which controls whether a cookie is sent with cross-site requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    /// Only send the cookie with requests which start on the same site.
+    Strict,
+    /// Also send the cookie when the user follows a link to the site from elsewhere.
+    Lax,
+    /// Send the cookie with every request. Browsers ignore this unless the cookie is also
+    /// `Secure`.
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+/// A single cookie, with a name, a value and any of the attributes which can be given to a client
+/// in a `Set-Cookie` header.
+///
+/// Its `Display` implementation writes the `Set-Cookie` form, such as
+/// `id=a3fWa; Path=/; Max-Age=3600; HttpOnly`, and its `FromStr` implementation parses one.
+/// Cookies sent by a client only carry a name and value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    expires: Option<HttpDate>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// Create a cookie with no attributes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or value is invalid. Use [`Cookie::try_new`] for untrusted input.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self::try_new(name, value).expect("Invalid cookie.")
+    }
+
+    /// Create a cookie with no attributes, or return an error if the name or value is invalid.
+    /// Values which may contain other characters should be encoded first, for example as base64.
+    pub fn try_new(
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Self, InvalidCookieError> {
+        let (name, value) = (name.into(), value.into());
+        // Cookie names have the same rules as header names.
+        if !is_valid_name(&name) || !is_valid_value(&value) {
+            return Err(InvalidCookieError);
+        }
+
+        Ok(Self {
+            name,
+            value,
+            path: None,
+            domain: None,
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        })
+    }
+
+    /// Create a cookie which tells the client to delete `name`. The cookie is only deleted if its
+    /// `Path` and `Domain` match the ones it was set with.
+    pub fn removal(name: impl Into<String>) -> Self {
+        Self::new(name, "")
+            .with_max_age(Duration::ZERO)
+            .with_expires(HttpDate::from_unix_secs(0))
+    }
+
+    /// Return the name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Return the `Path` attribute, if there is one.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Return the `Domain` attribute, if there is one.
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    /// Return the `Expires` attribute, if there is one.
+    pub fn expires(&self) -> Option<HttpDate> {
+        self.expires
+    }
+
+    /// Return the `Max-Age` attribute, if there is one.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    /// Return `true` if the cookie has the `Secure` attribute.
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Return `true` if the cookie has the `HttpOnly` attribute.
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    /// Return the `SameSite` attribute, if there is one.
+    pub fn same_site(&self) -> Option<SameSite> {
+        self.same_site
+    }
+
+    /// Return `true` if this cookie tells the client to delete it, either with a `Max-Age` of
+    /// zero or an `Expires` date in the past.
+    pub fn is_removal(&self) -> bool {
+        self.max_age == Some(Duration::ZERO)
+            || self
+                .expires
+                .is_some_and(|expires| expires < HttpDate::now())
+    }
+
+    /// Set the `Path` attribute, limiting the cookie to paths which start with `path`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `path` contains a `;` or any control characters.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        let path = path.into();
+        assert!(is_valid_attribute(&path), "Invalid cookie.");
+        self.path = Some(path);
+        self
+    }
+
+    /// Set the `Domain` attribute, sharing the cookie with subdomains of `domain`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `domain` contains a `;` or any control characters.
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        let domain = domain.into();
+        assert!(is_valid_attribute(&domain), "Invalid cookie.");
+        self.domain = Some(domain);
+        self
+    }
+
+    /// Set the `Expires` attribute, the time after which the client should delete the cookie.
+    pub fn with_expires(mut self, expires: impl Into<HttpDate>) -> Self {
+        self.expires = Some(expires.into());
+        self
+    }
+
+    /// Set the `Max-Age` attribute, which takes priority over `Expires`. Only whole seconds are
+    /// sent.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Set whether the cookie has the `Secure` attribute, so it is only sent over HTTPS.
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Set whether the cookie has the `HttpOnly` attribute, hiding it from scripts.
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Set the `SameSite` attribute.
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Return `true` if `other` would replace this cookie on the client, which is when the name,
+    /// path and domain all match.
+    fn same_slot(&self, other: &Cookie) -> bool {
+        self.name == other.name && self.path == other.path && self.domain == other.domain
+    }
+}
+
+impl fmt::Display for Cookie {
+    /// Write the cookie as the value of a `Set-Cookie` header.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", expires)?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Cookie {
+    type Err = InvalidCookieError;
+
+    /// Parse the value of a `Set-Cookie` header. Attribute names are case-insensitive, and any
+    /// which are unknown or have an invalid value are ignored, as RFC 6265 requires.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items = s.split(';');
+        let (name, value) = items
+            .next()
+            .and_then(parse_pair)
+            .ok_or(InvalidCookieError)?;
+        let mut cookie = Self::try_new(name, value)?;
+
+        for item in items {
+            let (attribute, value) = item.split_once('=').unwrap_or((item, ""));
+            let value = value.trim();
+            match attribute.trim().to_ascii_lowercase().as_str() {
+                "path" if value.starts_with('/') => cookie.path = Some(value.to_string()),
+                "domain" if !value.is_empty() => {
+                    let domain = value.strip_prefix('.').unwrap_or(value);
+                    cookie.domain = Some(domain.to_ascii_lowercase());
+                }
+                "expires" => {
+                    if let Ok(expires) = value.parse() {
+                        cookie.expires = Some(expires);
+                    }
+                }
+                "max-age" => {
+                    // A negative `Max-Age` deletes the cookie straight away, like zero.
+                    if let Some(negative) = value.strip_prefix('-') {
+                        if negative.bytes().all(|b| b.is_ascii_digit()) && !negative.is_empty() {
+                            cookie.max_age = Some(Duration::ZERO);
+                        }
+                    } else if let Ok(secs) = value.parse() {
+                        cookie.max_age = Some(Duration::from_secs(secs));
+                    }
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => Some(SameSite::Strict),
+                        "lax" => Some(SameSite::Lax),
+                        "none" => Some(SameSite::None),
+                        _ => cookie.same_site,
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(cookie)
+    }
+}
+
+/// Split a `name=value` pair, trimming whitespace and removing the double quotes which may
+/// surround a value.
+fn parse_pair(pair: &str) -> Option<(&str, &str)> {
+    let (name, value) = pair.split_once('=')?;
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((name.trim(), value))
+}
+
+impl IntoResponseParts for Cookie {
+    /// Add a `Set-Cookie` header for this cookie, keeping any others the response already has.
+    fn into_response_parts(self, response: &mut Response) {
+        response
+            .headers_mut()
+            .append("Set-Cookie", self.to_string());
+    }
+}
+
+/// The cookies a client sent with a request, along with any changes which should be sent back.
+///
+/// The jar is read from the `Cookie` header. Calling [`CookieJar::insert`] or [`CookieJar::remove`]
+/// records a change, and returning the jar in a response tuple writes one `Set-Cookie` header for
+/// each change. Cookies which were only read are not sent back.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CookieJar {
+    /// The cookies from the request, in the order they were sent.
+    original: Vec<Cookie>,
+    /// The cookies inserted or removed since, in the order they were changed.
+    delta: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// Create an empty `CookieJar`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse every `Cookie` header in `headers`. Pairs which are malformed are skipped rather than
+    /// failing the whole jar, since a client may hold cookies set by other applications.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let original = headers
+            .get_all("Cookie")
+            .flat_map(|value| value.split(';'))
+            .filter_map(parse_pair)
+            .filter_map(|(name, value)| Cookie::try_new(name, value).ok())
+            .collect();
+        Self {
+            original,
+            delta: vec![],
+        }
+    }
+
+    /// Return the cookie called `name`, taking any changes into account. If the client sent more
+    /// than one cookie with that name, the first one is returned.
+    pub fn get(&self, name: &str) -> Option<&Cookie> {
+        match self.delta.iter().rev().find(|cookie| cookie.name == name) {
+            Some(cookie) if cookie.is_removal() => None,
+            Some(cookie) => Some(cookie),
+            None => self.original.iter().find(|cookie| cookie.name == name),
+        }
+    }
+
+    /// Insert `cookie` into the jar, to be sent back to the client with a `Set-Cookie` header. This
+    /// replaces any earlier change to a cookie with the same name, path and domain.
+    pub fn insert(mut self, cookie: Cookie) -> Self {
+        self.delta.retain(|existing| !existing.same_slot(&cookie));
+        self.delta.push(cookie);
+        self
+    }
+
+    /// Tell the client to delete `cookie`. Only its name, path and domain are used, which must
+    /// match the ones the cookie was set with.
+    pub fn remove(self, cookie: Cookie) -> Self {
+        let mut removal = Cookie::removal(cookie.name);
+        removal.path = cookie.path;
+        removal.domain = cookie.domain;
+        self.insert(removal)
+    }
+
+    /// Iterate over the cookies the client will hold once the changes have been applied.
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.original
+            .iter()
+            .filter(|cookie| !self.delta.iter().any(|changed| changed.name == cookie.name))
+            .chain(self.delta.iter().filter(|cookie| !cookie.is_removal()))
+    }
+
+    /// Iterate over the cookies which have been inserted or removed, in the order they were changed.
+    pub fn delta(&self) -> impl Iterator<Item = &Cookie> {
+        self.delta.iter()
+    }
+}
+
+impl FromRequestParts for CookieJar {
+    /// Parse the jar from the request headers. This never fails, since a request without cookies
+    /// simply has an empty jar.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+impl FromRequest for CookieJar {
+    /// When a `CookieJar` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+impl IntoResponseParts for CookieJar {
+    /// Add a `Set-Cookie` header for every cookie which was inserted or removed.
+    fn into_response_parts(self, response: &mut Response) {
+        for cookie in self.delta {
+            cookie.into_response_parts(response);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http::Method, response::IntoResponse};
+
+    #[test]
+    fn test_cookie_display() {
+        let cookie = Cookie::new("id", "a3fWa");
+        assert_eq!(cookie.to_string(), "id=a3fWa");
+
+        let cookie = Cookie::new("id", "a3fWa")
+            .with_path("/docs")
+            .with_domain("example.com")
+            .with_expires(HttpDate::from_unix_secs(784_111_777))
+            .with_max_age(Duration::from_secs(3600))
+            .with_secure(true)
+            .with_http_only(true)
+            .with_same_site(SameSite::Lax);
+        assert_eq!(
+            cookie.to_string(),
+            "id=a3fWa; Path=/docs; Domain=example.com; Expires=Sun, 06 Nov 1994 08:49:37 GMT; \
+             Max-Age=3600; Secure; HttpOnly; SameSite=Lax"
+        );
+    }
+
+    #[test]
+    fn test_cookie_parse_round_trip() {
+        let cookie = Cookie::new("theme", "dark")
+            .with_path("/")
+            .with_domain("example.com")
+            .with_expires(HttpDate::from_unix_secs(4_102_444_799))
+            .with_max_age(Duration::from_secs(60))
+            .with_secure(true)
+            .with_http_only(true)
+            .with_same_site(SameSite::Strict);
+        assert_eq!(cookie.to_string().parse(), Ok(cookie));
+    }
+
+    #[test]
+    fn test_cookie_parse_lenient_attributes() {
+        let cookie: Cookie = "a=\"b\"; path=/x; DOMAIN=.Example.com; max-age=-1; \
+                              expires=garbage; samesite=none; Unknown=1; secure"
+            .parse()
+            .unwrap();
+        assert_eq!(cookie.value(), "b");
+        assert_eq!(cookie.path(), Some("/x"));
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert_eq!(cookie.max_age(), Some(Duration::ZERO));
+        assert_eq!(cookie.expires(), None);
+        assert_eq!(cookie.same_site(), Some(SameSite::None));
+        assert!(cookie.secure());
+        assert!(!cookie.http_only());
+        assert!(cookie.is_removal());
+
+        assert_eq!("novalue".parse::<Cookie>(), Err(InvalidCookieError));
+        assert_eq!("=value".parse::<Cookie>(), Err(InvalidCookieError));
+    }
+
+    #[test]
+    fn test_cookie_rejects_invalid() {
+        assert_eq!(Cookie::try_new("", "x"), Err(InvalidCookieError));
+        assert_eq!(Cookie::try_new("a b", "x"), Err(InvalidCookieError));
+        assert_eq!(
+            Cookie::try_new("a", "x; Domain=evil.com"),
+            Err(InvalidCookieError)
+        );
+        assert_eq!(Cookie::try_new("a", "x\r\n"), Err(InvalidCookieError));
+        assert_eq!(Cookie::try_new("a", "ü"), Err(InvalidCookieError));
+        assert!(Cookie::try_new("a", "").is_ok());
+        assert!(Cookie::try_new("a", "base64+/=").is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid cookie.")]
+    fn test_cookie_invalid_path_panics() {
+        Cookie::new("a", "b").with_path("/; Domain=evil.com");
+    }
+
+    #[test]
+    fn test_cookie_jar_from_headers() {
+        let headers = HeaderMap::from([
+            ("Cookie", "a=1; b=\"two\";bad; c=3"),
+            ("cookie", "a=shadowed; d="),
+        ]);
+        let jar = CookieJar::from_headers(&headers);
+        assert_eq!(jar.get("a").map(Cookie::value), Some("1"));
+        assert_eq!(jar.get("b").map(Cookie::value), Some("two"));
+        assert_eq!(jar.get("c").map(Cookie::value), Some("3"));
+        assert_eq!(jar.get("d").map(Cookie::value), Some(""));
+        assert_eq!(jar.get("bad"), None);
+        assert_eq!(jar.iter().count(), 5);
+        assert_eq!(jar.delta().count(), 0);
+    }
+
+    #[test]
+    fn test_cookie_jar_changes() {
+        let headers = HeaderMap::from([("Cookie", "session=old; theme=dark")]);
+        let jar = CookieJar::from_headers(&headers)
+            .insert(Cookie::new("session", "new"))
+            .insert(Cookie::new("session", "newer"))
+            .remove(Cookie::new("theme", "").with_path("/"));
+
+        assert_eq!(jar.get("session").map(Cookie::value), Some("newer"));
+        assert_eq!(jar.get("theme"), None);
+        assert_eq!(
+            jar.iter().map(Cookie::name).collect::<Vec<_>>(),
+            vec!["session"]
+        );
+        assert_eq!(
+            jar.delta().map(Cookie::to_string).collect::<Vec<_>>(),
+            vec![
+                "session=newer",
+                "theme=; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cookie_jar_extractor() {
+        let headers = HeaderMap::from([("Cookie", "a=1")]);
+        let req = Request::with_headers(Method::Get, "/", headers);
+        let jar = CookieJar::from_request(req).unwrap();
+        assert_eq!(jar.get("a").map(Cookie::value), Some("1"));
+
+        let req = Request::new(Method::Get, "/");
+        let jar = CookieJar::from_request_parts(req.into_parts()).unwrap();
+        assert_eq!(jar.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_cookie_jar_into_response() {
+        let jar = CookieJar::new()
+            .insert(Cookie::new("a", "1").with_http_only(true))
+            .insert(Cookie::new("b", "2"));
+        let response = (jar, "Hello").into_response();
+        assert_eq!(
+            response.headers().get_all("Set-Cookie").collect::<Vec<_>>(),
+            vec!["a=1; HttpOnly", "b=2"]
+        );
+        assert_eq!(response.text(), "Hello");
+
+        let response = (Cookie::new("c", "3"), "Hello").into_response();
+        assert_eq!(response.headers().get("set-cookie"), Some("c=3"));
+    }
+}
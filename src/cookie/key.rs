@@ -0,0 +1,58 @@
+use std::{fmt, io};
+
+use crate::core::fill_random;
+
+/// The secret used by [`SignedCookieJar`](super::SignedCookieJar) and
+/// [`PrivateCookieJar`](super::PrivateCookieJar). Half of it signs cookies and the other half
+/// encrypts them, so the same key can safely be used for both.
+///
+/// Give it to the router with [`Router::with_state`](crate::Router::with_state), where the jars
+/// will find it. Anyone who learns the key can forge cookies, so load it from a secret store rather
+/// than the source code, and keep using the same one across restarts so existing cookies stay
+/// valid.
+#[derive(Clone)]
+pub struct Key {
+    signing: [u8; 32],
+    encryption: [u8; 32],
+}
+
+impl Key {
+    /// The length of a key in bytes.
+    pub const LEN: usize = 64;
+
+    /// Create a key from 64 bytes, which should be random.
+    pub fn new(bytes: [u8; Self::LEN]) -> Self {
+        let mut signing = [0; 32];
+        let mut encryption = [0; 32];
+        signing.copy_from_slice(&bytes[..32]);
+        encryption.copy_from_slice(&bytes[32..]);
+        Self {
+            signing,
+            encryption,
+        }
+    }
+
+    /// Generate a new random key using the operating system's random number generator.
+    pub fn generate() -> io::Result<Self> {
+        let mut bytes = [0; Self::LEN];
+        fill_random(&mut bytes)?;
+        Ok(Self::new(bytes))
+    }
+
+    /// Return the half of the key used for HMAC signatures.
+    pub(super) fn signing(&self) -> &[u8; 32] {
+        &self.signing
+    }
+
+    /// Return the half of the key used for encryption.
+    pub(super) fn encryption(&self) -> &[u8; 32] {
+        &self.encryption
+    }
+}
+
+impl fmt::Debug for Key {
+    /// The key is secret, so it is never printed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key").finish_non_exhaustive()
+    }
+}
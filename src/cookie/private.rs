@@ -0,0 +1,213 @@
+use super::{Cookie, CookieJar, Key};
+use crate::{
+    core::{
+        base64,
+        chacha20poly1305::{self, NONCE_LEN},
+        fill_random,
+    },
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    http::{HeaderMap, Parts, Request, Response},
+    response::IntoResponseParts,
+};
+
+/// A [`CookieJar`] whose cookies are encrypted with ChaCha20-Poly1305, so a client can neither
+/// read nor change them. Cookies which cannot be decrypted are treated as if they were never
+/// sent.
+///
+/// The cookie name is authenticated along with the value, so an encrypted value cannot be moved to
+/// a different cookie. The [`Key`] is read from the router state, and the request is rejected if
+/// there isn't one.
+#[derive(Debug, Clone)]
+pub struct PrivateCookieJar {
+    jar: CookieJar,
+    key: Key,
+}
+
+impl PrivateCookieJar {
+    /// Create an empty jar which encrypts with `key`.
+    pub fn new(key: Key) -> Self {
+        Self {
+            jar: CookieJar::new(),
+            key,
+        }
+    }
+
+    /// Parse every `Cookie` header in `headers`, to be decrypted with `key`.
+    pub fn from_headers(headers: &HeaderMap, key: Key) -> Self {
+        Self {
+            jar: CookieJar::from_headers(headers),
+            key,
+        }
+    }
+
+    /// Return the cookie called `name` with its value decrypted, if it can be.
+    pub fn get(&self, name: &str) -> Option<Cookie> {
+        self.jar.get(name).and_then(|cookie| self.decrypt(cookie))
+    }
+
+    /// Encrypt `cookie` and insert it into the jar, to be sent back to the client. The encrypted
+    /// value is base64 and about 40 bytes longer than the original.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operating system's random number generator is unavailable, since a nonce
+    /// must never be reused.
+    pub fn insert(mut self, mut cookie: Cookie) -> Self {
+        let mut nonce = [0; NONCE_LEN];
+        fill_random(&mut nonce).expect("Failed to generate a nonce.");
+
+        let sealed = chacha20poly1305::seal(
+            self.key.encryption(),
+            &nonce,
+            cookie.name.as_bytes(),
+            cookie.value.as_bytes(),
+        );
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&sealed);
+        cookie.value = base64::encode(&payload);
+
+        self.jar = self.jar.insert(cookie);
+        self
+    }
+
+    /// Tell the client to delete `cookie`, as for [`CookieJar::remove`].
+    pub fn remove(mut self, cookie: Cookie) -> Self {
+        self.jar = self.jar.remove(cookie);
+        self
+    }
+
+    /// Iterate over the cookies which could be decrypted, with their values decrypted.
+    pub fn iter(&self) -> impl Iterator<Item = Cookie> + '_ {
+        self.jar.iter().filter_map(|cookie| self.decrypt(cookie))
+    }
+
+    /// Decrypt and authenticate the value of a cookie.
+    fn decrypt(&self, cookie: &Cookie) -> Option<Cookie> {
+        let payload = base64::decode(&cookie.value)?;
+        if payload.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, sealed) = payload.split_at(NONCE_LEN);
+        let nonce = nonce.try_into().ok()?;
+        let plaintext =
+            chacha20poly1305::open(self.key.encryption(), nonce, cookie.name.as_bytes(), sealed)?;
+
+        let mut decrypted = cookie.clone();
+        decrypted.value = String::from_utf8(plaintext).ok()?;
+        Some(decrypted)
+    }
+}
+
+impl FromRequestParts for PrivateCookieJar {
+    /// Parse the jar from the request headers, using the [`Key`] from the router state.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        let key = parts
+            .extensions
+            .get::<Key>()
+            .ok_or(ExtractError::BadRequest)?;
+        Ok(Self::from_headers(&parts.headers, key.clone()))
+    }
+}
+
+impl FromRequest for PrivateCookieJar {
+    /// When a `PrivateCookieJar` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+impl IntoResponseParts for PrivateCookieJar {
+    /// Add a `Set-Cookie` header for every cookie which was inserted or removed.
+    fn into_response_parts(self, response: &mut Response) {
+        self.jar.into_response_parts(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::IntoResponse;
+
+    fn key(byte: u8) -> Key {
+        Key::new([byte; Key::LEN])
+    }
+
+    fn cookie_header(jar: PrivateCookieJar) -> HeaderMap {
+        let response = (jar, "").into_response();
+        let pairs: Vec<String> = response
+            .headers()
+            .get_all("Set-Cookie")
+            .map(|value| value.split(';').next().unwrap().to_string())
+            .collect();
+        HeaderMap::from([("Cookie", pairs.join("; "))])
+    }
+
+    #[test]
+    fn test_private_cookie_round_trip() {
+        let headers = cookie_header(
+            PrivateCookieJar::new(key(1))
+                .insert(Cookie::new("secret", "hunter2"))
+                .insert(Cookie::new("empty", "")),
+        );
+        assert!(!headers.get("Cookie").unwrap().contains("hunter2"));
+
+        let jar = PrivateCookieJar::from_headers(&headers, key(1));
+        assert_eq!(
+            jar.get("secret").as_ref().map(Cookie::value),
+            Some("hunter2")
+        );
+        assert_eq!(jar.get("empty").as_ref().map(Cookie::value), Some(""));
+        assert_eq!(jar.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_private_cookie_uses_fresh_nonces() {
+        let first = cookie_header(PrivateCookieJar::new(key(1)).insert(Cookie::new("a", "b")));
+        let second = cookie_header(PrivateCookieJar::new(key(1)).insert(Cookie::new("a", "b")));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_private_cookie_rejects_tampering() {
+        let headers = cookie_header(PrivateCookieJar::new(key(1)).insert(Cookie::new("a", "b")));
+        let encrypted = headers.get("Cookie").unwrap().to_string();
+
+        assert_eq!(
+            PrivateCookieJar::from_headers(&headers, key(2)).get("a"),
+            None
+        );
+
+        let moved = HeaderMap::from([("Cookie", encrypted.replacen("a=", "c=", 1))]);
+        assert_eq!(
+            PrivateCookieJar::from_headers(&moved, key(1)).get("c"),
+            None
+        );
+
+        let value = encrypted.trim_start_matches("a=");
+        let mut bytes = base64::decode(value).unwrap();
+        bytes[NONCE_LEN] ^= 1;
+        let flipped = HeaderMap::from([("Cookie", format!("a={}", base64::encode(&bytes)))]);
+        assert_eq!(
+            PrivateCookieJar::from_headers(&flipped, key(1)).get("a"),
+            None
+        );
+
+        let garbage = HeaderMap::from([("Cookie", "a=not-base64; b=AAAA")]);
+        assert_eq!(
+            PrivateCookieJar::from_headers(&garbage, key(1))
+                .iter()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_private_cookie_jar_extractor() {
+        let mut req = Request::new(crate::http::Method::Get, "/");
+        assert!(PrivateCookieJar::from_request_parts(req.into_parts()).is_err());
+
+        req.extensions_mut().insert(key(1));
+        assert!(PrivateCookieJar::from_request(req).is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+use super::{Cookie, CookieJar, Key};
+use crate::{
+    core::{
+        base64,
+        sha256::{constant_time_eq, hmac_sha256},
+    },
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    http::{HeaderMap, Parts, Request, Response},
+    response::IntoResponseParts,
+};
+
+/// The length of a base64-encoded HMAC-SHA256 tag.
+const ENCODED_TAG_LEN: usize = 44;
+
+/// A [`CookieJar`] whose cookies are signed with HMAC-SHA256, so a client can read them but not
+/// change them. Cookies with a missing or incorrect signature are treated as if they were never
+/// sent.
+///
+/// The signature covers the name as well as the value, so a signed value cannot be moved to a
+/// different cookie. The [`Key`] is read from the router state, and the request is rejected if
+/// there isn't one.
+#[derive(Debug, Clone)]
+pub struct SignedCookieJar {
+    jar: CookieJar,
+    key: Key,
+}
+
+impl SignedCookieJar {
+    /// Create an empty jar which signs with `key`.
+    pub fn new(key: Key) -> Self {
+        Self {
+            jar: CookieJar::new(),
+            key,
+        }
+    }
+
+    /// Parse every `Cookie` header in `headers`, to be verified with `key`.
+    pub fn from_headers(headers: &HeaderMap, key: Key) -> Self {
+        Self {
+            jar: CookieJar::from_headers(headers),
+            key,
+        }
+    }
+
+    /// Return the cookie called `name` with its signature removed, if it has a valid one.
+    pub fn get(&self, name: &str) -> Option<Cookie> {
+        self.jar.get(name).and_then(|cookie| self.verify(cookie))
+    }
+
+    /// Sign `cookie` and insert it into the jar, to be sent back to the client.
+    pub fn insert(mut self, mut cookie: Cookie) -> Self {
+        let tag = base64::encode(&self.tag(&cookie.name, &cookie.value));
+        cookie.value.insert_str(0, &tag);
+        self.jar = self.jar.insert(cookie);
+        self
+    }
+
+    /// Tell the client to delete `cookie`, as for [`CookieJar::remove`].
+    pub fn remove(mut self, cookie: Cookie) -> Self {
+        self.jar = self.jar.remove(cookie);
+        self
+    }
+
+    /// Iterate over the cookies with a valid signature, with the signature removed.
+    pub fn iter(&self) -> impl Iterator<Item = Cookie> + '_ {
+        self.jar.iter().filter_map(|cookie| self.verify(cookie))
+    }
+
+    /// Compute the signature for a cookie.
+    fn tag(&self, name: &str, value: &str) -> [u8; 32] {
+        hmac_sha256(self.key.signing(), format!("{}={}", name, value).as_bytes())
+    }
+
+    /// Check the signature at the start of the cookie's value, returning the cookie without it.
+    fn verify(&self, cookie: &Cookie) -> Option<Cookie> {
+        if !cookie.value.is_char_boundary(ENCODED_TAG_LEN) {
+            return None;
+        }
+        let (tag, value) = cookie.value.split_at(ENCODED_TAG_LEN);
+        let tag = base64::decode(tag)?;
+        if !constant_time_eq(&tag, &self.tag(&cookie.name, value)) {
+            return None;
+        }
+
+        let mut verified = cookie.clone();
+        verified.value = value.to_string();
+        Some(verified)
+    }
+}
+
+impl FromRequestParts for SignedCookieJar {
+    /// Parse the jar from the request headers, using the [`Key`] from the router state.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        let key = parts
+            .extensions
+            .get::<Key>()
+            .ok_or(ExtractError::BadRequest)?;
+        Ok(Self::from_headers(&parts.headers, key.clone()))
+    }
+}
+
+impl FromRequest for SignedCookieJar {
+    /// When a `SignedCookieJar` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+impl IntoResponseParts for SignedCookieJar {
+    /// Add a `Set-Cookie` header for every cookie which was inserted or removed.
+    fn into_response_parts(self, response: &mut Response) {
+        self.jar.into_response_parts(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::IntoResponse;
+
+    fn key(byte: u8) -> Key {
+        Key::new([byte; Key::LEN])
+    }
+
+    /// Send the cookies a jar would set back in a `Cookie` header, like a browser would.
+    fn round_trip(response: Response) -> HeaderMap {
+        let pairs: Vec<String> = response
+            .headers()
+            .get_all("Set-Cookie")
+            .map(|value| {
+                let cookie: Cookie = value.parse().unwrap();
+                format!("{}={}", cookie.name(), cookie.value())
+            })
+            .collect();
+        HeaderMap::from([("Cookie", pairs.join("; "))])
+    }
+
+    #[test]
+    fn test_signed_cookie_round_trip() {
+        let jar = SignedCookieJar::new(key(1))
+            .insert(Cookie::new("user", "ferris").with_http_only(true))
+            .insert(Cookie::new("empty", ""));
+        let response = (jar, "").into_response();
+        let set_cookie = response.headers().get("Set-Cookie").unwrap();
+        assert!(set_cookie.ends_with("ferris; HttpOnly"));
+        assert_eq!(
+            set_cookie.len(),
+            "user=ferris; HttpOnly".len() + ENCODED_TAG_LEN
+        );
+
+        let jar = SignedCookieJar::from_headers(&round_trip(response), key(1));
+        assert_eq!(jar.get("user").as_ref().map(Cookie::value), Some("ferris"));
+        assert_eq!(jar.get("empty").as_ref().map(Cookie::value), Some(""));
+        assert_eq!(jar.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_signed_cookie_rejects_forgery() {
+        let response = (
+            SignedCookieJar::new(key(1)).insert(Cookie::new("role", "user")),
+            "",
+        )
+            .into_response();
+        let headers = round_trip(response);
+        let signed = headers.get("Cookie").unwrap().to_string();
+
+        // A different key does not accept the signature.
+        let jar = SignedCookieJar::from_headers(&headers, key(2));
+        assert_eq!(jar.get("role"), None);
+
+        // Neither does a changed value, or the same value under another name.
+        let tampered = HeaderMap::from([
+            (
+                "Cookie",
+                signed.replace("=user", "=admin").replace("role=", "a="),
+            ),
+            ("Cookie", signed.replace("role=", "b=")),
+            ("Cookie", "c=unsigned".to_string()),
+        ]);
+        let jar = SignedCookieJar::from_headers(&tampered, key(1));
+        assert_eq!(jar.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_signed_cookie_jar_extractor() {
+        let mut req = Request::new(crate::http::Method::Get, "/");
+        assert!(SignedCookieJar::from_request_parts(req.into_parts()).is_err());
+
+        req.extensions_mut().insert(key(1));
+        let jar = SignedCookieJar::from_request(req).unwrap();
+        assert_eq!(jar.iter().count(), 0);
+    }
+}
@@ -0,0 +1,164 @@
+//! Base64 encoding with the standard alphabet and padding, as specified in RFC 4648, and the
+//! unpadded URL-safe alphabet used by JSON Web Tokens.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `bytes` as base64.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = match *chunk {
+            [a] => u32::from(a) << 16,
+            [a, b] => u32::from(a) << 16 | u32::from(b) << 8,
+            [a, b, c, ..] => u32::from(a) << 16 | u32::from(b) << 8 | u32::from(c),
+            [] => unreachable!(),
+        };
+        // Each group of up to three bytes becomes one more character than it has bytes, with the
+        // rest made up with padding.
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                output.push(ALPHABET[index as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+/// Return the 6-bit value of a base64 character.
+fn decode_char(c: u8) -> Option<u32> {
+    let value = match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => return None,
+    };
+    Some(u32::from(value))
+}
+
+/// Decode base64, returning `None` unless `input` is exactly what [`encode`] would produce. Padding
+/// is required and any unused bits must be zero, so each value has only one valid encoding.
+pub(crate) fn decode(input: &str) -> Option<Vec<u8>> {
+    let input = input.as_bytes();
+    if !input.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut output = Vec::with_capacity(input.len() / 4 * 3);
+    let groups = input.len() / 4;
+    for (index, chunk) in input.chunks_exact(4).enumerate() {
+        // Padding may only appear at the end of the last group.
+        let padding = if index + 1 == groups {
+            chunk.iter().rev().take_while(|&&c| c == b'=').count()
+        } else {
+            0
+        };
+        if padding > 2 {
+            return None;
+        }
+
+        let mut group = 0;
+        for &c in &chunk[..4 - padding] {
+            group = group << 6 | decode_char(c)?;
+        }
+        group <<= 6 * padding;
+
+        let bytes = [(group >> 16) as u8, (group >> 8) as u8, group as u8];
+        let len = 3 - padding;
+        if bytes[len..].iter().any(|&b| b != 0) {
+            return None;
+        }
+        output.extend_from_slice(&bytes[..len]);
+    }
+    Some(output)
+}
+
+/// Encode `bytes` as unpadded base64 with the URL-safe alphabet, where `-` and `_` replace `+`
+/// and `/`.
+pub(crate) fn encode_url(bytes: &[u8]) -> String {
+    encode(bytes)
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Decode unpadded base64 with the URL-safe alphabet, returning `None` unless `input` is exactly
+/// what [`encode_url`] would produce.
+pub(crate) fn decode_url(input: &str) -> Option<Vec<u8>> {
+    if input.len() % 4 == 1 || input.contains(['+', '/', '=']) {
+        return None;
+    }
+    let mut standard: String = input
+        .chars()
+        .map(|c| match c {
+            '-' => '+',
+            '_' => '/',
+            c => c,
+        })
+        .collect();
+    while !standard.len().is_multiple_of(4) {
+        standard.push('=');
+    }
+    decode(&standard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn test_base64_rfc4648_vectors() {
+        for (plain, encoded) in VECTORS {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+            assert_eq!(decode(encoded).as_deref(), Some(plain.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_base64_binary_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode(&bytes)), Some(bytes));
+    }
+
+    #[test]
+    fn test_base64_decode_invalid() {
+        for invalid in [
+            "Zg", "Zg=", "Z===", "Zh==", "Zm9v!A==", "Zg==Zg==", "=Zg=", "Zm 9v",
+        ] {
+            assert_eq!(decode(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_base64_url() {
+        for (plain, encoded) in VECTORS {
+            let unpadded = encoded.trim_end_matches('=');
+            assert_eq!(encode_url(plain.as_bytes()), unpadded);
+            assert_eq!(decode_url(unpadded).as_deref(), Some(plain.as_bytes()));
+        }
+        assert_eq!(encode_url(&[0xfb, 0xff]), "-_8");
+        assert_eq!(decode_url("-_8"), Some(vec![0xfb, 0xff]));
+        for invalid in ["+_8", "-/8", "Zg==", "Zm9vY", "Zh"] {
+            assert_eq!(decode_url(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! The ChaCha20-Poly1305 authenticated encryption algorithm, as specified in RFC 8439.
+
+use super::sha256::constant_time_eq;
+
+/// The length of a ChaCha20 key in bytes.
+pub(crate) const KEY_LEN: usize = 32;
+/// The length of a nonce in bytes. A nonce must never be reused with the same key.
+pub(crate) const NONCE_LEN: usize = 12;
+/// The length of the authentication tag which is appended to the ciphertext.
+pub(crate) const TAG_LEN: usize = 16;
+
+/// The ChaCha quarter round, which mixes four words of the state.
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+/// Read a little-endian word from the start of `bytes`.
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Produce one 64-byte block of keystream.
+fn chacha20_block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    // The constant is "expand 32-byte k".
+    let mut initial = [
+        0x61707865, 0x3320646e, 0x79622d32, 0x6b206574, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    for (word, bytes) in initial[4..12].iter_mut().zip(key.chunks_exact(4)) {
+        *word = le_u32(bytes);
+    }
+    initial[12] = counter;
+    for (word, bytes) in initial[13..].iter_mut().zip(nonce.chunks_exact(4)) {
+        *word = le_u32(bytes);
+    }
+
+    let mut state = initial;
+    for _ in 0..10 {
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+
+    let mut block = [0; 64];
+    for ((chunk, word), initial) in block.chunks_exact_mut(4).zip(state).zip(initial) {
+        chunk.copy_from_slice(&word.wrapping_add(initial).to_le_bytes());
+    }
+    block
+}
+
+/// Encrypt or decrypt `data` in place by XORing it with the keystream, starting at block
+/// `counter`.
+fn chacha20_xor(key: &[u8; KEY_LEN], mut counter: u32, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for chunk in data.chunks_mut(64) {
+        let block = chacha20_block(key, counter, nonce);
+        for (byte, key_byte) in chunk.iter_mut().zip(block) {
+            *byte ^= key_byte;
+        }
+        counter = counter.wrapping_add(1);
+    }
+}
+
+/// Compute the Poly1305 one-time authenticator of `message`. The arithmetic is done modulo
+/// 2^130 - 5 using five 26-bit limbs, so that every product fits in a `u64`.
+fn poly1305(key: &[u8; 32], message: &[u8]) -> [u8; TAG_LEN] {
+    const MASK: u32 = 0x3ffffff;
+
+    // `r` is clamped as the specification requires.
+    let r0 = le_u32(&key[0..]) & 0x3ffffff;
+    let r1 = (le_u32(&key[3..]) >> 2) & 0x3ffff03;
+    let r2 = (le_u32(&key[6..]) >> 4) & 0x3ffc0ff;
+    let r3 = (le_u32(&key[9..]) >> 6) & 0x3f03fff;
+    let r4 = (le_u32(&key[12..]) >> 8) & 0x00fffff;
+    let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+
+    let (mut h0, mut h1, mut h2, mut h3, mut h4) = (0u32, 0u32, 0u32, 0u32, 0u32);
+    for chunk in message.chunks(16) {
+        // Each block has a 1 appended just past its last byte, which for a full block is bit 128.
+        let mut block = [0; 17];
+        block[..chunk.len()].copy_from_slice(chunk);
+        block[chunk.len()] = 1;
+
+        h0 += le_u32(&block[0..]) & MASK;
+        h1 += (le_u32(&block[3..]) >> 2) & MASK;
+        h2 += (le_u32(&block[6..]) >> 4) & MASK;
+        h3 += (le_u32(&block[9..]) >> 6) & MASK;
+        h4 += (le_u32(&block[12..]) >> 8) | (u32::from(block[16]) << 24);
+
+        let mul = |a: u32, b: u32| u64::from(a) * u64::from(b);
+        let d0 = mul(h0, r0) + mul(h1, s4) + mul(h2, s3) + mul(h3, s2) + mul(h4, s1);
+        let mut d1 = mul(h0, r1) + mul(h1, r0) + mul(h2, s4) + mul(h3, s3) + mul(h4, s2);
+        let mut d2 = mul(h0, r2) + mul(h1, r1) + mul(h2, r0) + mul(h3, s4) + mul(h4, s3);
+        let mut d3 = mul(h0, r3) + mul(h1, r2) + mul(h2, r1) + mul(h3, r0) + mul(h4, s4);
+        let mut d4 = mul(h0, r4) + mul(h1, r3) + mul(h2, r2) + mul(h3, r1) + mul(h4, r0);
+
+        // Propagate the carries, folding anything above bit 130 back in multiplied by 5.
+        h0 = d0 as u32 & MASK;
+        d1 += d0 >> 26;
+        h1 = d1 as u32 & MASK;
+        d2 += d1 >> 26;
+        h2 = d2 as u32 & MASK;
+        d3 += d2 >> 26;
+        h3 = d3 as u32 & MASK;
+        d4 += d3 >> 26;
+        h4 = d4 as u32 & MASK;
+        h0 += (d4 >> 26) as u32 * 5;
+        h1 += h0 >> 26;
+        h0 &= MASK;
+    }
+
+    // Fully carry `h`.
+    h2 += h1 >> 26;
+    h1 &= MASK;
+    h3 += h2 >> 26;
+    h2 &= MASK;
+    h4 += h3 >> 26;
+    h3 &= MASK;
+    h0 += (h4 >> 26) * 5;
+    h4 &= MASK;
+    h1 += h0 >> 26;
+    h0 &= MASK;
+
+    // Compute `h - p` and use it instead of `h` if it did not underflow, without branching.
+    let mut g0 = h0.wrapping_add(5);
+    let mut g1 = h1.wrapping_add(g0 >> 26);
+    g0 &= MASK;
+    let mut g2 = h2.wrapping_add(g1 >> 26);
+    g1 &= MASK;
+    let mut g3 = h3.wrapping_add(g2 >> 26);
+    g2 &= MASK;
+    let g4 = h4.wrapping_add(g3 >> 26).wrapping_sub(1 << 26);
+    g3 &= MASK;
+
+    let use_g = (g4 >> 31).wrapping_sub(1);
+    let use_h = !use_g;
+    h0 = (h0 & use_h) | (g0 & use_g);
+    h1 = (h1 & use_h) | (g1 & use_g);
+    h2 = (h2 & use_h) | (g2 & use_g);
+    h3 = (h3 & use_h) | (g3 & use_g);
+    h4 = (h4 & use_h) | (g4 & use_g);
+
+    // Pack into 128 bits and add `s`, dropping the final carry.
+    let words = [
+        h0 | (h1 << 26),
+        (h1 >> 6) | (h2 << 20),
+        (h2 >> 12) | (h3 << 14),
+        (h3 >> 18) | (h4 << 8),
+    ];
+    let mut tag = [0; TAG_LEN];
+    let mut carry = 0u64;
+    for (i, word) in words.into_iter().enumerate() {
+        let sum = u64::from(word) + u64::from(le_u32(&key[16 + 4 * i..])) + carry;
+        tag[4 * i..4 * i + 4].copy_from_slice(&(sum as u32).to_le_bytes());
+        carry = sum >> 32;
+    }
+    tag
+}
+
+/// Compute the tag over the additional data and ciphertext, each padded to 16 bytes and followed
+/// by their lengths.
+fn compute_tag(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> [u8; TAG_LEN] {
+    let mut poly_key = [0; 32];
+    poly_key.copy_from_slice(&chacha20_block(key, 0, nonce)[..32]);
+
+    let padding = |len: usize| (16 - len % 16) % 16;
+    let mut mac_data = Vec::with_capacity(aad.len() + ciphertext.len() + 48);
+    mac_data.extend_from_slice(aad);
+    mac_data.resize(mac_data.len() + padding(aad.len()), 0);
+    mac_data.extend_from_slice(ciphertext);
+    mac_data.resize(mac_data.len() + padding(ciphertext.len()), 0);
+    mac_data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    mac_data.extend_from_slice(&(ciphertext.len() as u64).to_le_bytes());
+    poly1305(&poly_key, &mac_data)
+}
+
+/// Encrypt `plaintext` and authenticate it along with `aad`, returning the ciphertext with the
+/// tag appended.
+pub(crate) fn seal(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let mut output = plaintext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut output);
+    let tag = compute_tag(key, nonce, aad, &output);
+    output.extend_from_slice(&tag);
+    output
+}
+
+/// Check the tag on the output of [`seal`] and decrypt it, returning `None` if the ciphertext or
+/// `aad` have been tampered with.
+pub(crate) fn open(
+    key: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    sealed: &[u8],
+) -> Option<Vec<u8>> {
+    let tag_start = sealed.len().checked_sub(TAG_LEN)?;
+    let (ciphertext, tag) = sealed.split_at(tag_start);
+    if !constant_time_eq(&compute_tag(key, nonce, aad, ciphertext), tag) {
+        return None;
+    }
+
+    let mut plaintext = ciphertext.to_vec();
+    chacha20_xor(key, 1, nonce, &mut plaintext);
+    Some(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::sha256::tests::hex;
+
+    fn key_from(start: u8) -> [u8; KEY_LEN] {
+        std::array::from_fn(|i| start + i as u8)
+    }
+
+    #[test]
+    fn test_chacha20_block_rfc8439() {
+        // RFC 8439, section 2.3.2.
+        let nonce = [0, 0, 0, 0x09, 0, 0, 0, 0x4a, 0, 0, 0, 0];
+        let block = chacha20_block(&key_from(0), 1, &nonce);
+        assert_eq!(
+            block.to_vec(),
+            hex(
+                "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e
+                 d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"
+            )
+        );
+    }
+
+    #[test]
+    fn test_poly1305_rfc8439() {
+        // RFC 8439, section 2.5.2.
+        let key: [u8; 32] = hex("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b")
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            poly1305(&key, b"Cryptographic Forum Research Group").to_vec(),
+            hex("a8061dc1305136c6c22b8baf0c0127a9")
+        );
+    }
+
+    #[test]
+    fn test_poly1305_wraps_modulus() {
+        // RFC 8439, appendix A.3, test vectors 6 and 8: `h` ends up at or just above `p`.
+        let mut key = [0; 32];
+        key[0] = 2;
+        key[16..].fill(0xff);
+        assert_eq!(
+            poly1305(&key, &[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).to_vec(),
+            hex("03000000000000000000000000000000")
+        );
+
+        let mut key = [0; 32];
+        key[0] = 1;
+        let message = hex(
+            "ffffffffffffffffffffffffffffffff fbfefefefefefefefefefefefefefefe
+             01010101010101010101010101010101",
+        );
+        assert_eq!(
+            poly1305(&key, &message).to_vec(),
+            hex("00000000000000000000000000000000")
+        );
+    }
+
+    #[test]
+    fn test_aead_rfc8439() {
+        // RFC 8439, section 2.8.2.
+        let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only \
+                          one tip for the future, sunscreen would be it.";
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let key = key_from(0x80);
+        let nonce = [
+            0x07, 0, 0, 0, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47,
+        ];
+
+        let sealed = seal(&key, &nonce, &aad, plaintext);
+        let expected = hex(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6
+             3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36
+             92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc
+             3ff4def08e4b7a9de576d26586cec64b6116
+             1ae10b594f09e26a7e902ecbd0600691",
+        );
+        assert_eq!(sealed, expected);
+        assert_eq!(
+            open(&key, &nonce, &aad, &sealed).as_deref(),
+            Some(&plaintext[..])
+        );
+    }
+
+    #[test]
+    fn test_aead_rejects_tampering() {
+        let key = key_from(1);
+        let nonce = [7; NONCE_LEN];
+        let sealed = seal(&key, &nonce, b"name", b"secret");
+
+        let mut flipped = sealed.clone();
+        flipped[0] ^= 1;
+        assert_eq!(open(&key, &nonce, b"name", &flipped), None);
+        assert_eq!(open(&key, &nonce, b"other", &sealed), None);
+        assert_eq!(open(&key_from(2), &nonce, b"name", &sealed), None);
+        assert_eq!(open(&key, &nonce, b"name", &sealed[..TAG_LEN - 1]), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! The checksums used by the gzip (RFC 1952) and zlib (RFC 1950) formats.
+
+/// The CRC-32 lookup table for the reversed polynomial `0xEDB88320`, built at compile time.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+/// An incremental CRC-32, as used by gzip.
+#[derive(Debug, Clone)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    pub(crate) fn new() -> Self {
+        Self(0xffff_ffff)
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            self.0 = CRC32_TABLE[((self.0 ^ u32::from(b)) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u32 {
+        self.0 ^ 0xffff_ffff
+    }
+}
+
+/// The largest prime below 2^16, which Adler-32 works modulo.
+const ADLER_MODULUS: u32 = 65521;
+
+/// The most bytes which can be summed before the Adler-32 sums must be reduced to avoid overflow.
+const ADLER_BLOCK: usize = 5552;
+
+/// An incremental Adler-32, as used by zlib.
+#[derive(Debug, Clone)]
+pub(crate) struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    pub(crate) fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for block in data.chunks(ADLER_BLOCK) {
+            for &byte in block {
+                self.a += u32::from(byte);
+                self.b += self.a;
+            }
+            self.a %= ADLER_MODULUS;
+            self.b %= ADLER_MODULUS;
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        let mut crc = Crc32::new();
+        assert_eq!(crc.finish(), 0);
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xcbf4_3926);
+
+        let mut crc = Crc32::new();
+        crc.update(b"The quick brown fox ");
+        crc.update(b"jumps over the lazy dog");
+        assert_eq!(crc.finish(), 0x414f_a339);
+    }
+
+    #[test]
+    fn test_adler32() {
+        let mut adler = Adler32::new();
+        assert_eq!(adler.finish(), 1);
+        adler.update(b"Wikipedia");
+        assert_eq!(adler.finish(), 0x11e6_0398);
+
+        // Enough bytes to need several reductions.
+        let mut adler = Adler32::new();
+        adler.update(&[0xff; 100_000]);
+        assert_eq!(adler.finish(), 0x149a_302c);
+    }
+}
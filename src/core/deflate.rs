@@ -0,0 +1,1034 @@
+//! DEFLATE compression as specified in RFC 1951, wrapped in the gzip (RFC 1952) or zlib
+//! (RFC 1950) format.
+//!
+//! The input is compressed in chunks of up to [`CHUNK_SIZE`] bytes, and each chunk becomes one
+//! block. Matches are found with hash chains and lazy matching, and each block is written with
+//! whichever of dynamic Huffman codes, the fixed codes, or no compression is smallest.
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    io::{self, Read},
+};
+
+use super::checksum::{Adler32, Crc32};
+
+/// How much input is compressed into each block. A stored block can hold at most 65535 bytes.
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// How far back a match may refer.
+pub(super) const WINDOW_SIZE: usize = 32 * 1024;
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// How many earlier positions to try when looking for the longest match.
+const MAX_CHAIN: usize = 128;
+
+/// A match at least this long is taken at once, rather than checking whether the next position
+/// has a longer one.
+const LAZY_THRESHOLD: usize = 32;
+
+const HASH_BITS: u32 = 15;
+const NO_POSITION: usize = usize::MAX;
+
+/// The number of literal/length and distance symbols.
+const NUM_LITERAL_LENGTHS: usize = 286;
+const NUM_DISTANCES: usize = 30;
+const END_OF_BLOCK: usize = 256;
+
+/// The longest code allowed for the literal/length and distance alphabets, and for the alphabet
+/// which encodes their code lengths.
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+
+/// The order in which the code length code lengths are sent.
+pub(super) const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub(super) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(super) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+pub(super) const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(super) const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The container around the compressed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// The gzip format, sent as `Content-Encoding: gzip`.
+    Gzip,
+    /// The zlib format, which is what HTTP calls `deflate`.
+    Zlib,
+}
+
+/// Compress all of `data` at once.
+pub(crate) fn compress(format: Format, data: &[u8]) -> Vec<u8> {
+    let mut output = vec![];
+    // Reading from a slice cannot fail.
+    let _ = Encoder::new(data, format).read_to_end(&mut output);
+    output
+}
+
+/// A reader which compresses everything read from `reader`.
+pub(crate) struct Encoder<R> {
+    reader: R,
+    format: Format,
+    /// The last [`WINDOW_SIZE`] bytes of input, which the next chunk may refer back to.
+    window: Vec<u8>,
+    crc: Crc32,
+    adler: Adler32,
+    /// The number of input bytes so far, modulo 2^32 as gzip requires.
+    input_len: u32,
+    writer: BitWriter,
+    /// Compressed bytes waiting to be read, and how many of them have been.
+    output: Vec<u8>,
+    output_pos: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl<R: Read> Encoder<R> {
+    pub(crate) fn new(reader: R, format: Format) -> Self {
+        Self {
+            reader,
+            format,
+            window: vec![],
+            crc: Crc32::new(),
+            adler: Adler32::new(),
+            input_len: 0,
+            writer: BitWriter::new(),
+            output: vec![],
+            output_pos: 0,
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// Read the next chunk of input, compress it, and queue up the output.
+    fn fill_output(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            match self.format {
+                // No file name, modification time or flags, and an unknown operating system.
+                Format::Gzip => self
+                    .writer
+                    .write_bytes(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255]),
+                Format::Zlib => self.writer.write_bytes(&[0x78, 0x9c]),
+            }
+        }
+
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let mut len = 0;
+        let mut at_end = false;
+        while len < CHUNK_SIZE {
+            match self.reader.read(&mut chunk[len..]) {
+                Ok(0) => {
+                    at_end = true;
+                    break;
+                }
+                Ok(num_bytes) => len += num_bytes,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        chunk.truncate(len);
+
+        self.crc.update(&chunk);
+        self.adler.update(&chunk);
+        self.input_len = self.input_len.wrapping_add(len as u32);
+
+        let history = self.window.len();
+        self.window.extend_from_slice(&chunk);
+        let tokens = find_matches(&self.window, history);
+        write_block(&mut self.writer, &tokens, &chunk, at_end);
+        let keep_from = self.window.len().saturating_sub(WINDOW_SIZE);
+        self.window.drain(..keep_from);
+
+        if at_end {
+            self.finished = true;
+            self.writer.align();
+            match self.format {
+                Format::Gzip => {
+                    self.writer.write_bytes(&self.crc.finish().to_le_bytes());
+                    self.writer.write_bytes(&self.input_len.to_le_bytes());
+                }
+                Format::Zlib => self.writer.write_bytes(&self.adler.finish().to_be_bytes()),
+            }
+        }
+
+        self.output = self.writer.take_bytes();
+        self.output_pos = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Encoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.output_pos == self.output.len() {
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            self.fill_output()?;
+        }
+        let num_bytes = buf.len().min(self.output.len() - self.output_pos);
+        buf[..num_bytes].copy_from_slice(&self.output[self.output_pos..][..num_bytes]);
+        self.output_pos += num_bytes;
+        Ok(num_bytes)
+    }
+}
+
+/// Writes values least significant bit first, as DEFLATE packs them.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    num_bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: vec![],
+            bits: 0,
+            num_bits: 0,
+        }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u8) {
+        self.bits |= u64::from(value) << self.num_bits;
+        self.num_bits += u32::from(count);
+        while self.num_bits >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.num_bits -= 8;
+        }
+    }
+
+    /// Pad with zeros up to the next byte boundary.
+    fn align(&mut self) {
+        if self.num_bits > 0 {
+            self.write_bits(0, (8 - self.num_bits) as u8);
+        }
+    }
+
+    /// Write whole bytes, which must start on a byte boundary.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        debug_assert_eq!(self.num_bits, 0);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Take the bytes written so far, leaving any bits of an unfinished byte behind.
+    fn take_bytes(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+/// A piece of the compressed stream: a byte sent as it is, or a copy of earlier output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Literal(u8),
+    Match { len: u16, distance: u16 },
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value =
+        (usize::from(bytes[0]) << 10) ^ (usize::from(bytes[1]) << 5) ^ usize::from(bytes[2]);
+    value & ((1 << HASH_BITS) - 1)
+}
+
+/// Hash chains over a buffer, linking each position to the previous one with the same next three
+/// bytes.
+struct Chains<'a> {
+    buf: &'a [u8],
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl<'a> Chains<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self {
+            buf,
+            head: vec![NO_POSITION; 1 << HASH_BITS],
+            prev: vec![NO_POSITION; buf.len()],
+        }
+    }
+
+    fn insert(&mut self, pos: usize) {
+        if pos + MIN_MATCH <= self.buf.len() {
+            let hash = hash(&self.buf[pos..]);
+            self.prev[pos] = self.head[hash];
+            self.head[hash] = pos;
+        }
+    }
+
+    /// Find the longest earlier match for the bytes at `pos`, returning its length and distance.
+    /// `pos` must not have been inserted yet.
+    fn longest_match(&self, pos: usize) -> (usize, usize) {
+        let max_len = MAX_MATCH.min(self.buf.len() - pos);
+        if max_len < MIN_MATCH {
+            return (0, 0);
+        }
+
+        let mut best = (0, 0);
+        let mut candidate = self.head[hash(&self.buf[pos..])];
+        let mut chain = MAX_CHAIN;
+        while candidate != NO_POSITION && chain > 0 {
+            let distance = pos - candidate;
+            if distance > WINDOW_SIZE {
+                break;
+            }
+            // Only compare the whole match if it could beat the best so far.
+            if self.buf[candidate + best.0] == self.buf[pos + best.0] {
+                let len = self.buf[candidate..]
+                    .iter()
+                    .zip(&self.buf[pos..pos + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, distance);
+                    if len == max_len {
+                        break;
+                    }
+                }
+            }
+            candidate = self.prev[candidate];
+            chain -= 1;
+        }
+
+        if best.0 >= MIN_MATCH {
+            best
+        } else {
+            (0, 0)
+        }
+    }
+}
+
+/// Turn `buf[start..]` into tokens, allowing matches to refer back into `buf[..start]`.
+fn find_matches(buf: &[u8], start: usize) -> Vec<Token> {
+    let mut chains = Chains::new(buf);
+    for pos in start.saturating_sub(WINDOW_SIZE)..start {
+        chains.insert(pos);
+    }
+
+    let mut tokens = vec![];
+    // A match found at the previous position, which is only taken if this one is no better.
+    let mut pending: Option<(usize, usize)> = None;
+    let mut pos = start;
+    while pos < buf.len() {
+        let found = chains.longest_match(pos);
+        chains.insert(pos);
+
+        if let Some((len, distance)) = pending.take() {
+            if found.0 <= len {
+                tokens.push(match_token(len, distance));
+                // The match started at `pos - 1`, and `pos` is already in the chains.
+                let end = pos - 1 + len;
+                for skipped in pos + 1..end {
+                    chains.insert(skipped);
+                }
+                pos = end;
+                continue;
+            }
+            tokens.push(Token::Literal(buf[pos - 1]));
+        }
+
+        if found.0 >= LAZY_THRESHOLD {
+            tokens.push(match_token(found.0, found.1));
+            let end = pos + found.0;
+            for skipped in pos + 1..end {
+                chains.insert(skipped);
+            }
+            pos = end;
+            continue;
+        }
+
+        if found.0 >= MIN_MATCH {
+            pending = Some(found);
+        } else {
+            tokens.push(Token::Literal(buf[pos]));
+        }
+        pos += 1;
+    }
+    if let Some((len, distance)) = pending {
+        tokens.push(match_token(len, distance));
+    }
+    tokens
+}
+
+fn match_token(len: usize, distance: usize) -> Token {
+    Token::Match {
+        len: len as u16,
+        distance: distance as u16,
+    }
+}
+
+/// Return the index into `bases` of the code for `value`, which is the last base not above it.
+fn code_index(bases: &[u16], value: u16) -> usize {
+    bases.partition_point(|&base| base <= value) - 1
+}
+
+/// A canonical Huffman code, with each code stored bit-reversed ready to be written.
+struct HuffmanCode {
+    lengths: Vec<u8>,
+    codes: Vec<u16>,
+}
+
+impl HuffmanCode {
+    fn from_lengths(lengths: Vec<u8>) -> Self {
+        let mut count = [0u16; MAX_CODE_LENGTH as usize + 1];
+        for &len in &lengths {
+            count[usize::from(len)] += 1;
+        }
+        count[0] = 0;
+
+        let mut next_code = [0u16; MAX_CODE_LENGTH as usize + 2];
+        let mut code = 0;
+        for len in 1..=MAX_CODE_LENGTH as usize {
+            code = (code + count[len - 1]) << 1;
+            next_code[len] = code;
+        }
+
+        let codes = lengths
+            .iter()
+            .map(|&len| {
+                if len == 0 {
+                    return 0;
+                }
+                let code = next_code[usize::from(len)];
+                next_code[usize::from(len)] += 1;
+                code.reverse_bits() >> (16 - len)
+            })
+            .collect();
+        Self { lengths, codes }
+    }
+
+    fn fixed_literal_lengths() -> Self {
+        let lengths = (0..288)
+            .map(|symbol| match symbol {
+                0..=143 => 8,
+                144..=255 => 9,
+                256..=279 => 7,
+                _ => 8,
+            })
+            .collect();
+        Self::from_lengths(lengths)
+    }
+
+    fn fixed_distances() -> Self {
+        Self::from_lengths(vec![5; 30])
+    }
+
+    fn write(&self, writer: &mut BitWriter, symbol: usize) {
+        writer.write_bits(u32::from(self.codes[symbol]), self.lengths[symbol]);
+    }
+
+    /// The number of bits needed to write symbols with these frequencies.
+    fn cost(&self, frequencies: &[u32]) -> u64 {
+        frequencies
+            .iter()
+            .zip(&self.lengths)
+            .map(|(&frequency, &len)| u64::from(frequency) * u64::from(len))
+            .sum()
+    }
+}
+
+/// Choose code lengths of at most `max_len` bits for symbols with the given frequencies.
+///
+/// The lengths come from a Huffman tree. Any which are too long are cut down to `max_len`, and
+/// then other codes are lengthened or shortened until the code is complete, since decoders reject
+/// codes which do not use every bit pattern.
+fn code_lengths(frequencies: &[u32], max_len: u8) -> Vec<u8> {
+    let mut frequencies = frequencies.to_vec();
+    // A code needs at least two symbols to be complete, even if only one of them is ever used.
+    for symbol in 0..frequencies.len() {
+        if frequencies.iter().filter(|&&f| f > 0).count() >= 2 {
+            break;
+        }
+        if frequencies[symbol] == 0 {
+            frequencies[symbol] = 1;
+        }
+    }
+
+    // Build the tree, recording the parent of each node. Leaves come first.
+    let symbols: Vec<usize> = (0..frequencies.len())
+        .filter(|&symbol| frequencies[symbol] > 0)
+        .collect();
+    let mut parents = vec![0; symbols.len() * 2 - 1];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> = symbols
+        .iter()
+        .enumerate()
+        .map(|(node, &symbol)| Reverse((u64::from(frequencies[symbol]), node)))
+        .collect();
+    let mut next_node = symbols.len();
+    while let (Some(Reverse((w1, n1))), Some(Reverse((w2, n2)))) = (heap.pop(), heap.pop()) {
+        parents[n1] = next_node;
+        parents[n2] = next_node;
+        heap.push(Reverse((w1 + w2, next_node)));
+        next_node += 1;
+    }
+
+    // Parents are always created after their children, so walk down from the root.
+    let root = next_node - 1;
+    let mut depths = vec![0u32; next_node];
+    for node in (0..root).rev() {
+        depths[node] = depths[parents[node]] + 1;
+    }
+
+    let mut lengths = vec![0u8; frequencies.len()];
+    for (node, &symbol) in symbols.iter().enumerate() {
+        lengths[symbol] = depths[node].min(u32::from(max_len)) as u8;
+    }
+
+    // The Kraft sum of a complete code, measured in units of the longest code.
+    let capacity = 1u64 << max_len;
+    let weight = |len: u8| 1u64 << (max_len - len);
+    let mut kraft: u64 = symbols.iter().map(|&symbol| weight(lengths[symbol])).sum();
+    while kraft > capacity {
+        // Lengthen the longest code which can still grow, picking the rarest symbol on a tie.
+        let &symbol = symbols
+            .iter()
+            .filter(|&&symbol| lengths[symbol] < max_len)
+            .max_by_key(|&&symbol| (lengths[symbol], Reverse(frequencies[symbol])))
+            .expect("A code of max_len bits always has room for the symbols");
+        lengths[symbol] += 1;
+        kraft -= weight(lengths[symbol]);
+    }
+    while kraft < capacity {
+        // Shorten the longest code, picking the most common symbol on a tie. The shortfall is
+        // always a multiple of its weight, so it never overshoots.
+        let &symbol = symbols
+            .iter()
+            .max_by_key(|&&symbol| (lengths[symbol], frequencies[symbol]))
+            .expect("There are at least two symbols");
+        kraft += weight(lengths[symbol]);
+        lengths[symbol] -= 1;
+    }
+    lengths
+}
+
+/// Run-length encode the code lengths of a dynamic block, returning each code length symbol with
+/// the value of its extra bits.
+fn encode_code_lengths(lengths: &[u8]) -> Vec<(u8, u8)> {
+    let mut symbols = vec![];
+    let mut pos = 0;
+    while pos < lengths.len() {
+        let len = lengths[pos];
+        let run = lengths[pos..].iter().take_while(|&&l| l == len).count();
+        pos += run;
+
+        let mut remaining = run;
+        if len == 0 {
+            while remaining >= 11 {
+                let count = remaining.min(138);
+                symbols.push((18, (count - 11) as u8));
+                remaining -= count;
+            }
+            if remaining >= 3 {
+                symbols.push((17, (remaining - 3) as u8));
+                remaining = 0;
+            }
+        } else {
+            symbols.push((len, 0));
+            remaining -= 1;
+            while remaining >= 3 {
+                let count = remaining.min(6);
+                symbols.push((16, (count - 3) as u8));
+                remaining -= count;
+            }
+        }
+        symbols.extend(std::iter::repeat_n((len, 0), remaining));
+    }
+    symbols
+}
+
+/// The number of extra bits after each code length symbol.
+fn code_length_extra_bits(symbol: u8) -> u8 {
+    match symbol {
+        16 => 2,
+        17 => 3,
+        18 => 7,
+        _ => 0,
+    }
+}
+
+/// The codes and header for a block with dynamic Huffman codes.
+struct DynamicCodes {
+    literals: HuffmanCode,
+    distances: HuffmanCode,
+    code_lengths: HuffmanCode,
+    encoded_lengths: Vec<(u8, u8)>,
+    num_literals: usize,
+    num_distances: usize,
+    num_code_lengths: usize,
+}
+
+impl DynamicCodes {
+    fn new(literal_frequencies: &[u32], distance_frequencies: &[u32]) -> Self {
+        let literal_lengths = code_lengths(literal_frequencies, MAX_CODE_LENGTH);
+        let distance_lengths = code_lengths(distance_frequencies, MAX_CODE_LENGTH);
+        let num_literals = 257.max(last_used(&literal_lengths));
+        let num_distances = 1.max(last_used(&distance_lengths));
+
+        let mut all_lengths = literal_lengths[..num_literals].to_vec();
+        all_lengths.extend_from_slice(&distance_lengths[..num_distances]);
+        let encoded_lengths = encode_code_lengths(&all_lengths);
+
+        let mut code_length_frequencies = [0u32; 19];
+        for &(symbol, _) in &encoded_lengths {
+            code_length_frequencies[usize::from(symbol)] += 1;
+        }
+        let code_length_lengths =
+            code_lengths(&code_length_frequencies, MAX_CODE_LENGTH_CODE_LENGTH);
+        let num_code_lengths = 4.max(
+            CODE_LENGTH_ORDER
+                .iter()
+                .rposition(|&symbol| code_length_lengths[symbol] > 0)
+                .map_or(0, |index| index + 1),
+        );
+
+        Self {
+            literals: HuffmanCode::from_lengths(literal_lengths),
+            distances: HuffmanCode::from_lengths(distance_lengths),
+            code_lengths: HuffmanCode::from_lengths(code_length_lengths),
+            encoded_lengths,
+            num_literals,
+            num_distances,
+            num_code_lengths,
+        }
+    }
+
+    /// The number of bits in the header which describes the codes.
+    fn header_cost(&self) -> u64 {
+        let lengths: u64 = self
+            .encoded_lengths
+            .iter()
+            .map(|&(symbol, _)| {
+                u64::from(self.code_lengths.lengths[usize::from(symbol)])
+                    + u64::from(code_length_extra_bits(symbol))
+            })
+            .sum();
+        5 + 5 + 4 + 3 * self.num_code_lengths as u64 + lengths
+    }
+
+    fn write_header(&self, writer: &mut BitWriter) {
+        writer.write_bits((self.num_literals - 257) as u32, 5);
+        writer.write_bits((self.num_distances - 1) as u32, 5);
+        writer.write_bits((self.num_code_lengths - 4) as u32, 4);
+        for &symbol in &CODE_LENGTH_ORDER[..self.num_code_lengths] {
+            writer.write_bits(u32::from(self.code_lengths.lengths[symbol]), 3);
+        }
+        for &(symbol, extra) in &self.encoded_lengths {
+            self.code_lengths.write(writer, usize::from(symbol));
+            writer.write_bits(u32::from(extra), code_length_extra_bits(symbol));
+        }
+    }
+}
+
+/// Return one past the index of the last non-zero length.
+fn last_used(lengths: &[u8]) -> usize {
+    lengths
+        .iter()
+        .rposition(|&len| len > 0)
+        .map_or(0, |index| index + 1)
+}
+
+/// Write `tokens` as one block, using whichever encoding is smallest. `raw` is the input the
+/// tokens came from, for when storing it uncompressed is smallest.
+fn write_block(writer: &mut BitWriter, tokens: &[Token], raw: &[u8], is_final: bool) {
+    let mut literal_frequencies = [0u32; NUM_LITERAL_LENGTHS];
+    let mut distance_frequencies = [0u32; NUM_DISTANCES];
+    let mut extra_bits = 0u64;
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => literal_frequencies[usize::from(byte)] += 1,
+            Token::Match { len, distance } => {
+                let len_index = code_index(&LENGTH_BASE, len);
+                let distance_index = code_index(&DISTANCE_BASE, distance);
+                literal_frequencies[257 + len_index] += 1;
+                distance_frequencies[distance_index] += 1;
+                extra_bits +=
+                    u64::from(LENGTH_EXTRA[len_index]) + u64::from(DISTANCE_EXTRA[distance_index]);
+            }
+        }
+    }
+    literal_frequencies[END_OF_BLOCK] = 1;
+
+    let fixed_literals = HuffmanCode::fixed_literal_lengths();
+    let fixed_distances = HuffmanCode::fixed_distances();
+    let fixed_cost = fixed_literals.cost(&literal_frequencies)
+        + fixed_distances.cost(&distance_frequencies)
+        + extra_bits;
+
+    let dynamic = DynamicCodes::new(&literal_frequencies, &distance_frequencies);
+    let dynamic_cost = dynamic.header_cost()
+        + dynamic.literals.cost(&literal_frequencies)
+        + dynamic.distances.cost(&distance_frequencies)
+        + extra_bits;
+
+    // A stored block is padded to a byte boundary, then has a four byte length.
+    let stored_cost = 7 + 32 + 8 * raw.len() as u64;
+
+    writer.write_bits(u32::from(is_final), 1);
+    if stored_cost < fixed_cost.min(dynamic_cost) {
+        writer.write_bits(0, 2);
+        writer.align();
+        let len = raw.len() as u16;
+        writer.write_bytes(&len.to_le_bytes());
+        writer.write_bytes(&(!len).to_le_bytes());
+        writer.write_bytes(raw);
+    } else if fixed_cost <= dynamic_cost {
+        writer.write_bits(1, 2);
+        write_tokens(writer, tokens, &fixed_literals, &fixed_distances);
+    } else {
+        writer.write_bits(2, 2);
+        dynamic.write_header(writer);
+        write_tokens(writer, tokens, &dynamic.literals, &dynamic.distances);
+    }
+}
+
+fn write_tokens(
+    writer: &mut BitWriter,
+    tokens: &[Token],
+    literals: &HuffmanCode,
+    distances: &HuffmanCode,
+) {
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => literals.write(writer, usize::from(byte)),
+            Token::Match { len, distance } => {
+                let len_index = code_index(&LENGTH_BASE, len);
+                literals.write(writer, 257 + len_index);
+                writer.write_bits(
+                    u32::from(len - LENGTH_BASE[len_index]),
+                    LENGTH_EXTRA[len_index],
+                );
+
+                let distance_index = code_index(&DISTANCE_BASE, distance);
+                distances.write(writer, distance_index);
+                writer.write_bits(
+                    u32::from(distance - DISTANCE_BASE[distance_index]),
+                    DISTANCE_EXTRA[distance_index],
+                );
+            }
+        }
+    }
+    literals.write(writer, END_OF_BLOCK);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::core::inflate;
+
+    /// Reads bits least significant first, for the reference decoder.
+    struct BitReader<'a> {
+        data: &'a [u8],
+        pos: usize,
+        bit: u8,
+    }
+
+    impl BitReader<'_> {
+        fn bit(&mut self) -> u32 {
+            let byte = self.data[self.pos];
+            let bit = (byte >> self.bit) & 1;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+            u32::from(bit)
+        }
+
+        fn bits(&mut self, count: u8) -> u32 {
+            (0..count).fold(0, |value, i| value | (self.bit() << i))
+        }
+
+        fn align(&mut self) {
+            if self.bit > 0 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// A canonical Huffman code for decoding, as the number of codes of each length and the
+    /// symbols in code order.
+    struct Decoder {
+        counts: [u16; 16],
+        symbols: Vec<u16>,
+    }
+
+    impl Decoder {
+        fn new(lengths: &[u8]) -> Self {
+            let mut counts = [0; 16];
+            for &len in lengths {
+                counts[usize::from(len)] += 1;
+            }
+            counts[0] = 0;
+            let mut symbols = vec![];
+            for len in 1..16 {
+                for (symbol, &l) in lengths.iter().enumerate() {
+                    if usize::from(l) == len {
+                        symbols.push(symbol as u16);
+                    }
+                }
+            }
+            Self { counts, symbols }
+        }
+
+        fn decode(&self, reader: &mut BitReader<'_>) -> usize {
+            let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+            for len in 1..16 {
+                code |= reader.bit() as i32;
+                let count = i32::from(self.counts[len]);
+                if code - count < first {
+                    return usize::from(self.symbols[(index + code - first) as usize]);
+                }
+                index += count;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+            panic!("Invalid Huffman code");
+        }
+    }
+
+    /// A simple, independent DEFLATE decoder written from RFC 1951, which panics on any error.
+    pub(crate) fn inflate(data: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader {
+            data,
+            pos: 0,
+            bit: 0,
+        };
+        let mut output: Vec<u8> = vec![];
+        loop {
+            let is_final = reader.bit() == 1;
+            match reader.bits(2) {
+                0 => {
+                    reader.align();
+                    let len =
+                        usize::from(u16::from_le_bytes([data[reader.pos], data[reader.pos + 1]]));
+                    let nlen = u16::from_le_bytes([data[reader.pos + 2], data[reader.pos + 3]]);
+                    assert_eq!(len as u16, !nlen);
+                    reader.pos += 4;
+                    output.extend_from_slice(&data[reader.pos..reader.pos + len]);
+                    reader.pos += len;
+                }
+                kind @ (1 | 2) => {
+                    let (literals, distances) = if kind == 1 {
+                        let mut lengths = vec![8; 144];
+                        lengths.extend([9; 112]);
+                        lengths.extend([7; 24]);
+                        lengths.extend([8; 8]);
+                        (Decoder::new(&lengths), Decoder::new(&[5; 30]))
+                    } else {
+                        read_dynamic_codes(&mut reader)
+                    };
+                    loop {
+                        let symbol = literals.decode(&mut reader);
+                        if symbol < 256 {
+                            output.push(symbol as u8);
+                        } else if symbol == 256 {
+                            break;
+                        } else {
+                            let index = symbol - 257;
+                            let len = usize::from(LENGTH_BASE[index])
+                                + reader.bits(LENGTH_EXTRA[index]) as usize;
+                            let index = distances.decode(&mut reader);
+                            let distance = usize::from(DISTANCE_BASE[index])
+                                + reader.bits(DISTANCE_EXTRA[index]) as usize;
+                            let start = output.len() - distance;
+                            for i in 0..len {
+                                output.push(output[start + i]);
+                            }
+                        }
+                    }
+                }
+                _ => panic!("Invalid block type"),
+            }
+            if is_final {
+                return output;
+            }
+        }
+    }
+
+    fn read_dynamic_codes(reader: &mut BitReader<'_>) -> (Decoder, Decoder) {
+        let num_literals = reader.bits(5) as usize + 257;
+        let num_distances = reader.bits(5) as usize + 1;
+        let num_code_lengths = reader.bits(4) as usize + 4;
+        let mut code_length_lengths = [0; 19];
+        for &symbol in &CODE_LENGTH_ORDER[..num_code_lengths] {
+            code_length_lengths[symbol] = reader.bits(3) as u8;
+        }
+        let code_lengths = Decoder::new(&code_length_lengths);
+
+        let mut lengths: Vec<u8> = vec![];
+        while lengths.len() < num_literals + num_distances {
+            match code_lengths.decode(reader) {
+                16 => {
+                    let previous = *lengths.last().unwrap();
+                    let count = 3 + reader.bits(2) as usize;
+                    lengths.extend(std::iter::repeat_n(previous, count));
+                }
+                17 => lengths.extend(std::iter::repeat_n(0, 3 + reader.bits(3) as usize)),
+                18 => lengths.extend(std::iter::repeat_n(0, 11 + reader.bits(7) as usize)),
+                len => lengths.push(len as u8),
+            }
+        }
+        assert_eq!(lengths.len(), num_literals + num_distances);
+        (
+            Decoder::new(&lengths[..num_literals]),
+            Decoder::new(&lengths[num_literals..]),
+        )
+    }
+
+    /// Check the wrapper and checksum of compressed data, and return what it decompresses to.
+    pub(crate) fn decompress(format: Format, data: &[u8]) -> Vec<u8> {
+        match format {
+            Format::Gzip => {
+                assert_eq!(&data[..4], &[0x1f, 0x8b, 8, 0]);
+                let body = &data[10..data.len() - 8];
+                let output = inflate(body);
+                let trailer = &data[data.len() - 8..];
+                let mut crc = Crc32::new();
+                crc.update(&output);
+                assert_eq!(trailer[..4], crc.finish().to_le_bytes());
+                assert_eq!(trailer[4..], (output.len() as u32).to_le_bytes());
+                output
+            }
+            Format::Zlib => {
+                assert_eq!((u16::from(data[0]) << 8 | u16::from(data[1])) % 31, 0);
+                let output = inflate(&data[2..data.len() - 4]);
+                let mut adler = Adler32::new();
+                adler.update(&output);
+                assert_eq!(data[data.len() - 4..], adler.finish().to_be_bytes());
+                output
+            }
+        }
+    }
+
+    /// Bytes which look a little like text, with repetition at a range of distances.
+    pub(crate) fn sample_text(len: usize) -> Vec<u8> {
+        let words = [
+            "cairo ",
+            "handler ",
+            "router ",
+            "layer ",
+            "request ",
+            "response\n",
+        ];
+        let mut state = 12345u32;
+        let mut text = vec![];
+        while text.len() < len {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            text.extend_from_slice(words[(state >> 16) as usize % words.len()].as_bytes());
+        }
+        text.truncate(len);
+        text
+    }
+
+    /// Bytes with no structure to find, which cannot be compressed.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 99u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut cases = vec![
+            vec![],
+            b"a".to_vec(),
+            b"abcabcabcabcabcabc".to_vec(),
+            vec![0; 100_000],
+            sample_text(200_000),
+            noise(70_000),
+        ];
+        // Every byte value, so the whole literal alphabet is needed.
+        cases.push((0..=255).cycle().take(1000).collect());
+        for data in cases {
+            for format in [Format::Gzip, Format::Zlib] {
+                let compressed = compress(format, &data);
+                assert_eq!(
+                    decompress(format, &compressed),
+                    data,
+                    "{} bytes",
+                    data.len()
+                );
+
+                // The crate's own decoder must agree with the reference one.
+                let mut inflated = vec![];
+                inflate::Decoder::new(&compressed[..], format)
+                    .read_to_end(&mut inflated)
+                    .unwrap();
+                assert_eq!(inflated, data, "{} bytes", data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_compresses() {
+        let text = sample_text(100_000);
+        assert!(compress(Format::Gzip, &text).len() < text.len() / 4);
+        assert!(compress(Format::Gzip, &vec![b'x'; 100_000]).len() < 500);
+
+        // Data which does not compress is stored, so it barely grows.
+        let noise = noise(100_000);
+        assert!(compress(Format::Zlib, &noise).len() < noise.len() + 100);
+    }
+
+    #[test]
+    fn test_encoder_reads_in_small_pieces() {
+        let text = sample_text(100_000);
+        let mut encoder = Encoder::new(&text[..], Format::Gzip);
+        let mut compressed = vec![];
+        let mut buf = [0; 7];
+        loop {
+            let num_bytes = encoder.read(&mut buf).unwrap();
+            if num_bytes == 0 {
+                break;
+            }
+            compressed.extend_from_slice(&buf[..num_bytes]);
+        }
+        assert_eq!(compressed, compress(Format::Gzip, &text));
+    }
+
+    #[test]
+    fn test_code_lengths() {
+        // A skewed distribution would give codes longer than allowed without the limit.
+        let frequencies: Vec<u32> = (0..30).map(|i| 1 << i.min(25)).collect();
+        let lengths = code_lengths(&frequencies, 7);
+        assert!(lengths.iter().all(|&len| (1..=7).contains(&len)));
+        let kraft: u32 = lengths.iter().map(|&len| 1 << (7 - len)).sum();
+        assert_eq!(kraft, 1 << 7);
+
+        // A single symbol still gets a complete code.
+        let lengths = code_lengths(&[0, 5, 0], 15);
+        assert_eq!(lengths, vec![1, 1, 0]);
+    }
+
+    #[test]
+    fn test_encode_code_lengths() {
+        let mut lengths = vec![8; 10];
+        lengths.extend([0; 150]);
+        lengths.extend([5, 5]);
+        assert_eq!(
+            encode_code_lengths(&lengths),
+            vec![(8, 0), (16, 3), (16, 0), (18, 127), (18, 1), (5, 0), (5, 0)]
+        );
+    }
+}
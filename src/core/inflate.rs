@@ -0,0 +1,557 @@
+//! DEFLATE decompression as specified in RFC 1951, of data in the gzip (RFC 1952) or zlib
+//! (RFC 1950) format.
+//!
+//! The [`Decoder`] is a reader which pulls compressed bytes from another reader only as fast as
+//! its output is read, so a large body never has to be held in memory.
+use std::io::{self, Read};
+
+use super::{
+    checksum::{Adler32, Crc32},
+    deflate::{
+        Format, CODE_LENGTH_ORDER, DISTANCE_BASE, DISTANCE_EXTRA, LENGTH_BASE, LENGTH_EXTRA,
+        WINDOW_SIZE,
+    },
+};
+
+/// The size of the buffer for compressed input.
+const INPUT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Output below this size is never rejected for its compression ratio, since any short, repetitive
+/// body can have a high ratio without being a threat.
+const RATIO_GRACE: u64 = 1024 * 1024;
+
+const MAX_BITS: usize = 15;
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads values least significant bit first, as DEFLATE packs them.
+struct BitReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    pos: usize,
+    bits: u64,
+    num_bits: u32,
+    /// The number of bytes taken from `reader` so far.
+    consumed: u64,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: vec![],
+            pos: 0,
+            bits: 0,
+            num_bits: 0,
+            consumed: 0,
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<u8> {
+        if self.pos == self.buffer.len() {
+            self.buffer.resize(INPUT_BUFFER_SIZE, 0);
+            let num_bytes = loop {
+                match self.reader.read(&mut self.buffer) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    result => break result?,
+                }
+            };
+            self.buffer.truncate(num_bytes);
+            self.pos = 0;
+            if num_bytes == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Compressed data ended early.",
+                ));
+            }
+        }
+        self.pos += 1;
+        self.consumed += 1;
+        Ok(self.buffer[self.pos - 1])
+    }
+
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.num_bits < count {
+            self.bits |= u64::from(self.next_byte()?) << self.num_bits;
+            self.num_bits += 8;
+        }
+        let value = (self.bits & ((1 << count) - 1)) as u32;
+        self.bits >>= count;
+        self.num_bits -= count;
+        Ok(value)
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.bits(8)? as u8)
+    }
+
+    /// Put back bytes which were read by mistake. Only valid before anything else is buffered.
+    fn unread(&mut self, bytes: &[u8]) {
+        debug_assert_eq!(self.num_bits, 0);
+        for &byte in bytes.iter().rev() {
+            self.bits = (self.bits << 8) | u64::from(byte);
+            self.num_bits += 8;
+        }
+    }
+
+    /// Skip to the next byte boundary.
+    fn align(&mut self) {
+        let skip = self.num_bits % 8;
+        self.bits >>= skip;
+        self.num_bits -= skip;
+    }
+}
+
+/// A canonical Huffman code for decoding, as the number of codes of each length and the symbols
+/// in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[usize::from(len)] += 1;
+        }
+        counts[0] = 0;
+
+        // Check that no bit pattern is claimed by more than one code.
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(invalid("Invalid Huffman code lengths."));
+            }
+        }
+
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for len in 1..=MAX_BITS as u8 {
+            for (symbol, &l) in lengths.iter().enumerate() {
+                if l == len {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> io::Result<usize> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - count < first {
+                return Ok(usize::from(self.symbols[(index + code - first) as usize]));
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("Invalid Huffman code."))
+    }
+
+    fn fixed() -> (Self, Self) {
+        let mut lengths = [8; 288];
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        // These lengths are valid, so building the codes cannot fail.
+        let literals = Self::new(&lengths).unwrap_or_else(|_| unreachable!());
+        let distances = Self::new(&[5; 30]).unwrap_or_else(|_| unreachable!());
+        (literals, distances)
+    }
+}
+
+/// Where the decoder is in the stream.
+enum State {
+    Header,
+    /// Between blocks. `last` is set once the final block has been read.
+    BlockStart {
+        last: bool,
+    },
+    Stored {
+        remaining: usize,
+        last: bool,
+    },
+    Huffman {
+        literals: Huffman,
+        distances: Huffman,
+        last: bool,
+    },
+    Done,
+}
+
+/// A reader which decompresses everything read from `reader`.
+pub(crate) struct Decoder<R> {
+    input: BitReader<R>,
+    format: Format,
+    /// Set when the `deflate` coding turned out to be raw DEFLATE without the zlib wrapper, which
+    /// some clients send.
+    raw: bool,
+    state: State,
+    /// The decompressed output. Bytes before `returned` have been read, and only the last
+    /// [`WINDOW_SIZE`] of those are kept for back-references.
+    history: Vec<u8>,
+    returned: usize,
+    crc: Crc32,
+    adler: Adler32,
+    total_out: u64,
+    max_ratio: Option<u64>,
+}
+
+impl<R: Read> Decoder<R> {
+    pub(crate) fn new(reader: R, format: Format) -> Self {
+        Self {
+            input: BitReader::new(reader),
+            format,
+            raw: false,
+            state: State::Header,
+            history: vec![],
+            returned: 0,
+            crc: Crc32::new(),
+            adler: Adler32::new(),
+            total_out: 0,
+            max_ratio: None,
+        }
+    }
+
+    /// Fail once the output is more than `max_ratio` times the size of the input consumed, after
+    /// the first megabyte. This stops a small body from expanding to fill the memory or disk of
+    /// whatever reads it.
+    pub(crate) fn with_max_ratio(mut self, max_ratio: u64) -> Self {
+        self.max_ratio = Some(max_ratio);
+        self
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        match self.format {
+            Format::Gzip => {
+                let mut header = [0; 10];
+                for byte in &mut header {
+                    *byte = self.input.byte()?;
+                }
+                if header[..3] != [0x1f, 0x8b, 8] {
+                    return Err(invalid("Invalid gzip header."));
+                }
+                let flags = header[3];
+                if flags & 0x04 != 0 {
+                    let len = u16::from_le_bytes([self.input.byte()?, self.input.byte()?]);
+                    for _ in 0..len {
+                        self.input.byte()?;
+                    }
+                }
+                // The file name and comment are each terminated by a zero byte.
+                for flag in [0x08, 0x10] {
+                    if flags & flag != 0 {
+                        while self.input.byte()? != 0 {}
+                    }
+                }
+                if flags & 0x02 != 0 {
+                    self.input.bits(16)?;
+                }
+            }
+            Format::Zlib => {
+                let header = [self.input.byte()?, self.input.byte()?];
+                let is_zlib = header[0] & 0x0f == 8
+                    && (u16::from(header[0]) << 8 | u16::from(header[1])).is_multiple_of(31);
+                if !is_zlib {
+                    self.raw = true;
+                    self.input.unread(&header);
+                } else if header[1] & 0x20 != 0 {
+                    return Err(invalid("Preset zlib dictionaries are not supported."));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_trailer(&mut self) -> io::Result<()> {
+        self.input.align();
+        let mut word = || -> io::Result<[u8; 4]> {
+            Ok([
+                self.input.byte()?,
+                self.input.byte()?,
+                self.input.byte()?,
+                self.input.byte()?,
+            ])
+        };
+        let valid = match self.format {
+            Format::Gzip => {
+                let crc = u32::from_le_bytes(word()?);
+                let len = u32::from_le_bytes(word()?);
+                crc == self.crc.finish() && len == self.total_out as u32
+            }
+            Format::Zlib if self.raw => true,
+            Format::Zlib => u32::from_be_bytes(word()?) == self.adler.finish(),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(invalid("Compressed data failed its checksum."))
+        }
+    }
+
+    fn read_dynamic_codes(&mut self) -> io::Result<(Huffman, Huffman)> {
+        let num_literals = self.input.bits(5)? as usize + 257;
+        let num_distances = self.input.bits(5)? as usize + 1;
+        let num_code_lengths = self.input.bits(4)? as usize + 4;
+        if num_literals > 286 || num_distances > 30 {
+            return Err(invalid("Too many Huffman codes."));
+        }
+
+        let mut code_length_lengths = [0; 19];
+        for &symbol in &CODE_LENGTH_ORDER[..num_code_lengths] {
+            code_length_lengths[symbol] = self.input.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_lengths)?;
+
+        let total = num_literals + num_distances;
+        let mut lengths = Vec::with_capacity(total);
+        while lengths.len() < total {
+            let (len, count) = match code_lengths.decode(&mut self.input)? {
+                16 => {
+                    let previous = *lengths
+                        .last()
+                        .ok_or_else(|| invalid("Repeated a code length with none before it."))?;
+                    (previous, 3 + self.input.bits(2)?)
+                }
+                17 => (0, 3 + self.input.bits(3)?),
+                18 => (0, 11 + self.input.bits(7)?),
+                len => (len as u8, 1),
+            };
+            if lengths.len() + count as usize > total {
+                return Err(invalid("Too many code lengths."));
+            }
+            lengths.extend(std::iter::repeat_n(len, count as usize));
+        }
+
+        if lengths[256] == 0 {
+            return Err(invalid("Missing the end of block code."));
+        }
+        Ok((
+            Huffman::new(&lengths[..num_literals])?,
+            Huffman::new(&lengths[num_literals..])?,
+        ))
+    }
+
+    /// Add the output from `start` on to the checksums and the total.
+    fn account(&mut self, start: usize) {
+        let output = &self.history[start..];
+        self.crc.update(output);
+        self.adler.update(output);
+        self.total_out += output.len() as u64;
+    }
+
+    /// Decompress until `target` more bytes are in `history` or the stream ends.
+    fn decode(&mut self, target: usize) -> io::Result<()> {
+        let start = self.history.len();
+        let goal = start + target;
+        while self.history.len() < goal {
+            match &mut self.state {
+                State::Header => {
+                    self.read_header()?;
+                    self.state = State::BlockStart { last: false };
+                }
+                State::BlockStart { last: true } => {
+                    self.account(start);
+                    self.read_trailer()?;
+                    self.state = State::Done;
+                    return Ok(());
+                }
+                State::BlockStart { last: false } => {
+                    let last = self.input.bits(1)? == 1;
+                    self.state = match self.input.bits(2)? {
+                        0 => {
+                            self.input.align();
+                            let len = self.input.bits(16)?;
+                            let nlen = self.input.bits(16)?;
+                            if len != !nlen & 0xffff {
+                                return Err(invalid("Invalid stored block length."));
+                            }
+                            State::Stored {
+                                remaining: len as usize,
+                                last,
+                            }
+                        }
+                        1 => {
+                            let (literals, distances) = Huffman::fixed();
+                            State::Huffman {
+                                literals,
+                                distances,
+                                last,
+                            }
+                        }
+                        2 => {
+                            let (literals, distances) = self.read_dynamic_codes()?;
+                            State::Huffman {
+                                literals,
+                                distances,
+                                last,
+                            }
+                        }
+                        _ => return Err(invalid("Invalid block type.")),
+                    };
+                }
+                State::Stored { remaining: 0, last } => {
+                    self.state = State::BlockStart { last: *last };
+                }
+                State::Stored { remaining, .. } => {
+                    *remaining -= 1;
+                    let byte = self.input.byte()?;
+                    self.history.push(byte);
+                }
+                State::Huffman {
+                    literals,
+                    distances,
+                    last,
+                } => {
+                    let symbol = literals.decode(&mut self.input)?;
+                    match symbol {
+                        0..=255 => self.history.push(symbol as u8),
+                        256 => self.state = State::BlockStart { last: *last },
+                        257..=285 => {
+                            let index = symbol - 257;
+                            let len = usize::from(LENGTH_BASE[index])
+                                + self.input.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+                            let index = distances.decode(&mut self.input)?;
+                            if index >= DISTANCE_BASE.len() {
+                                return Err(invalid("Invalid distance code."));
+                            }
+                            let distance = usize::from(DISTANCE_BASE[index])
+                                + self.input.bits(u32::from(DISTANCE_EXTRA[index]))? as usize;
+                            if distance > self.history.len() {
+                                return Err(invalid("Distance is too far back."));
+                            }
+                            let start = self.history.len() - distance;
+                            for i in start..start + len {
+                                self.history.push(self.history[i]);
+                            }
+                        }
+                        _ => return Err(invalid("Invalid length code.")),
+                    }
+                }
+                State::Done => return Ok(()),
+            }
+        }
+        self.account(start);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.returned == self.history.len() {
+            // Everything has been read, so only the window needs to be kept.
+            let excess = self.history.len().saturating_sub(WINDOW_SIZE);
+            if excess >= WINDOW_SIZE {
+                self.history.drain(..excess);
+                self.returned -= excess;
+            }
+
+            self.decode(buf.len())?;
+
+            if let Some(max_ratio) = self.max_ratio {
+                let limit = self.input.consumed.saturating_mul(max_ratio);
+                if self.total_out > RATIO_GRACE && self.total_out > limit {
+                    return Err(invalid("Compressed data expands too much."));
+                }
+            }
+        }
+
+        let num_bytes = buf.len().min(self.history.len() - self.returned);
+        buf[..num_bytes].copy_from_slice(&self.history[self.returned..][..num_bytes]);
+        self.returned += num_bytes;
+        Ok(num_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::deflate::{compress, tests::sample_text};
+
+    fn decompress(format: Format, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = vec![];
+        Decoder::new(data, format).read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn test_decoder_round_trip() {
+        let cases = [
+            vec![],
+            b"Hello, World!".to_vec(),
+            sample_text(300_000),
+            (0..=255).cycle().take(70_000).collect(),
+        ];
+        for data in cases {
+            for format in [Format::Gzip, Format::Zlib] {
+                let compressed = compress(format, &data);
+                assert_eq!(decompress(format, &compressed).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decoder_reference_streams() {
+        // `gzip.compress` with a file name, made by CPython's zlib.
+        let gzip = [
+            0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x61, 0x2e, 0x74, 0x78,
+            0x74, 0x00, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00, 0x3b,
+            0x7c, 0x8a, 0xdf, 0x12, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            decompress(Format::Gzip, &gzip).unwrap(),
+            b"hello hello hello\n"
+        );
+
+        // Raw DEFLATE, which some clients send for the `deflate` coding.
+        let raw = [
+            0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x5c, 0x00,
+        ];
+        assert_eq!(
+            decompress(Format::Zlib, &raw).unwrap(),
+            b"hello hello hello\n"
+        );
+    }
+
+    #[test]
+    fn test_decoder_rejects_corruption() {
+        let mut gzip = compress(Format::Gzip, b"Hello, World! Hello, World!");
+        let len = gzip.len();
+        gzip[len - 5] ^= 1;
+        let error = decompress(Format::Gzip, &gzip).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let truncated = &compress(Format::Zlib, &sample_text(1000))[..20];
+        let error = decompress(Format::Zlib, truncated).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        assert!(decompress(Format::Gzip, b"not gzip at all").is_err());
+    }
+
+    #[test]
+    fn test_decoder_max_ratio() {
+        let bomb = compress(Format::Gzip, &vec![0; 4 * 1024 * 1024]);
+        assert!(bomb.len() < 20_000);
+
+        let mut decoder = Decoder::new(&bomb[..], Format::Gzip).with_max_ratio(100);
+        let error = io::copy(&mut decoder, &mut io::sink()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Ordinary text is well within the limit.
+        let text = sample_text(2 * 1024 * 1024);
+        let compressed = compress(Format::Gzip, &text);
+        let mut decoder = Decoder::new(&compressed[..], Format::Gzip).with_max_ratio(100);
+        let mut output = vec![];
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, text);
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    io::{self, ErrorKind, IoSlice, Write},
+    thread,
+    time::Duration,
+};
+
+/// How long to wait before retrying a write which failed with `WouldBlock`.
+const WOULD_BLOCK_BACKOFF: Duration = Duration::from_millis(10);
+
+/// How many times in a row a write may fail with `WouldBlock` before we give up. A socket with a
+/// write timeout also reports `WouldBlock` once the timeout expires, so retrying forever would
+/// defeat it.
+const MAX_WOULD_BLOCK_RETRIES: u32 = 100;
+
+/// Write every byte of `bufs` to `writer`, returning the total number of bytes written.
+///
+/// A single call to `write` or `write_vectored` may only write part of what it was given, so we
+/// keep going until everything has been written. Writes which are `Interrupted` are retried
+/// immediately, and writes which fail with `WouldBlock` are retried after a short backoff. The
+/// slices are handed to the writer together so that a head and a body can be sent without first
+/// copying them into one buffer.
+pub(crate) fn write_all_vectored<W: Write>(
+    writer: &mut W,
+    mut bufs: &mut [IoSlice<'_>],
+) -> io::Result<usize> {
+    let mut total = 0;
+    let mut would_block_retries = 0;
+
+    // Skip past any empty slices so that we only loop while there is something left to write.
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    ErrorKind::WriteZero,
+                    "Failed to write the whole buffer.",
+                ));
+            }
+            Ok(num_bytes_written) => {
+                total += num_bytes_written;
+                would_block_retries = 0;
+                IoSlice::advance_slices(&mut bufs, num_bytes_written);
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                would_block_retries += 1;
+                if would_block_retries > MAX_WOULD_BLOCK_RETRIES {
+                    return Err(e);
+                }
+                thread::sleep(WOULD_BLOCK_BACKOFF);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Return a path for a test's scratch directory, which is unique to this process and `name`
+    /// and does not exist yet.
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("cairo-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    /// A writer which accepts at most a few bytes per call and fails every other call, to
+    /// simulate a slow or busy socket.
+    pub(crate) struct TrickleWriter {
+        pub(crate) output: Vec<u8>,
+        calls: usize,
+    }
+
+    impl TrickleWriter {
+        pub(crate) fn new() -> Self {
+            Self {
+                output: vec![],
+                calls: 0,
+            }
+        }
+    }
+
+    impl Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            match self.calls % 4 {
+                1 => Err(ErrorKind::Interrupted.into()),
+                3 => Err(ErrorKind::WouldBlock.into()),
+                _ => {
+                    let num_bytes = buf.len().min(3);
+                    self.output.extend_from_slice(&buf[..num_bytes]);
+                    Ok(num_bytes)
+                }
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_all_vectored() {
+        let mut output = vec![];
+        let mut bufs = [
+            IoSlice::new(b"Hello"),
+            IoSlice::new(b""),
+            IoSlice::new(b", World!"),
+        ];
+        let num_bytes_written = write_all_vectored(&mut output, &mut bufs).unwrap();
+        assert_eq!(num_bytes_written, 13);
+        assert_eq!(output, b"Hello, World!");
+    }
+
+    #[test]
+    fn test_write_all_vectored_partial_writes() {
+        let mut writer = TrickleWriter::new();
+        let mut bufs = [IoSlice::new(b"Hello"), IoSlice::new(b", World!")];
+        let num_bytes_written = write_all_vectored(&mut writer, &mut bufs).unwrap();
+        assert_eq!(num_bytes_written, 13);
+        assert_eq!(writer.output, b"Hello, World!");
+    }
+
+    #[test]
+    fn test_write_all_vectored_write_zero() {
+        struct FullWriter;
+
+        impl Write for FullWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Ok(0)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut bufs = [IoSlice::new(b"Hello")];
+        let result = write_all_vectored(&mut FullWriter, &mut bufs);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_write_all_vectored_gives_up_on_would_block() {
+        struct BlockedWriter;
+
+        impl Write for BlockedWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(ErrorKind::WouldBlock.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut bufs = [IoSlice::new(b"Hello")];
+        let result = write_all_vectored(&mut BlockedWriter, &mut bufs);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::WouldBlock);
+    }
+}
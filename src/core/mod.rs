@@ -1,3 +1,17 @@
+pub(crate) mod base64;
+pub(crate) mod chacha20poly1305;
+mod checksum;
+pub(crate) mod deflate;
+pub(crate) mod inflate;
+mod io;
+mod random;
+pub(crate) mod sha256;
 mod thread_pool;
 
+pub(crate) use io::write_all_vectored;
+pub(crate) use random::fill_random;
+pub(crate) use thread_pool::PoolStats;
 pub use thread_pool::ThreadPool;
+
+#[cfg(test)]
+pub(crate) use io::tests::{temp_dir, TrickleWriter};
@@ -0,0 +1,19 @@
+use std::{fs::File, io::Read};
+
+/// Fill `buf` with cryptographically secure random bytes from the operating system.
+pub(crate) fn fill_random(buf: &mut [u8]) -> std::io::Result<()> {
+    File::open("/dev/urandom")?.read_exact(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_random() {
+        let (mut a, mut b) = ([0; 32], [0; 32]);
+        fill_random(&mut a).unwrap();
+        fill_random(&mut b).unwrap();
+        assert_ne!(a, b);
+    }
+}
@@ -1,11 +1,14 @@
 use std::{
     sync::{
+        atomic::{AtomicUsize, Ordering},
         mpsc::{self, Receiver, Sender},
         Arc, Mutex,
     },
     thread,
 };
 
+use crate::log::{log, Level};
+
 /// Represents a type alias for a job, which is a boxed closure which can be called once and takes
 /// no arguments.
 ///
@@ -21,11 +24,32 @@ enum Message {
     Terminate,
 }
 
+/// Counts of what a `ThreadPool` is doing, shared with its workers and readable while it runs.
+#[derive(Debug, Default)]
+pub(crate) struct PoolStats {
+    /// The number of workers.
+    pub(crate) size: usize,
+    /// Jobs which have been sent but not yet picked up by a worker.
+    pub(crate) queued: AtomicUsize,
+    /// Workers which are running a job.
+    pub(crate) busy: AtomicUsize,
+}
+
+/// Decrements a counter when dropped, so a job which panics is still counted as finished.
+struct CountGuard<'a>(&'a AtomicUsize);
+
+impl Drop for CountGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 /// Represents a `ThreadPool` with an arbitrary number of workers which uses a `Sender` to
 /// communicate to its `Worker` threads.
 pub struct ThreadPool {
     workers: Vec<Worker>,
     sender: Sender<Message>,
+    stats: Arc<PoolStats>,
 }
 
 impl ThreadPool {
@@ -38,14 +62,27 @@ impl ThreadPool {
         // Wrap the receiver in an `Arc<Mutext<_>>` to allow shared ownership and safe concurrent
         // access.
         let receiver = Arc::new(Mutex::new(receiver));
+        let stats = Arc::new(PoolStats {
+            size,
+            ..PoolStats::default()
+        });
 
         // Initialize the `Worker` objects for the given `size` of the thread pool.
         let mut workers = Vec::with_capacity(size);
         for id in 0..size {
-            workers.push(Worker::new(id, receiver.clone()));
+            workers.push(Worker::new(id, receiver.clone(), stats.clone()));
         }
 
-        Self { workers, sender }
+        Self {
+            workers,
+            sender,
+            stats,
+        }
+    }
+
+    /// Return the pool's statistics, which stay up to date as jobs are run.
+    pub(crate) fn stats(&self) -> Arc<PoolStats> {
+        Arc::clone(&self.stats)
     }
 
     /// Execute a job by sending it to the channel.
@@ -66,6 +103,7 @@ impl ThreadPool {
         // the heap and also gives us a sized type. Its size is now the size of a pointer, which
         // allows the `Sender` to accept the boxed closure.
         let job: Job = Box::new(f);
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
         self.sender
             .send(Message::NewJob(job))
             .expect("Failed to send.");
@@ -84,7 +122,7 @@ impl Drop for ThreadPool {
 
         for worker in &mut self.workers {
             if let Some(thread) = worker.thread.take() {
-                println!("Stopping worker {}", worker.id);
+                log!(Level::Debug, "Stopping worker {}", worker.id);
                 thread.join().expect("Failed to join.");
             }
         }
@@ -103,7 +141,7 @@ struct Worker {
 
 impl Worker {
     /// Create a new `Worker` with a given ID and a receiver for `Job`s.
-    fn new(id: usize, receiver: Arc<Mutex<Receiver<Message>>>) -> Self {
+    fn new(id: usize, receiver: Arc<Mutex<Receiver<Message>>>, stats: Arc<PoolStats>) -> Self {
         let thread = thread::spawn(move || loop {
             // While another worker holds the `Mutex` lock, we will block on `receiver.lock()`.
             // While we hold the `Mutex` lock, we will block on `recv()` while we wait for a
@@ -112,11 +150,14 @@ impl Worker {
 
             match job {
                 Ok(Message::NewJob(job)) => {
+                    stats.queued.fetch_sub(1, Ordering::Relaxed);
+                    stats.busy.fetch_add(1, Ordering::Relaxed);
+                    let _busy = CountGuard(&stats.busy);
                     // Execute the job.
                     job();
                 }
                 Ok(Message::Terminate) => {
-                    println!("Worker {} received terminate message.", id);
+                    log!(Level::Debug, "Worker {} received terminate message", id);
                     break;
                 }
                 Err(_) => {
@@ -187,6 +228,29 @@ mod tests {
         assert_eq!(results, (0..10).collect::<Vec<i32>>());
     }
 
+    #[test]
+    fn test_thread_pool_stats() {
+        let pool = ThreadPool::new(1);
+        let stats = pool.stats();
+        let (started_sender, started) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+
+        pool.execute(move || {
+            started_sender.send(()).unwrap();
+            released.recv().unwrap();
+        });
+        pool.execute(|| {});
+        started.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(stats.size, 1);
+        assert_eq!(stats.busy.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.queued.load(Ordering::Relaxed), 1);
+
+        release.send(()).unwrap();
+        drop(pool);
+        assert_eq!(stats.busy.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.queued.load(Ordering::Relaxed), 0);
+    }
+
     #[test]
     fn test_thread_pool_drop() {
         let pool = ThreadPool::new(4);
@@ -1,19 +1,47 @@
+mod form;
+mod json;
+mod multipart;
+
+use std::{
+    io::{self, Read},
+    net::SocketAddr,
+};
+
 use crate::{
-    http::{Parts, Request, Response},
+    headers::Header,
+    http::{Body, HeaderMap, Parts, Request, Response},
     response::IntoResponse,
 };
 
-/// This represents a placeholder error type for when an extractor fails. For now, we will
-/// universally consider this to be a 400 BAD REQUEST.
-#[derive(Debug)]
-pub struct ExtractError;
+pub(crate) use form::percent_decode;
+pub use form::{Form, FormData, FromForm, Query};
+pub use json::{FromJson, JsonValue};
+pub use multipart::{Field, Multipart, MultipartError, DEFAULT_MULTIPART_TOTAL_LIMIT};
+
+/// The largest body, in bytes, which an extractor will buffer into memory. Anything larger should
+/// be read with [`BodyStream`] instead.
+pub const DEFAULT_BODY_LIMIT: u64 = 2 * 1024 * 1024;
+
+/// The error returned when an extractor fails. Unless the extractor overrides
+/// [`FromRequestParts::rejection`], it is answered with the matching status code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractError {
+    /// The request is malformed or lacks what the extractor needs: 400 BAD REQUEST.
+    BadRequest,
+    /// The body is larger than [`DEFAULT_BODY_LIMIT`]: 413 PAYLOAD TOO LARGE.
+    PayloadTooLarge,
+}
 
 impl IntoResponse for ExtractError {
     fn into_response(self) -> Response {
+        let (status, body) = match self {
+            Self::BadRequest => (400, "Bad request"),
+            Self::PayloadTooLarge => (413, "Payload too large"),
+        };
         Response::new(
-            400,
-            vec![("Content-Type".to_string(), "text/plain".to_string())],
-            "Bad request".to_string(),
+            status,
+            HeaderMap::from([("Content-Type", "text/plain")]),
+            body.to_string(),
         )
     }
 }
@@ -22,6 +50,12 @@ impl IntoResponse for ExtractError {
 /// will be consumed, the extractor should implement [`FromRequest`] instead.
 pub trait FromRequestParts: Sized {
     fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError>;
+
+    /// The response sent instead of calling the handler when extraction fails, which is the
+    /// error's own response unless the extractor chooses another.
+    fn rejection(err: ExtractError) -> Response {
+        err.into_response()
+    }
 }
 
 /// Any extractor which will consume the [`Request`] body must implement this. Because this is a
@@ -33,6 +67,12 @@ pub trait FromRequestParts: Sized {
 /// which implement [`FromRequestParts`].
 pub trait FromRequest: Sized {
     fn from_request(req: Request) -> Result<Self, ExtractError>;
+
+    /// The response sent instead of calling the handler when extraction fails, as for
+    /// [`FromRequestParts::rejection`].
+    fn rejection(err: ExtractError) -> Response {
+        err.into_response()
+    }
 }
 
 /// Represents parameters of type `T` we expect to parse from the path. The data `T` must be public
@@ -45,9 +85,9 @@ impl FromRequestParts for Path<usize> {
         let param = parts
             .path_params
             .first()
-            .ok_or(ExtractError)?
+            .ok_or(ExtractError::BadRequest)?
             .parse()
-            .map_err(|_| ExtractError)?;
+            .map_err(|_| ExtractError::BadRequest)?;
         Ok(Self(param))
     }
 }
@@ -61,11 +101,201 @@ impl FromRequest for Path<usize> {
     }
 }
 
+/// An extractor for a value which was given to the router with
+/// [`Router::with_state`](crate::Router::with_state). The request is rejected if the router has
+/// no state of type `S`, so make sure the types match.
+///
+/// ```
+/// use cairo::{extract::State, routing::get, Router};
+///
+/// #[derive(Clone)]
+/// struct Config {
+///     greeting: String,
+/// }
+///
+/// fn hello(State(config): State<Config>) -> String {
+///     config.greeting
+/// }
+///
+/// let config = Config { greeting: "Hello".to_string() };
+/// let router = Router::new().route("/", get(hello)).with_state(config);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct State<S>(pub S);
+
+impl<S: Clone + Send + Sync + 'static> FromRequestParts for State<S> {
+    /// Clone the state out of the request extensions, where the router put it.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .extensions
+            .get::<S>()
+            .cloned()
+            .map(Self)
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl<S: Clone + Send + Sync + 'static> FromRequest for State<S> {
+    /// When a `State` is requested as the last parameter, we pull it from the parts like normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// An extractor for the addresses of the connection a request arrived on, recorded by
+/// [`serve`](crate::serve).
+///
+/// Behind a reverse proxy the peer is the proxy rather than the client. Use
+/// [`ClientIp`](crate::middleware::ClientIp) with a
+/// [`TrustedProxyLayer`](crate::middleware::TrustedProxyLayer) to find the client's address.
+///
+/// ```
+/// use cairo::{extract::ConnectInfo, routing::get, Router};
+///
+/// let router = Router::new().route(
+///     "/",
+///     get(|info: ConnectInfo| format!("Hello, {}", info.peer_addr)),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectInfo {
+    /// The address of the other end of the connection.
+    pub peer_addr: SocketAddr,
+    /// The address of this end of the connection, which the server accepted it on.
+    pub local_addr: SocketAddr,
+}
+
+impl FromRequestParts for ConnectInfo {
+    /// Copy the addresses out of the request extensions, where the server put them.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .extensions
+            .get::<ConnectInfo>()
+            .copied()
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl FromRequest for ConnectInfo {
+    /// When a `ConnectInfo` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// An extractor for a typed [`Header`], such as `TypedHeader<UserAgent>`. The request is rejected
+/// if the header is missing or cannot be parsed.
+///
+/// A `TypedHeader` can also be returned alongside a body, for example
+/// `(TypedHeader(ContentType::html()), body)`, to set the header on the response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedHeader<H>(pub H);
+
+impl<H: Header> FromRequestParts for TypedHeader<H> {
+    /// Parse the header `H` from the request headers.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .headers
+            .typed_get()
+            .map(Self)
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl<H: Header> FromRequest for TypedHeader<H> {
+    /// When a `TypedHeader` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+impl FromRequestParts for HeaderMap {
+    /// Clone every header of the request.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        Ok(parts.headers.clone())
+    }
+}
+
+impl FromRequest for HeaderMap {
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// Read the entire body of a [`Request`] into memory, failing if it is larger than
+/// [`DEFAULT_BODY_LIMIT`] or if the connection is closed before all of it arrives.
+pub(crate) fn body_bytes(req: Request) -> Result<Vec<u8>, ExtractError> {
+    read_body(req.into_body())
+}
+
+/// Read an entire [`Body`] into memory, with the same limit as [`body_bytes`].
+pub(crate) fn read_body(body: Body) -> Result<Vec<u8>, ExtractError> {
+    if body.len().is_some_and(|len| len > DEFAULT_BODY_LIMIT) {
+        return Err(ExtractError::PayloadTooLarge);
+    }
+
+    // A body of unknown length could be arbitrarily large, so we read at most one byte past the
+    // limit to find out whether it was exceeded.
+    let mut bytes = vec![];
+    body.into_reader()
+        .take(DEFAULT_BODY_LIMIT + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|_| ExtractError::BadRequest)?;
+    if bytes.len() as u64 > DEFAULT_BODY_LIMIT {
+        return Err(ExtractError::PayloadTooLarge);
+    }
+
+    Ok(bytes)
+}
+
 impl FromRequest for String {
     /// A `String` as the last parameter of a handler indicates we should parse the request body as
     /// plain text.
     fn from_request(req: Request) -> Result<Self, ExtractError> {
-        req.body.ok_or(ExtractError)
+        let bytes = body_bytes(req)?;
+        if bytes.is_empty() {
+            return Err(ExtractError::BadRequest);
+        }
+
+        String::from_utf8(bytes).map_err(|_| ExtractError::BadRequest)
+    }
+}
+
+/// An extractor which reads the request body straight from the connection as the handler consumes
+/// it, rather than buffering the whole thing first. This allows large uploads to be written to
+/// disk without holding them in memory.
+///
+/// The stream yields exactly `Content-Length` bytes, or the decoded data of a chunked body. If the
+/// client disconnects early, reading fails with [`io::ErrorKind::UnexpectedEof`].
+pub struct BodyStream {
+    reader: Box<dyn Read + Send>,
+    content_length: Option<u64>,
+}
+
+impl BodyStream {
+    /// Return the number of bytes the client declared it will send, if known.
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+}
+
+impl Read for BodyStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl FromRequest for BodyStream {
+    /// Hand the unread body over to the handler. This never fails, a request without a body
+    /// simply produces an empty stream.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        let body = req.into_body();
+        Ok(Self {
+            content_length: body.len(),
+            reader: body.into_reader(),
+        })
     }
 }
 
@@ -73,7 +303,10 @@ impl FromRequest for String {
 mod tests {
     use super::*;
 
-    use crate::http::Method;
+    use crate::{
+        handler::BoxedHandler,
+        http::{Body, Extensions, Method},
+    };
 
     #[test]
     fn test_from_request_parts() {
@@ -87,8 +320,9 @@ mod tests {
         let parts = Parts {
             method: Method::Get,
             path: "/".to_string(),
-            headers: vec![],
+            headers: HeaderMap::new(),
             path_params: vec!["dummy".to_string()],
+            extensions: Extensions::new(),
         };
         let extractor =
             DummyExtractor::from_request_parts(&parts).expect("Should return extractor");
@@ -103,6 +337,80 @@ mod tests {
         assert_eq!(path.0, 42);
     }
 
+    #[test]
+    fn test_from_request_typed_header() {
+        use crate::headers::{ContentLength, UserAgent};
+
+        let headers = HeaderMap::from([("user-agent", "curl/8.4.0")]);
+        let req = Request::with_headers(Method::Get, "/", headers);
+        let TypedHeader(user_agent) =
+            TypedHeader::<UserAgent>::from_request_parts(req.into_parts()).unwrap();
+        assert_eq!(user_agent.as_str(), "curl/8.4.0");
+
+        assert!(TypedHeader::<ContentLength>::from_request(req).is_err());
+    }
+
+    #[test]
+    fn test_from_request_string() {
+        let mut req = Request::new(Method::Post, "/");
+        req.set_body("Hello Rust");
+        assert_eq!(String::from_request(req).unwrap(), "Hello Rust");
+
+        let req = Request::new(Method::Post, "/");
+        assert!(String::from_request(req).is_err());
+
+        let mut req = Request::new(Method::Post, "/");
+        req.set_body(vec![0x80, 0x81]);
+        assert!(String::from_request(req).is_err());
+    }
+
+    #[test]
+    fn test_from_request_string_over_limit() {
+        let mut req = Request::new(Method::Post, "/");
+        req.set_body(Body::from_reader(
+            io::repeat(b'a').take(DEFAULT_BODY_LIMIT + 1),
+        ));
+        assert_eq!(
+            String::from_request(req).unwrap_err(),
+            ExtractError::PayloadTooLarge
+        );
+    }
+
+    #[test]
+    fn test_body_over_limit_rejected_with_413() {
+        fn handler(body: String) -> String {
+            body
+        }
+
+        let mut req = Request::new(Method::Post, "/");
+        req.set_body(Body::sized_reader(
+            io::repeat(b'a').take(DEFAULT_BODY_LIMIT + 1),
+            DEFAULT_BODY_LIMIT + 1,
+        ));
+        let response = BoxedHandler::from_handler(handler).call_handler(req);
+        assert_eq!(response.status_code(), 413);
+
+        let req = Request::new(Method::Post, "/");
+        let response = BoxedHandler::from_handler(handler).call_handler(req);
+        assert_eq!(response.status_code(), 400);
+    }
+
+    #[test]
+    fn test_from_request_body_stream() {
+        let mut req = Request::new(Method::Post, "/");
+        req.set_body(Body::sized_reader(
+            io::Cursor::new(b"Hello Rust".to_vec()),
+            10,
+        ));
+
+        let mut stream = BodyStream::from_request(req).unwrap();
+        assert_eq!(stream.content_length(), Some(10));
+
+        let mut body = String::new();
+        stream.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "Hello Rust");
+    }
+
     #[test]
     #[should_panic]
     fn test_from_request_path_usize_invalid() {
@@ -0,0 +1,308 @@
+use std::{collections::HashMap, str::FromStr};
+
+use super::{body_bytes, ExtractError, FromRequest, FromRequestParts};
+use crate::{
+    headers::ContentType,
+    http::{Parts, Request},
+};
+
+/// Return the value of an ASCII hex digit.
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decode `%XX` escapes in `input`, and `+` as a space if `plus_as_space` is set. The decoded
+/// bytes must be valid UTF-8.
+fn decode(input: &str, plus_as_space: bool) -> Result<String, ExtractError> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if plus_as_space => decoded.push(b' '),
+            b'%' => {
+                let high = bytes.get(i + 1).copied().and_then(hex_value);
+                let low = bytes.get(i + 2).copied().and_then(hex_value);
+                match (high, low) {
+                    (Some(high), Some(low)) => {
+                        decoded.push(high << 4 | low);
+                        i += 2;
+                    }
+                    _ => return Err(ExtractError::BadRequest),
+                }
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8(decoded).map_err(|_| ExtractError::BadRequest)
+}
+
+/// Decode one name or value from an `application/x-www-form-urlencoded` string, where `+` stands
+/// for a space and `%XX` for the byte with hex value `XX`. The decoded bytes must be valid UTF-8.
+pub(crate) fn url_decode(input: &str) -> Result<String, ExtractError> {
+    decode(input, true)
+}
+
+/// Decode the `%XX` escapes in a URL path. Unlike a form, a `+` in a path is a literal plus sign.
+pub(crate) fn percent_decode(input: &str) -> Result<String, ExtractError> {
+    decode(input, false)
+}
+
+/// The decoded name and value pairs of a URL-encoded form or query string. A name may appear more
+/// than once, such as for a group of checkboxes, and the original order is preserved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormData(Vec<(String, String)>);
+
+impl FormData {
+    /// Parse a string such as `name=Ferris&tags=a&tags=b`. A pair without an `=` has an empty
+    /// value.
+    pub fn parse(input: &str) -> Result<Self, ExtractError> {
+        input
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                Ok((url_decode(name)?, url_decode(value)?))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+
+    /// Return the first value for `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return every value for `name`, in the order they were sent.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.0
+            .iter()
+            .filter(move |(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return the first value for `name`, or an error if it is missing. This is a convenience
+    /// for implementing [`FromForm`].
+    pub fn require(&self, name: &str) -> Result<&str, ExtractError> {
+        self.get(name).ok_or(ExtractError::BadRequest)
+    }
+
+    /// Parse the first value for `name` into `T`, or return an error if it is missing or cannot be
+    /// parsed. This is a convenience for implementing [`FromForm`].
+    pub fn parse_value<T: FromStr>(&self, name: &str) -> Result<T, ExtractError> {
+        self.require(name)?
+            .parse()
+            .map_err(|_| ExtractError::BadRequest)
+    }
+
+    /// Iterate over every name and value, in the order they were sent.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Return the number of pairs.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return `true` if there are no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Any type which can be built from decoded [`FormData`]. Implement this for your own types to
+/// use them with [`Form`] and [`Query`].
+///
+/// ```
+/// use cairo::extract::{ExtractError, FormData, FromForm};
+///
+/// struct Login {
+///     username: String,
+///     remember: bool,
+/// }
+///
+/// impl FromForm for Login {
+///     fn from_form(form: &FormData) -> Result<Self, ExtractError> {
+///         Ok(Self {
+///             username: form.require("username")?.to_string(),
+///             remember: form.get("remember") == Some("on"),
+///         })
+///     }
+/// }
+/// ```
+pub trait FromForm: Sized {
+    fn from_form(form: &FormData) -> Result<Self, ExtractError>;
+}
+
+impl FromForm for FormData {
+    fn from_form(form: &FormData) -> Result<Self, ExtractError> {
+        Ok(form.clone())
+    }
+}
+
+impl FromForm for HashMap<String, String> {
+    /// Collect the form into a map. When a name appears more than once, the last value wins.
+    fn from_form(form: &FormData) -> Result<Self, ExtractError> {
+        Ok(form
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect())
+    }
+}
+
+/// An extractor which parses an `application/x-www-form-urlencoded` request body into `T`. The
+/// request is rejected if it has any other `Content-Type`.
+pub struct Form<T>(pub T);
+
+impl<T: FromForm> FromRequest for Form<T> {
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        let content_type: ContentType =
+            req.headers().typed_get().ok_or(ExtractError::BadRequest)?;
+        if content_type.mime_type() != "application/x-www-form-urlencoded" {
+            return Err(ExtractError::BadRequest);
+        }
+
+        let bytes = body_bytes(req)?;
+        let body = std::str::from_utf8(&bytes).map_err(|_| ExtractError::BadRequest)?;
+        let form = FormData::parse(body)?;
+        T::from_form(&form).map(Self)
+    }
+}
+
+/// An extractor which parses the query string of the request path into `T`. A request without a
+/// query string is treated as an empty one.
+pub struct Query<T>(pub T);
+
+impl<T: FromForm> FromRequestParts for Query<T> {
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        let form = FormData::parse(parts.query().unwrap_or_default())?;
+        T::from_form(&form).map(Self)
+    }
+}
+
+impl<T: FromForm> FromRequest for Query<T> {
+    /// When a `Query` is requested as the last parameter, we pull it from the parts like normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{HeaderMap, Method};
+
+    #[test]
+    fn test_url_decode() {
+        assert_eq!(url_decode("Hello+Rust").unwrap(), "Hello Rust");
+        assert_eq!(url_decode("a%20b%2Bc%26").unwrap(), "a b+c&");
+        assert_eq!(url_decode("%E2%9C%93").unwrap(), "✓");
+        assert_eq!(url_decode("%e2%9c%93").unwrap(), "✓");
+        assert!(url_decode("%").is_err());
+        assert!(url_decode("%4").is_err());
+        assert!(url_decode("%zz").is_err());
+        assert!(url_decode("%80").is_err());
+
+        assert_eq!(percent_decode("a+b%20c").unwrap(), "a+b c");
+        assert!(percent_decode("%2").is_err());
+    }
+
+    #[test]
+    fn test_form_data_parse() {
+        let form = FormData::parse("name=Ferris+Crab&tags=a&tags=b&empty&x=1%3D2").unwrap();
+        assert_eq!(form.len(), 5);
+        assert_eq!(form.get("name"), Some("Ferris Crab"));
+        assert_eq!(form.get_all("tags").collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(form.get("empty"), Some(""));
+        assert_eq!(form.get("x"), Some("1=2"));
+        assert_eq!(form.get("missing"), None);
+        assert!(form.require("missing").is_err());
+        assert_eq!(form.parse_value::<u8>("x").ok(), None);
+
+        assert!(FormData::parse("").unwrap().is_empty());
+    }
+
+    struct Login {
+        username: String,
+        age: u8,
+    }
+
+    impl FromForm for Login {
+        fn from_form(form: &FormData) -> Result<Self, ExtractError> {
+            Ok(Self {
+                username: form.require("username")?.to_string(),
+                age: form.parse_value("age")?,
+            })
+        }
+    }
+
+    fn form_request(content_type: &str, body: &str) -> Request {
+        let headers = HeaderMap::from([("Content-Type", content_type)]);
+        let mut req = Request::with_headers(Method::Post, "/", headers);
+        req.set_body(body.to_string());
+        req
+    }
+
+    #[test]
+    fn test_form_user_type() {
+        let req = form_request(
+            "application/x-www-form-urlencoded",
+            "username=ferris%40rust&age=9",
+        );
+        let Form(login) = Form::<Login>::from_request(req).unwrap();
+        assert_eq!(login.username, "ferris@rust");
+        assert_eq!(login.age, 9);
+
+        let req = form_request("application/x-www-form-urlencoded", "username=ferris");
+        assert!(Form::<Login>::from_request(req).is_err());
+    }
+
+    #[test]
+    fn test_form_multimap() {
+        let req = form_request(
+            "Application/X-WWW-Form-Urlencoded; charset=utf-8",
+            "a=1&a=2",
+        );
+        let Form(form) = Form::<FormData>::from_request(req).unwrap();
+        assert_eq!(form.get_all("a").collect::<Vec<_>>(), vec!["1", "2"]);
+
+        let req = form_request("application/x-www-form-urlencoded", "a=1&a=2");
+        let Form(map) = Form::<HashMap<String, String>>::from_request(req).unwrap();
+        assert_eq!(map.get("a").map(String::as_str), Some("2"));
+    }
+
+    #[test]
+    fn test_form_wrong_content_type() {
+        let req = form_request("text/plain", "a=1");
+        assert!(Form::<FormData>::from_request(req).is_err());
+
+        let mut req = Request::new(Method::Post, "/");
+        req.set_body("a=1");
+        assert!(Form::<FormData>::from_request(req).is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let req = Request::new(Method::Get, "/search?q=rust+lang&page=2");
+        let Query(query) = Query::<FormData>::from_request_parts(req.into_parts()).unwrap();
+        assert_eq!(query.get("q"), Some("rust lang"));
+        assert_eq!(query.get("page"), Some("2"));
+
+        let req = Request::new(Method::Get, "/search");
+        let Query(query) = Query::<FormData>::from_request(req).unwrap();
+        assert!(query.is_empty());
+    }
+}
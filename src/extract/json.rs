@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use super::ExtractError;
+
+/// How deeply arrays and objects may be nested, so that a hostile document cannot overflow the
+/// stack of the recursive parser.
+const MAX_DEPTH: usize = 128;
+
+/// A parsed JSON document, as described in RFC 8259.
+///
+/// Object members keep the order they were written in. If a name appears more than once,
+/// [`get`](JsonValue::get) returns the last value, as most parsers do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Parse a complete JSON document. Anything other than whitespace after the value is an error.
+    pub fn parse(input: &str) -> Result<Self, ExtractError> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos != parser.input.len() {
+            return Err(ExtractError::BadRequest);
+        }
+        Ok(value)
+    }
+
+    /// Return the member `name` if this is an object which has it.
+    pub fn get(&self, name: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .rev()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Return the string if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Return the number if this is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Return the number if this is a number with no fractional part that fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        let n = self.as_f64()?;
+        (n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64).then_some(n as i64)
+    }
+
+    /// Return the value if this is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Return the elements if this is an array.
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// Return `true` if this is `null`.
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+}
+
+/// Any type which can be built from a parsed [`JsonValue`]. Implement this for your own types to
+/// use them with extractors which read JSON, such as
+/// [`Claims`](crate::middleware::Claims).
+///
+/// ```
+/// use cairo::extract::{ExtractError, FromJson, JsonValue};
+///
+/// struct User {
+///     name: String,
+///     admin: bool,
+/// }
+///
+/// impl FromJson for User {
+///     fn from_json(value: &JsonValue) -> Result<Self, ExtractError> {
+///         let name = value.get("name").and_then(JsonValue::as_str).ok_or(ExtractError::BadRequest)?;
+///         Ok(Self {
+///             name: name.to_string(),
+///             admin: value.get("admin").and_then(JsonValue::as_bool).unwrap_or(false),
+///         })
+///     }
+/// }
+/// ```
+pub trait FromJson: Sized {
+    fn from_json(value: &JsonValue) -> Result<Self, ExtractError>;
+}
+
+impl FromJson for JsonValue {
+    fn from_json(value: &JsonValue) -> Result<Self, ExtractError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromJson for HashMap<String, JsonValue> {
+    /// Collect the members of an object into a map. When a name appears more than once, the last
+    /// value wins.
+    fn from_json(value: &JsonValue) -> Result<Self, ExtractError> {
+        match value {
+            JsonValue::Object(members) => Ok(members.iter().cloned().collect()),
+            _ => Err(ExtractError::BadRequest),
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<u8, ExtractError> {
+        let b = self.peek().ok_or(ExtractError::BadRequest)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), ExtractError> {
+        if self.next()? == b {
+            Ok(())
+        } else {
+            Err(ExtractError::BadRequest)
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8], value: JsonValue) -> Result<JsonValue, ExtractError> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            Err(ExtractError::BadRequest)
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue, ExtractError> {
+        if depth > MAX_DEPTH {
+            return Err(ExtractError::BadRequest);
+        }
+        self.skip_whitespace();
+        match self.peek().ok_or(ExtractError::BadRequest)? {
+            b'n' => self.literal(b"null", JsonValue::Null),
+            b't' => self.literal(b"true", JsonValue::Bool(true)),
+            b'f' => self.literal(b"false", JsonValue::Bool(false)),
+            b'"' => self.string().map(JsonValue::String),
+            b'-' | b'0'..=b'9' => self.number(),
+            b'[' => {
+                self.pos += 1;
+                let mut elements = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(elements));
+                }
+                loop {
+                    elements.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.next()? {
+                        b',' => continue,
+                        b']' => return Ok(JsonValue::Array(elements)),
+                        _ => return Err(ExtractError::BadRequest),
+                    }
+                }
+            }
+            b'{' => {
+                self.pos += 1;
+                let mut members = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let name = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                    members.push((name, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.next()? {
+                        b',' => continue,
+                        b'}' => return Ok(JsonValue::Object(members)),
+                        _ => return Err(ExtractError::BadRequest),
+                    }
+                }
+            }
+            _ => Err(ExtractError::BadRequest),
+        }
+    }
+
+    /// Parse a number, checking the JSON grammar before handing the text to Rust, which accepts
+    /// forms that JSON does not such as `+1` or `.5`.
+    fn number(&mut self) -> Result<JsonValue, ExtractError> {
+        let start = self.pos;
+        let digits = |parser: &mut Self| {
+            let from = parser.pos;
+            while parser.peek().is_some_and(|b| b.is_ascii_digit()) {
+                parser.pos += 1;
+            }
+            parser.pos - from
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                digits(self);
+            }
+            _ => return Err(ExtractError::BadRequest),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if digits(self) == 0 {
+                return Err(ExtractError::BadRequest);
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if digits(self) == 0 {
+                return Err(ExtractError::BadRequest);
+            }
+        }
+
+        // The grammar above only lets ASCII through.
+        let text = std::str::from_utf8(&self.input[start..self.pos])
+            .map_err(|_| ExtractError::BadRequest)?;
+        let n: f64 = text.parse().map_err(|_| ExtractError::BadRequest)?;
+        if n.is_finite() {
+            Ok(JsonValue::Number(n))
+        } else {
+            Err(ExtractError::BadRequest)
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, ExtractError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = (self.next()? as char)
+                .to_digit(16)
+                .ok_or(ExtractError::BadRequest)?;
+            value = value << 4 | digit;
+        }
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, ExtractError> {
+        self.expect(b'"')?;
+        let mut bytes = vec![];
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex4()?;
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                // A character outside the Basic Multilingual Plane is written as a
+                                // surrogate pair.
+                                self.expect(b'\\')?;
+                                self.expect(b'u')?;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(ExtractError::BadRequest);
+                                }
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code).ok_or(ExtractError::BadRequest)?
+                        }
+                        _ => return Err(ExtractError::BadRequest),
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                b if b < 0x20 => return Err(ExtractError::BadRequest),
+                b => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| ExtractError::BadRequest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_parse() {
+        let value = JsonValue::parse(
+            r#" {"name": "Ferris", "age": 9, "pi": -3.5e0, "tags": ["a", true, null], "o": {}} "#,
+        )
+        .unwrap();
+        assert_eq!(
+            value.get("name").and_then(JsonValue::as_str),
+            Some("Ferris")
+        );
+        assert_eq!(value.get("age").and_then(JsonValue::as_i64), Some(9));
+        assert_eq!(value.get("pi").and_then(JsonValue::as_f64), Some(-3.5));
+        assert_eq!(value.get("pi").and_then(JsonValue::as_i64), None);
+        assert_eq!(
+            value.get("tags").and_then(JsonValue::as_array),
+            Some(
+                &[
+                    JsonValue::String("a".to_string()),
+                    JsonValue::Bool(true),
+                    JsonValue::Null
+                ][..]
+            )
+        );
+        assert_eq!(value.get("o"), Some(&JsonValue::Object(vec![])));
+        assert_eq!(value.get("missing"), None);
+
+        assert_eq!(JsonValue::parse("[]").unwrap(), JsonValue::Array(vec![]));
+        assert_eq!(JsonValue::parse("0").unwrap(), JsonValue::Number(0.0));
+        let duplicate = JsonValue::parse(r#"{"a": 1, "a": 2}"#).unwrap();
+        assert_eq!(duplicate.get("a"), Some(&JsonValue::Number(2.0)));
+    }
+
+    #[test]
+    fn test_json_parse_strings() {
+        let value = JsonValue::parse(r#""a\"b\\c\/\né🦀""#).unwrap();
+        assert_eq!(value.as_str(), Some("a\"b\\c/\né🦀"));
+        assert_eq!(JsonValue::parse("\"✓\"").unwrap().as_str(), Some("✓"));
+
+        for invalid in [
+            r#""\ud83e""#,
+            r#""\udd80""#,
+            r#""\x""#,
+            "\"a\nb\"",
+            r#""open"#,
+        ] {
+            assert!(JsonValue::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_json_parse_invalid() {
+        for invalid in [
+            "",
+            "nul",
+            "01",
+            "+1",
+            ".5",
+            "1.",
+            "1e",
+            "-",
+            "[1,]",
+            "[1 2]",
+            "{\"a\"}",
+            "{a: 1}",
+            "{\"a\": 1,}",
+            "1 2",
+            "1e999",
+            "[",
+        ] {
+            assert!(JsonValue::parse(invalid).is_err(), "{}", invalid);
+        }
+
+        let deep = "[".repeat(MAX_DEPTH + 2) + &"]".repeat(MAX_DEPTH + 2);
+        assert!(JsonValue::parse(&deep).is_err());
+        let nested = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(JsonValue::parse(&nested).is_ok());
+    }
+}
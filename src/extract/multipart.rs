@@ -0,0 +1,571 @@
+use std::{
+    error, fmt,
+    io::{self, Read},
+};
+
+use super::{ExtractError, FromRequest, DEFAULT_BODY_LIMIT};
+use crate::{
+    headers::ContentType,
+    http::{HeaderMap, Request, Response},
+    response::IntoResponse,
+};
+
+/// The default limit on the size of all the fields in a multipart body combined.
+pub const DEFAULT_MULTIPART_TOTAL_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// The largest block of headers we will accept for a single field.
+const MAX_FIELD_HEADERS_SIZE: usize = 8 * 1024;
+
+/// How many bytes to ask the body for at a time.
+const READ_SIZE: usize = 8 * 1024;
+
+/// Error type for a multipart body which could not be read.
+#[derive(Debug)]
+pub enum MultipartError {
+    /// The body does not follow the `multipart/form-data` format.
+    Malformed,
+    /// A single field was larger than the field limit.
+    FieldTooLarge,
+    /// The body as a whole was larger than the total limit.
+    TotalTooLarge,
+    /// The body could not be read from the connection.
+    Io(io::Error),
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "Malformed multipart body"),
+            Self::FieldTooLarge => write!(f, "Multipart field is too large"),
+            Self::TotalTooLarge => write!(f, "Multipart body is too large"),
+            Self::Io(e) => write!(f, "Failed to read multipart body: {}", e),
+        }
+    }
+}
+
+impl error::Error for MultipartError {}
+
+impl From<io::Error> for MultipartError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl IntoResponse for MultipartError {
+    /// A body over one of the limits is answered with a 413, everything else with a 400.
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            Self::FieldTooLarge | Self::TotalTooLarge => 413,
+            Self::Malformed | Self::Io(_) => 400,
+        };
+        (status_code, self.to_string()).into_response()
+    }
+}
+
+/// A single field of a multipart body, with its data held in memory.
+#[derive(Debug)]
+pub struct Field {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    data: Vec<u8>,
+}
+
+impl Field {
+    /// Return the name of the form field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the name of the uploaded file, if this field is a file.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Return the `Content-Type` of this field, if the client sent one.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Return the data of this field.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Return the data of this field as text, or `None` if it is not valid UTF-8.
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+
+    /// Consume the field and return its data.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// An extractor for a `multipart/form-data` request body, as sent by an HTML form with a file
+/// upload. Fields are read one at a time with [`Multipart::next_field`].
+///
+/// The body is parsed as it is read from the connection, so only the current field is ever held in
+/// memory. A field larger than the field limit, or a body larger than the total limit, is
+/// rejected. The limits can be changed before the first field is read.
+pub struct Multipart {
+    reader: Box<dyn Read + Send>,
+    /// `\r\n--` followed by the boundary, which separates one field from the next.
+    delimiter: Vec<u8>,
+    /// Bytes which have been read from the body but not yet parsed.
+    buffer: Vec<u8>,
+    total_read: u64,
+    field_limit: u64,
+    total_limit: u64,
+    state: State,
+}
+
+/// Where the parser is in the body.
+#[derive(Debug, PartialEq)]
+enum State {
+    /// Nothing has been parsed yet, we are looking for the first boundary.
+    Start,
+    /// We have just passed a boundary and the next field's headers follow.
+    Headers,
+    /// The closing boundary has been read.
+    Done,
+}
+
+impl Multipart {
+    /// Create a `Multipart` which parses the fields of `reader`, using `boundary` to separate
+    /// them.
+    pub fn new<R: Read + Send + 'static>(reader: R, boundary: &str) -> Self {
+        Self {
+            reader: Box::new(reader),
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            buffer: vec![],
+            total_read: 0,
+            field_limit: DEFAULT_BODY_LIMIT,
+            total_limit: DEFAULT_MULTIPART_TOTAL_LIMIT,
+            state: State::Start,
+        }
+    }
+
+    /// Set the largest size, in bytes, of a single field.
+    pub fn set_field_limit(&mut self, limit: u64) {
+        self.field_limit = limit;
+    }
+
+    /// Set the largest size, in bytes, of the entire body.
+    pub fn set_total_limit(&mut self, limit: u64) {
+        self.total_limit = limit;
+    }
+
+    /// Read the next field, or return `None` once the closing boundary has been reached.
+    pub fn next_field(&mut self) -> Result<Option<Field>, MultipartError> {
+        if self.state == State::Start {
+            self.skip_preamble()?;
+        }
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        let headers = self.read_headers()?;
+        let (name, file_name) = parse_content_disposition(&headers)?;
+        let content_type = headers.get("Content-Type").map(str::to_string);
+        let data = self.read_data()?;
+
+        Ok(Some(Field {
+            name,
+            file_name,
+            content_type,
+            data,
+        }))
+    }
+
+    /// Read more of the body into the buffer, returning `false` if the body has ended.
+    fn fill(&mut self) -> Result<bool, MultipartError> {
+        let mut chunk = [0; READ_SIZE];
+        let num_bytes_read = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        self.total_read += num_bytes_read as u64;
+        if self.total_read > self.total_limit {
+            return Err(MultipartError::TotalTooLarge);
+        }
+        self.buffer.extend_from_slice(&chunk[..num_bytes_read]);
+        Ok(num_bytes_read > 0)
+    }
+
+    /// Skip anything before the first boundary. The first boundary may be at the very start of
+    /// the body, so it need not be preceded by a line break.
+    fn skip_preamble(&mut self) -> Result<(), MultipartError> {
+        // Pretend the body starts with a line break so that the first boundary matches the same
+        // delimiter as every other one.
+        self.buffer.splice(0..0, *b"\r\n");
+        loop {
+            if let Some(position) = find(&self.buffer, &self.delimiter) {
+                self.buffer.drain(..position + self.delimiter.len());
+                return self.after_boundary();
+            }
+
+            // Keep the end of the buffer in case it holds the start of the delimiter.
+            let keep = self.delimiter.len() - 1;
+            if self.buffer.len() > keep {
+                self.buffer.drain(..self.buffer.len() - keep);
+            }
+            if !self.fill()? {
+                return Err(MultipartError::Malformed);
+            }
+        }
+    }
+
+    /// Work out what follows a boundary: `--` for the closing boundary, or a line break before the
+    /// next field's headers.
+    fn after_boundary(&mut self) -> Result<(), MultipartError> {
+        while self.buffer.len() < 2 {
+            if !self.fill()? {
+                return Err(MultipartError::Malformed);
+            }
+        }
+
+        if self.buffer.starts_with(b"--") {
+            self.state = State::Done;
+            return Ok(());
+        }
+
+        // Whitespace is allowed between the boundary and the line break.
+        loop {
+            match self.buffer.iter().position(|&b| b != b' ' && b != b'\t') {
+                Some(position) => {
+                    self.buffer.drain(..position);
+                    break;
+                }
+                None => {
+                    self.buffer.clear();
+                    if !self.fill()? {
+                        return Err(MultipartError::Malformed);
+                    }
+                }
+            }
+        }
+        while self.buffer.len() < 2 {
+            if !self.fill()? {
+                return Err(MultipartError::Malformed);
+            }
+        }
+        if !self.buffer.starts_with(b"\r\n") {
+            return Err(MultipartError::Malformed);
+        }
+        self.buffer.drain(..2);
+        self.state = State::Headers;
+        Ok(())
+    }
+
+    /// Read the headers of a field, up to and including the blank line which ends them.
+    fn read_headers(&mut self) -> Result<HeaderMap, MultipartError> {
+        let headers_end = loop {
+            // A field with no headers at all starts straight away with the blank line.
+            if self.buffer.starts_with(b"\r\n") {
+                break 0;
+            }
+            if let Some(position) = find(&self.buffer, b"\r\n\r\n") {
+                break position + 2;
+            }
+            if self.buffer.len() > MAX_FIELD_HEADERS_SIZE || !self.fill()? {
+                return Err(MultipartError::Malformed);
+            }
+        };
+
+        let block = std::str::from_utf8(&self.buffer[..headers_end])
+            .map_err(|_| MultipartError::Malformed)?;
+        let mut headers = HeaderMap::new();
+        for line in block.split("\r\n").filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':').ok_or(MultipartError::Malformed)?;
+            headers
+                .try_append(name, value)
+                .map_err(|_| MultipartError::Malformed)?;
+        }
+
+        self.buffer.drain(..headers_end + 2);
+        Ok(headers)
+    }
+
+    /// Read the data of a field, up to the next boundary.
+    fn read_data(&mut self) -> Result<Vec<u8>, MultipartError> {
+        let mut data = vec![];
+        loop {
+            if let Some(position) = find(&self.buffer, &self.delimiter) {
+                self.append_data(&mut data, position)?;
+                self.buffer.drain(..self.delimiter.len());
+                self.after_boundary()?;
+                return Ok(data);
+            }
+
+            // Everything except the last few bytes, which may be the start of the delimiter, is
+            // definitely part of this field.
+            let keep = self.delimiter.len() - 1;
+            if self.buffer.len() > keep {
+                self.append_data(&mut data, self.buffer.len() - keep)?;
+            }
+            if !self.fill()? {
+                return Err(MultipartError::Malformed);
+            }
+        }
+    }
+
+    /// Move the first `len` bytes of the buffer onto the end of `data`, enforcing the field limit.
+    fn append_data(&mut self, data: &mut Vec<u8>, len: usize) -> Result<(), MultipartError> {
+        if (data.len() + len) as u64 > self.field_limit {
+            return Err(MultipartError::FieldTooLarge);
+        }
+        data.extend(self.buffer.drain(..len));
+        Ok(())
+    }
+}
+
+impl FromRequest for Multipart {
+    /// Check the request is `multipart/form-data` and find its boundary. The body itself is not
+    /// read until the handler asks for the first field.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        let content_type: ContentType =
+            req.headers().typed_get().ok_or(ExtractError::BadRequest)?;
+        if content_type.mime_type() != "multipart/form-data" {
+            return Err(ExtractError::BadRequest);
+        }
+        let boundary = content_type
+            .param("boundary")
+            .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+            .ok_or(ExtractError::BadRequest)?
+            .to_string();
+
+        Ok(Self::new(req.into_body().into_reader(), &boundary))
+    }
+}
+
+/// Return the position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Find the field name and optional file name in a field's `Content-Disposition` header, such as
+/// `form-data; name="upload"; filename="a.txt"`.
+fn parse_content_disposition(
+    headers: &HeaderMap,
+) -> Result<(String, Option<String>), MultipartError> {
+    let value = headers
+        .get("Content-Disposition")
+        .ok_or(MultipartError::Malformed)?;
+    let mut params = split_params(value).into_iter();
+    let disposition = params.next().unwrap_or_default();
+    if !disposition.0.eq_ignore_ascii_case("form-data") {
+        return Err(MultipartError::Malformed);
+    }
+
+    let mut name = None;
+    let mut file_name = None;
+    for (key, value) in params {
+        if key.eq_ignore_ascii_case("name") {
+            name = Some(value);
+        } else if key.eq_ignore_ascii_case("filename") {
+            file_name = Some(value);
+        }
+    }
+
+    Ok((name.ok_or(MultipartError::Malformed)?, file_name))
+}
+
+/// Split a header value into `;`-separated parameters, unquoting any quoted values. The first
+/// item has no value and is returned with an empty one.
+fn split_params(value: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+    let mut current = String::new();
+    let mut chars = value.chars();
+    let mut in_quotes = false;
+    let mut parts: Vec<String> = vec![];
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            '\\' if in_quotes => {
+                current.push(c);
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            ';' if !in_quotes => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    for part in parts {
+        let (key, value) = part.split_once('=').unwrap_or((&part, ""));
+        params.push((key.trim().to_string(), unquote(value.trim())));
+    }
+    params
+}
+
+/// Remove the quotes from a quoted string, along with the backslashes which escape characters
+/// inside it.
+fn unquote(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            _ => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::http::{Body, Method};
+
+    const BODY: &[u8] = b"preamble is ignored\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Hello Rust\r\n\
+        --XyZ  \r\n\
+        content-disposition: form-data; name=\"upload\"; filename=\"a \\\"b\\\";c.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        line one\r\nline two\r\n--XY not a boundary\r\n\
+        --XyZ--\r\n\
+        epilogue is ignored";
+
+    /// A reader which hands out one byte at a time, so that every boundary and header is split
+    /// across reads.
+    struct OneByteReader(Cursor<Vec<u8>>);
+
+    impl Read for OneByteReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    fn assert_fields(mut multipart: Multipart) {
+        let field = multipart.next_field().unwrap().unwrap();
+        assert_eq!(field.name(), "title");
+        assert_eq!(field.file_name(), None);
+        assert_eq!(field.content_type(), None);
+        assert_eq!(field.text(), Some("Hello Rust"));
+
+        let field = multipart.next_field().unwrap().unwrap();
+        assert_eq!(field.name(), "upload");
+        assert_eq!(field.file_name(), Some("a \"b\";c.txt"));
+        assert_eq!(field.content_type(), Some("text/plain"));
+        assert_eq!(
+            field.bytes(),
+            b"line one\r\nline two\r\n--XY not a boundary"
+        );
+
+        assert!(multipart.next_field().unwrap().is_none());
+        assert!(multipart.next_field().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_multipart_buffered() {
+        assert_fields(Multipart::new(Cursor::new(BODY), "XyZ"));
+    }
+
+    #[test]
+    fn test_multipart_split_reads() {
+        assert_fields(Multipart::new(
+            OneByteReader(Cursor::new(BODY.to_vec())),
+            "XyZ",
+        ));
+    }
+
+    #[test]
+    fn test_multipart_from_request() {
+        let headers = HeaderMap::from([("Content-Type", "multipart/form-data; boundary=\"XyZ\"")]);
+        let mut req = Request::with_headers(Method::Post, "/", headers);
+        req.set_body(Body::from_reader(OneByteReader(Cursor::new(BODY.to_vec()))));
+        assert_fields(Multipart::from_request(req).unwrap());
+    }
+
+    #[test]
+    fn test_multipart_from_request_invalid_content_type() {
+        let headers = HeaderMap::from([("Content-Type", "multipart/form-data")]);
+        let req = Request::with_headers(Method::Post, "/", headers);
+        assert!(Multipart::from_request(req).is_err());
+
+        let headers = HeaderMap::from([("Content-Type", "text/plain; boundary=XyZ")]);
+        let req = Request::with_headers(Method::Post, "/", headers);
+        assert!(Multipart::from_request(req).is_err());
+    }
+
+    #[test]
+    fn test_multipart_field_limit() {
+        let mut multipart = Multipart::new(Cursor::new(BODY), "XyZ");
+        multipart.set_field_limit(10);
+        assert!(multipart.next_field().is_ok());
+        assert!(matches!(
+            multipart.next_field(),
+            Err(MultipartError::FieldTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_multipart_total_limit() {
+        let mut multipart = Multipart::new(Cursor::new(BODY), "XyZ");
+        multipart.set_total_limit(50);
+        assert!(matches!(
+            multipart.next_field(),
+            Err(MultipartError::TotalTooLarge)
+        ));
+        assert_eq!(
+            MultipartError::TotalTooLarge.into_response().status_code(),
+            413
+        );
+    }
+
+    #[test]
+    fn test_multipart_malformed() {
+        let mut multipart = Multipart::new(Cursor::new(b"no boundary here"), "XyZ");
+        assert!(matches!(
+            multipart.next_field(),
+            Err(MultipartError::Malformed)
+        ));
+
+        let body = b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nunterminated";
+        let mut multipart = Multipart::new(Cursor::new(body), "XyZ");
+        assert!(matches!(
+            multipart.next_field(),
+            Err(MultipartError::Malformed)
+        ));
+
+        let body = b"--XyZ\r\nContent-Type: text/plain\r\n\r\ndata\r\n--XyZ--";
+        let mut multipart = Multipart::new(Cursor::new(body), "XyZ");
+        assert!(matches!(
+            multipart.next_field(),
+            Err(MultipartError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("\"a\\\"b\""), "a\"b");
+        assert_eq!(unquote("plain"), "plain");
+    }
+}
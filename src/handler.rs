@@ -20,7 +20,7 @@ impl BoxedHandler {
     pub fn from_handler<H, T>(handler: H) -> Self
     where
         H: Handler<T> + Send + Sync + 'static,
-        T: Send + Sync + 'static,
+        T: 'static,
     {
         Self(Arc::new(MakeErasedHandler {
             handler,
@@ -40,15 +40,19 @@ impl Clone for BoxedHandler {
 }
 
 /// A struct to hold a [`Handler`] which hides away its type info using [`PhantomData`].
+///
+/// We use `PhantomData<fn() -> T>` rather than `PhantomData<T>` because we never hold a `T`, so
+/// this struct should be `Send` and `Sync` even when the extractors in `T` are not (for example,
+/// one which owns a handle to the connection).
 struct MakeErasedHandler<H, T> {
     handler: H,
-    _marker: PhantomData<T>,
+    _marker: PhantomData<fn() -> T>,
 }
 
 impl<H, T> ErasedHandler for MakeErasedHandler<H, T>
 where
     H: Handler<T> + Send + Sync + 'static,
-    T: Send + Sync + 'static,
+    T: 'static,
 {
     fn call_handler(&self, req: Request) -> Response {
         self.handler.call_handler(req)
@@ -90,13 +94,13 @@ macro_rules! handler_variable_args {
                     let parts = req.into_parts();
                     let $ty = match $ty::from_request_parts(&parts) {
                         Ok(value) => value,
-                        Err(e) => return e.into_response(),
+                        Err(err) => return <$ty as FromRequestParts>::rejection(err),
                     };
                 )*
 
                 let $last = match $last::from_request(req) {
                     Ok(value) => value,
-                    Err(e) => return e.into_response(),
+                    Err(err) => return <$last as FromRequest>::rejection(err),
                 };
 
                 let res = self($($ty,)* $last,);
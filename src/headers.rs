@@ -0,0 +1,1251 @@
+//! Typed representations of common HTTP headers.
+//!
+//! Rather than digging through a [`HeaderMap`] by name and parsing the string by hand, a handler
+//! can ask for one of these types with the [`TypedHeader`](crate::extract::TypedHeader)
+//! extractor, and return one alongside its body to set it on the response.
+use std::{fmt, str::FromStr};
+
+use crate::{
+    core::base64,
+    http::{HeaderMap, HttpDate, InvalidHeaderError},
+};
+
+/// A header with a known name which can be parsed from, and written back to, its string form.
+pub trait Header: Sized {
+    /// The name of the header, such as `Content-Type`.
+    const NAME: &'static str;
+
+    /// Parse the header from every value which was sent with its name. Most headers only allow a
+    /// single value, but list-based headers such as `Accept` may be split across several.
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>;
+
+    /// Convert the header into the value which should be sent.
+    fn encode(&self) -> String;
+}
+
+impl HeaderMap {
+    /// Parse the typed header `H`, returning `None` if it is missing or invalid.
+    pub fn typed_get<H: Header>(&self) -> Option<H> {
+        let mut values = self.get_all(H::NAME).peekable();
+        values.peek()?;
+        H::decode(values).ok()
+    }
+
+    /// Set the typed header `H`, replacing any values it already had.
+    pub fn typed_insert<H: Header>(&mut self, header: H) {
+        self.insert(H::NAME, header.encode());
+    }
+}
+
+/// Split a comma-separated header value into its trimmed, non-empty items. Commas inside a quoted
+/// string (such as an entity tag) do not split the item.
+pub(crate) fn split_list(value: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (index, c) in value.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                items.push(value[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(value[start..].trim());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Return the only value of a header which must not be repeated.
+fn single<'a, I: Iterator<Item = &'a str>>(mut values: I) -> Result<&'a str, InvalidHeaderError> {
+    let value = values.next().ok_or(InvalidHeaderError)?;
+    if values.next().is_some() {
+        return Err(InvalidHeaderError);
+    }
+    Ok(value)
+}
+
+/// A macro to define a header whose value is kept as an opaque string.
+macro_rules! string_header {
+    (
+        $(#[$doc:meta])* $name:ident, $header_name:literal
+    ) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name(pub String);
+
+        impl $name {
+            /// Return the value of the header.
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Header for $name {
+            const NAME: &'static str = $header_name;
+
+            fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+            where
+                I: Iterator<Item = &'a str>,
+            {
+                Ok(Self(single(values)?.to_string()))
+            }
+
+            fn encode(&self) -> String {
+                self.0.clone()
+            }
+        }
+    };
+}
+
+string_header!(
+    /// The `User-Agent` header, which identifies the client software making the request.
+    UserAgent,
+    "User-Agent"
+);
+string_header!(
+    /// The `Location` header, which gives the target of a redirect.
+    Location,
+    "Location"
+);
+string_header!(
+    /// The `Origin` header, which browsers send with cross-origin and unsafe requests.
+    Origin,
+    "Origin"
+);
+string_header!(
+    /// The `Referer` header, which gives the address of the page that made the request.
+    Referer,
+    "Referer"
+);
+string_header!(
+    /// The `Accept-Ranges` header, which says whether range requests are supported, such as
+    /// `bytes` or `none`.
+    AcceptRanges,
+    "Accept-Ranges"
+);
+string_header!(
+    /// The `Content-Encoding` header, which names the coding applied to the body, such as `gzip`.
+    ContentEncoding,
+    "Content-Encoding"
+);
+string_header!(
+    /// The `Cache-Control` header, which holds caching directives such as `no-store`.
+    CacheControl,
+    "Cache-Control"
+);
+
+/// A macro to define a header whose value is an [`HttpDate`].
+macro_rules! date_header {
+    (
+        $(#[$doc:meta])* $name:ident, $header_name:literal
+    ) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub struct $name(pub HttpDate);
+
+        impl Header for $name {
+            const NAME: &'static str = $header_name;
+
+            fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+            where
+                I: Iterator<Item = &'a str>,
+            {
+                single(values)?
+                    .parse()
+                    .map(Self)
+                    .map_err(|_| InvalidHeaderError)
+            }
+
+            fn encode(&self) -> String {
+                self.0.to_string()
+            }
+        }
+    };
+}
+
+date_header!(
+    /// The `Last-Modified` header, which gives the time the resource was last changed.
+    LastModified,
+    "Last-Modified"
+);
+date_header!(
+    /// The `If-Modified-Since` header, which asks for the resource only if it has changed since
+    /// the given time.
+    IfModifiedSince,
+    "If-Modified-Since"
+);
+date_header!(
+    /// The `If-Unmodified-Since` header, which makes a request conditional on the resource not
+    /// having changed since the given time.
+    IfUnmodifiedSince,
+    "If-Unmodified-Since"
+);
+
+/// The `Host` header, which names the host and optional port the request was sent to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Host(pub String);
+
+impl Host {
+    /// Return the host name without the port.
+    pub fn hostname(&self) -> &str {
+        self.split().0
+    }
+
+    /// Return the port, if one was given.
+    pub fn port(&self) -> Option<u16> {
+        self.split().1.and_then(|port| port.parse().ok())
+    }
+
+    /// Split the host from the port, taking care not to split an IPv6 address such as `[::1]`.
+    fn split(&self) -> (&str, Option<&str>) {
+        match self.0.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, Some(port)),
+            _ => (&self.0, None),
+        }
+    }
+}
+
+impl Header for Host {
+    const NAME: &'static str = "Host";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let value = single(values)?;
+        if value.is_empty() {
+            return Err(InvalidHeaderError);
+        }
+        Ok(Self(value.to_string()))
+    }
+
+    fn encode(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// The `Content-Type` header, which gives the media type of the body and any parameters such as
+/// its `charset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentType(pub String);
+
+impl ContentType {
+    /// `text/plain; charset=utf-8`
+    pub fn text() -> Self {
+        Self("text/plain; charset=utf-8".to_string())
+    }
+
+    /// `text/html; charset=utf-8`
+    pub fn html() -> Self {
+        Self("text/html; charset=utf-8".to_string())
+    }
+
+    /// `application/json`
+    pub fn json() -> Self {
+        Self("application/json".to_string())
+    }
+
+    /// `application/x-www-form-urlencoded`
+    pub fn form_url_encoded() -> Self {
+        Self("application/x-www-form-urlencoded".to_string())
+    }
+
+    /// `application/octet-stream`
+    pub fn octet_stream() -> Self {
+        Self("application/octet-stream".to_string())
+    }
+
+    /// Return the media type without any parameters, such as `text/plain`. Media types are
+    /// case-insensitive, so this is always lowercase.
+    pub fn mime_type(&self) -> String {
+        self.0
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+    }
+
+    /// Return the value of the parameter `name`, such as the `boundary` of a multipart body.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.0.split(';').skip(1).find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().trim_matches('"'))
+        })
+    }
+}
+
+impl Header for ContentType {
+    const NAME: &'static str = "Content-Type";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let value = single(values)?;
+        if !value.contains('/') {
+            return Err(InvalidHeaderError);
+        }
+        Ok(Self(value.to_string()))
+    }
+
+    fn encode(&self) -> String {
+        self.0.clone()
+    }
+}
+
+/// The `Content-Length` header, which gives the size of the body in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentLength(pub u64);
+
+impl Header for ContentLength {
+    const NAME: &'static str = "Content-Length";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        // Repeated lengths are only allowed if they all agree.
+        let mut length = None;
+        for value in values {
+            let parsed = value.parse().map_err(|_| InvalidHeaderError)?;
+            if length.is_some_and(|length| length != parsed) {
+                return Err(InvalidHeaderError);
+            }
+            length = Some(parsed);
+        }
+        length.map(Self).ok_or(InvalidHeaderError)
+    }
+
+    fn encode(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// The `Authorization` header, which holds the credentials for an authentication `scheme` such
+/// as `Basic` or `Bearer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Authorization {
+    scheme: String,
+    credentials: String,
+}
+
+impl Authorization {
+    /// Create an `Authorization` header for the given scheme and credentials.
+    pub fn new(scheme: &str, credentials: &str) -> Self {
+        Self {
+            scheme: scheme.to_string(),
+            credentials: credentials.to_string(),
+        }
+    }
+
+    /// Create a `Bearer` `Authorization` header.
+    pub fn bearer(token: &str) -> Self {
+        Self::new("Bearer", token)
+    }
+
+    /// Create a `Basic` `Authorization` header, which carries a username and password encoded as
+    /// base64.
+    pub fn basic(username: &str, password: &str) -> Self {
+        Self::new(
+            "Basic",
+            &base64::encode(format!("{}:{}", username, password).as_bytes()),
+        )
+    }
+
+    /// Return the authentication scheme, such as `Bearer`.
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Return the credentials which follow the scheme.
+    pub fn credentials(&self) -> &str {
+        &self.credentials
+    }
+
+    /// Return the token if this uses the `Bearer` scheme. Schemes are case-insensitive.
+    pub fn bearer_token(&self) -> Option<&str> {
+        self.scheme
+            .eq_ignore_ascii_case("Bearer")
+            .then_some(self.credentials.as_str())
+    }
+
+    /// Return the username and password if this uses the `Basic` scheme and they decode to
+    /// UTF-8. The username ends at the first colon, and the password may contain more.
+    pub fn basic_credentials(&self) -> Option<(String, String)> {
+        if !self.scheme.eq_ignore_ascii_case("Basic") {
+            return None;
+        }
+        let decoded = String::from_utf8(base64::decode(&self.credentials)?).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+        Some((username.to_string(), password.to_string()))
+    }
+}
+
+impl Header for Authorization {
+    const NAME: &'static str = "Authorization";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let value = single(values)?;
+        let (scheme, credentials) = value.split_once(' ').ok_or(InvalidHeaderError)?;
+        if scheme.is_empty() || credentials.trim().is_empty() {
+            return Err(InvalidHeaderError);
+        }
+        Ok(Self::new(scheme, credentials.trim()))
+    }
+
+    fn encode(&self) -> String {
+        format!("{} {}", self.scheme, self.credentials)
+    }
+}
+
+/// A value from a list-based header, such as `text/html;q=0.8`, together with its quality. The
+/// quality is kept in thousandths, so `q=0.8` is `800` and a missing `q` is `1000`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityItem {
+    pub value: String,
+    pub quality: u16,
+}
+
+impl QualityItem {
+    /// Parse a single item, stripping the `q` parameter from its value.
+    fn parse(item: &str) -> Result<Self, InvalidHeaderError> {
+        let mut quality = 1000;
+        let mut value = item;
+        if let Some((rest, param)) = item.rsplit_once(';') {
+            if let Some(q) = param.trim().strip_prefix("q=") {
+                quality = parse_quality(q).ok_or(InvalidHeaderError)?;
+                value = rest.trim();
+            }
+        }
+        if value.is_empty() {
+            return Err(InvalidHeaderError);
+        }
+        Ok(Self {
+            value: value.to_string(),
+            quality,
+        })
+    }
+
+    /// Parse every item of a list-based header.
+    pub(crate) fn parse_list<'a, I>(values: I) -> Result<Vec<Self>, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        values.flat_map(split_list).map(Self::parse).collect()
+    }
+}
+
+impl fmt::Display for QualityItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.quality == 1000 {
+            return write!(f, "{}", self.value);
+        }
+        let fraction = format!("{:03}", self.quality);
+        match fraction.trim_end_matches('0') {
+            "" => write!(f, "{};q=0", self.value),
+            fraction => write!(f, "{};q=0.{}", self.value, fraction),
+        }
+    }
+}
+
+/// Parse a quality value between `0` and `1` with up to three decimal places into thousandths.
+fn parse_quality(q: &str) -> Option<u16> {
+    let (whole, fraction) = q.split_once('.').unwrap_or((q, ""));
+    if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let thousandths = format!("{:0<3}", fraction).parse::<u16>().ok()?;
+    match whole {
+        "0" => Some(thousandths),
+        "1" if thousandths == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+/// The `Accept` header, which lists the media types a client will accept in order of preference.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accept(pub Vec<QualityItem>);
+
+impl Accept {
+    /// Return `true` if the client will accept `mime_type`, either directly or through a wildcard
+    /// such as `text/*` or `*/*`.
+    pub fn accepts(&self, mime_type: &str) -> bool {
+        let (kind, _) = mime_type.split_once('/').unwrap_or((mime_type, ""));
+        self.0.iter().filter(|item| item.quality > 0).any(|item| {
+            let range = item.value.split(';').next().unwrap_or_default().trim();
+            range == "*/*"
+                || range.eq_ignore_ascii_case(mime_type)
+                || range
+                    .strip_suffix("/*")
+                    .is_some_and(|range_kind| range_kind.eq_ignore_ascii_case(kind))
+        })
+    }
+}
+
+impl Header for Accept {
+    const NAME: &'static str = "Accept";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        Ok(Self(QualityItem::parse_list(values)?))
+    }
+
+    fn encode(&self) -> String {
+        let items: Vec<String> = self.0.iter().map(|item| item.to_string()).collect();
+        items.join(", ")
+    }
+}
+
+/// The `Accept-Encoding` header, which lists the content codings a client can decode, such as
+/// `gzip, deflate;q=0.5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcceptEncoding(pub Vec<QualityItem>);
+
+impl AcceptEncoding {
+    /// Return the quality the client gave `coding`, in thousandths. A coding which is not listed
+    /// takes the quality of `*` if there is one, and otherwise is not acceptable, except for
+    /// `identity` which always is unless it is refused.
+    pub fn quality(&self, coding: &str) -> u16 {
+        let find = |name: &str| {
+            self.0
+                .iter()
+                .find(|item| item.value.eq_ignore_ascii_case(name))
+                .map(|item| item.quality)
+        };
+        find(coding)
+            .or_else(|| find("*"))
+            .unwrap_or(if coding.eq_ignore_ascii_case("identity") {
+                1000
+            } else {
+                0
+            })
+    }
+}
+
+impl Header for AcceptEncoding {
+    const NAME: &'static str = "Accept-Encoding";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        Ok(Self(QualityItem::parse_list(values)?))
+    }
+
+    fn encode(&self) -> String {
+        let items: Vec<String> = self.0.iter().map(|item| item.to_string()).collect();
+        items.join(", ")
+    }
+}
+
+/// An entity tag, which identifies a specific version of a resource. A weak tag only promises the
+/// content is equivalent, not byte-for-byte identical.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntityTag {
+    weak: bool,
+    tag: String,
+}
+
+impl EntityTag {
+    /// Create a strong entity tag. The tag must not contain a double quote.
+    pub fn strong(tag: &str) -> Self {
+        Self {
+            weak: false,
+            tag: tag.to_string(),
+        }
+    }
+
+    /// Create a weak entity tag. The tag must not contain a double quote.
+    pub fn weak(tag: &str) -> Self {
+        Self {
+            weak: true,
+            tag: tag.to_string(),
+        }
+    }
+
+    /// Return `true` if this is a weak tag.
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Return the tag without its quotes or weak prefix.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Compare two tags using the strong comparison, where both must be strong and identical.
+    pub fn strong_eq(&self, other: &Self) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Compare two tags using the weak comparison, where only the tags need to match.
+    pub fn weak_eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl FromStr for EntityTag {
+    type Err = InvalidHeaderError;
+
+    /// Parse a tag such as `"abc"` or `W/"abc"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (weak, quoted) = match s.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, s),
+        };
+        let tag = quoted
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .ok_or(InvalidHeaderError)?;
+        if tag.contains('"') {
+            return Err(InvalidHeaderError);
+        }
+        Ok(Self {
+            weak,
+            tag: tag.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// The `ETag` header, which gives the entity tag of the response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ETag(pub EntityTag);
+
+impl Header for ETag {
+    const NAME: &'static str = "ETag";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        Ok(Self(single(values)?.parse()?))
+    }
+
+    fn encode(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// A list of entity tags, or `*` to match any current version of the resource. This is the value
+/// of both `If-Match` and `If-None-Match`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntityTagList {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl EntityTagList {
+    /// Parse the list from every value of a header.
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let items: Vec<&str> = values.flat_map(split_list).collect();
+        if items == ["*"] {
+            return Ok(Self::Any);
+        }
+        let tags = items
+            .into_iter()
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::Tags(tags))
+    }
+
+    fn encode(&self) -> String {
+        match self {
+            Self::Any => "*".to_string(),
+            Self::Tags(tags) => {
+                let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+                tags.join(", ")
+            }
+        }
+    }
+}
+
+/// The `If-None-Match` header, which makes a request conditional on the resource *not* matching
+/// any of the given entity tags. Tags are compared using the weak comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfNoneMatch(pub EntityTagList);
+
+impl IfNoneMatch {
+    /// Return `true` if the condition passes for a resource with the entity tag `etag`, meaning
+    /// the full response should be sent.
+    pub fn precondition_passes(&self, etag: &EntityTag) -> bool {
+        match &self.0 {
+            EntityTagList::Any => false,
+            EntityTagList::Tags(tags) => !tags.iter().any(|tag| tag.weak_eq(etag)),
+        }
+    }
+}
+
+impl Header for IfNoneMatch {
+    const NAME: &'static str = "If-None-Match";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        Ok(Self(EntityTagList::decode(values)?))
+    }
+
+    fn encode(&self) -> String {
+        self.0.encode()
+    }
+}
+
+/// The `If-Match` header, which makes a request conditional on the resource matching one of the
+/// given entity tags. Tags are compared using the strong comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfMatch(pub EntityTagList);
+
+impl IfMatch {
+    /// Return `true` if the condition passes for a resource with the entity tag `etag`.
+    pub fn precondition_passes(&self, etag: &EntityTag) -> bool {
+        match &self.0 {
+            EntityTagList::Any => true,
+            EntityTagList::Tags(tags) => tags.iter().any(|tag| tag.strong_eq(etag)),
+        }
+    }
+}
+
+impl Header for IfMatch {
+    const NAME: &'static str = "If-Match";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        Ok(Self(EntityTagList::decode(values)?))
+    }
+
+    fn encode(&self) -> String {
+        self.0.encode()
+    }
+}
+
+/// The `Vary` header, which lists the request headers that were used to choose the response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vary(pub Vec<String>);
+
+impl Header for Vary {
+    const NAME: &'static str = "Vary";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        Ok(Self(
+            values.flat_map(split_list).map(str::to_string).collect(),
+        ))
+    }
+
+    fn encode(&self) -> String {
+        self.0.join(", ")
+    }
+}
+
+/// One range of bytes requested in a `Range` header. Positions are zero-based and inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The bytes from the first position to the last, such as `0-499`.
+    Bounded(u64, u64),
+    /// Every byte from a position to the end, such as `500-`.
+    From(u64),
+    /// The given number of bytes at the end, such as `-500`.
+    Last(u64),
+}
+
+impl ByteRange {
+    /// Return the first and last positions this range covers in a representation of `len` bytes,
+    /// or `None` if it covers none of them. A range which runs past the end is cut short.
+    pub fn to_bounds(&self, len: u64) -> Option<(u64, u64)> {
+        match *self {
+            Self::Bounded(first, last) if first < len => Some((first, last.min(len - 1))),
+            Self::From(first) if first < len => Some((first, len - 1)),
+            Self::Last(count) if count > 0 && len > 0 => Some((len.saturating_sub(count), len - 1)),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ByteRange {
+    type Err = InvalidHeaderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, last) = s.trim().split_once('-').ok_or(InvalidHeaderError)?;
+        let parse = |digits: &str| {
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(InvalidHeaderError);
+            }
+            digits.parse::<u64>().map_err(|_| InvalidHeaderError)
+        };
+        match (first, last) {
+            ("", count) => Ok(Self::Last(parse(count)?)),
+            (first, "") => Ok(Self::From(parse(first)?)),
+            (first, last) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(InvalidHeaderError);
+                }
+                Ok(Self::Bounded(first, last))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bounded(first, last) => write!(f, "{}-{}", first, last),
+            Self::From(first) => write!(f, "{}-", first),
+            Self::Last(count) => write!(f, "-{}", count),
+        }
+    }
+}
+
+/// The `Range` header, which asks for only some of the bytes of a representation, such as
+/// `bytes=0-499, -100`. Only the `bytes` unit is supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range(pub Vec<ByteRange>);
+
+impl Header for Range {
+    const NAME: &'static str = "Range";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let (unit, ranges) = single(values)?.split_once('=').ok_or(InvalidHeaderError)?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return Err(InvalidHeaderError);
+        }
+        let ranges = split_list(ranges)
+            .into_iter()
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if ranges.is_empty() {
+            return Err(InvalidHeaderError);
+        }
+        Ok(Self(ranges))
+    }
+
+    fn encode(&self) -> String {
+        let ranges: Vec<String> = self.0.iter().map(|range| range.to_string()).collect();
+        format!("bytes={}", ranges.join(", "))
+    }
+}
+
+/// The `Content-Range` header, which says which bytes of a representation of `complete_length`
+/// bytes a partial response holds. A `range` of `None` is sent with a 416 response, to say that
+/// none of the requested ranges could be satisfied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentRange {
+    pub range: Option<(u64, u64)>,
+    pub complete_length: u64,
+}
+
+impl Header for ContentRange {
+    const NAME: &'static str = "Content-Range";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let value = single(values)?;
+        let rest = value.strip_prefix("bytes ").ok_or(InvalidHeaderError)?;
+        let (range, complete_length) = rest.split_once('/').ok_or(InvalidHeaderError)?;
+        let complete_length = complete_length.parse().map_err(|_| InvalidHeaderError)?;
+        let range = match range {
+            "*" => None,
+            range => match range.parse()? {
+                ByteRange::Bounded(first, last) if last < complete_length => Some((first, last)),
+                _ => return Err(InvalidHeaderError),
+            },
+        };
+        Ok(Self {
+            range,
+            complete_length,
+        })
+    }
+
+    fn encode(&self) -> String {
+        match self.range {
+            Some((first, last)) => format!("bytes {}-{}/{}", first, last, self.complete_length),
+            None => format!("bytes */{}", self.complete_length),
+        }
+    }
+}
+
+/// The `If-Range` header, which asks for the `Range` to be ignored, and the whole representation
+/// sent instead, unless it still has the given entity tag or modification date.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IfRange {
+    ETag(EntityTag),
+    Date(HttpDate),
+}
+
+impl IfRange {
+    /// Return `true` if the range should be honoured for a representation with these validators.
+    /// Only a strong entity tag, or a date exactly equal to the modification date, can match.
+    pub fn matches(&self, etag: Option<&EntityTag>, last_modified: Option<HttpDate>) -> bool {
+        match self {
+            Self::ETag(tag) => etag.is_some_and(|etag| tag.strong_eq(etag)),
+            Self::Date(date) => last_modified == Some(*date),
+        }
+    }
+}
+
+impl Header for IfRange {
+    const NAME: &'static str = "If-Range";
+
+    fn decode<'a, I>(values: I) -> Result<Self, InvalidHeaderError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let value = single(values)?;
+        if value.starts_with('"') || value.starts_with("W/") {
+            Ok(Self::ETag(value.parse()?))
+        } else {
+            value
+                .parse()
+                .map(Self::Date)
+                .map_err(|_| InvalidHeaderError)
+        }
+    }
+
+    fn encode(&self) -> String {
+        match self {
+            Self::ETag(tag) => tag.to_string(),
+            Self::Date(date) => date.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_list() {
+        assert_eq!(split_list(" a, b ,,c "), vec!["a", "b", "c"]);
+        assert_eq!(split_list(r#""a,b", W/"c""#), vec![r#""a,b""#, r#"W/"c""#]);
+        assert!(split_list("").is_empty());
+    }
+
+    #[test]
+    fn test_typed_get_and_insert() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(headers.typed_get::<ContentLength>(), None);
+
+        headers.typed_insert(ContentLength(42));
+        assert_eq!(headers.get("content-length"), Some("42"));
+        assert_eq!(
+            headers.typed_get::<ContentLength>(),
+            Some(ContentLength(42))
+        );
+
+        headers.insert("Content-Length", "nope");
+        assert_eq!(headers.typed_get::<ContentLength>(), None);
+    }
+
+    #[test]
+    fn test_content_type() {
+        let headers = HeaderMap::from([("Content-Type", "Multipart/Form-Data; boundary=\"abc\"")]);
+        let content_type: ContentType = headers.typed_get().unwrap();
+        assert_eq!(content_type.mime_type(), "multipart/form-data");
+        assert_eq!(content_type.param("BOUNDARY"), Some("abc"));
+        assert_eq!(content_type.param("charset"), None);
+        assert_eq!(ContentType::json().mime_type(), "application/json");
+
+        let headers = HeaderMap::from([("Content-Type", "nonsense")]);
+        assert_eq!(headers.typed_get::<ContentType>(), None);
+    }
+
+    #[test]
+    fn test_content_length_repeated() {
+        let headers = HeaderMap::from([("Content-Length", "5"), ("Content-Length", "5")]);
+        assert_eq!(headers.typed_get(), Some(ContentLength(5)));
+
+        let headers = HeaderMap::from([("Content-Length", "5"), ("Content-Length", "6")]);
+        assert_eq!(headers.typed_get::<ContentLength>(), None);
+    }
+
+    #[test]
+    fn test_authorization() {
+        let headers = HeaderMap::from([("Authorization", "bearer abc.def")]);
+        let authorization: Authorization = headers.typed_get().unwrap();
+        assert_eq!(authorization.scheme(), "bearer");
+        assert_eq!(authorization.bearer_token(), Some("abc.def"));
+        assert_eq!(authorization.encode(), "bearer abc.def");
+
+        let headers = HeaderMap::from([("Authorization", "Basic dXNlcjpwYXNz")]);
+        let authorization: Authorization = headers.typed_get().unwrap();
+        assert_eq!(authorization.bearer_token(), None);
+        assert_eq!(authorization.credentials(), "dXNlcjpwYXNz");
+        assert_eq!(
+            authorization.basic_credentials(),
+            Some(("user".to_string(), "pass".to_string()))
+        );
+        assert_eq!(Authorization::basic("user", "pass"), authorization);
+
+        let authorization = Authorization::basic("user", "p:a:ss");
+        assert_eq!(
+            authorization.basic_credentials(),
+            Some(("user".to_string(), "p:a:ss".to_string()))
+        );
+        assert_eq!(
+            Authorization::new("Basic", "not base64").basic_credentials(),
+            None
+        );
+        assert_eq!(
+            Authorization::new("Basic", "dXNlcg==").basic_credentials(),
+            None
+        );
+
+        let headers = HeaderMap::from([("Authorization", "Bearer")]);
+        assert_eq!(headers.typed_get::<Authorization>(), None);
+    }
+
+    #[test]
+    fn test_accept() {
+        let headers = HeaderMap::from([
+            ("Accept", "text/html, application/xml;q=0.9"),
+            ("Accept", "image/*;q=0, */*;q=0.8"),
+        ]);
+        let accept: Accept = headers.typed_get().unwrap();
+        assert_eq!(
+            accept.0,
+            vec![
+                QualityItem {
+                    value: "text/html".to_string(),
+                    quality: 1000
+                },
+                QualityItem {
+                    value: "application/xml".to_string(),
+                    quality: 900
+                },
+                QualityItem {
+                    value: "image/*".to_string(),
+                    quality: 0
+                },
+                QualityItem {
+                    value: "*/*".to_string(),
+                    quality: 800
+                },
+            ]
+        );
+        assert_eq!(
+            accept.encode(),
+            "text/html, application/xml;q=0.9, image/*;q=0, */*;q=0.8"
+        );
+        assert!(accept.accepts("application/json"));
+
+        let accept = Accept(vec![QualityItem {
+            value: "text/*".to_string(),
+            quality: 1000,
+        }]);
+        assert!(accept.accepts("text/plain"));
+        assert!(!accept.accepts("application/json"));
+    }
+
+    #[test]
+    fn test_parse_quality() {
+        assert_eq!(parse_quality("1"), Some(1000));
+        assert_eq!(parse_quality("1.000"), Some(1000));
+        assert_eq!(parse_quality("0.5"), Some(500));
+        assert_eq!(parse_quality("0.05"), Some(50));
+        assert_eq!(parse_quality("0"), Some(0));
+        assert_eq!(parse_quality("1.1"), None);
+        assert_eq!(parse_quality("0.1234"), None);
+        assert_eq!(parse_quality("2"), None);
+    }
+
+    #[test]
+    fn test_host() {
+        let host = Host("example.com:8080".to_string());
+        assert_eq!(host.hostname(), "example.com");
+        assert_eq!(host.port(), Some(8080));
+
+        let host = Host("[::1]".to_string());
+        assert_eq!(host.hostname(), "[::1]");
+        assert_eq!(host.port(), None);
+
+        let host = Host("[::1]:80".to_string());
+        assert_eq!(host.hostname(), "[::1]");
+        assert_eq!(host.port(), Some(80));
+    }
+
+    #[test]
+    fn test_accept_encoding() {
+        let headers = HeaderMap::from([("Accept-Encoding", "GZIP;q=0.5, br, identity;q=0")]);
+        let accept_encoding: AcceptEncoding = headers.typed_get().unwrap();
+        assert_eq!(accept_encoding.quality("gzip"), 500);
+        assert_eq!(accept_encoding.quality("br"), 1000);
+        assert_eq!(accept_encoding.quality("deflate"), 0);
+        assert_eq!(accept_encoding.quality("identity"), 0);
+
+        let headers = HeaderMap::from([("Accept-Encoding", "*;q=0.2")]);
+        let accept_encoding: AcceptEncoding = headers.typed_get().unwrap();
+        assert_eq!(accept_encoding.quality("deflate"), 200);
+        assert_eq!(accept_encoding.encode(), "*;q=0.2");
+    }
+
+    #[test]
+    fn test_entity_tag() {
+        let strong: EntityTag = "\"abc\"".parse().unwrap();
+        let weak: EntityTag = "W/\"abc\"".parse().unwrap();
+        assert!(!strong.is_weak());
+        assert!(weak.is_weak());
+        assert!(strong.weak_eq(&weak));
+        assert!(!strong.strong_eq(&weak));
+        assert!(strong.strong_eq(&EntityTag::strong("abc")));
+        assert_eq!(weak.to_string(), "W/\"abc\"");
+        assert!("abc".parse::<EntityTag>().is_err());
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let headers = HeaderMap::from([("If-None-Match", "\"a\", W/\"b\"")]);
+        let if_none_match: IfNoneMatch = headers.typed_get().unwrap();
+        assert!(!if_none_match.precondition_passes(&EntityTag::strong("b")));
+        assert!(if_none_match.precondition_passes(&EntityTag::strong("c")));
+
+        let headers = HeaderMap::from([("If-None-Match", "*")]);
+        let if_none_match: IfNoneMatch = headers.typed_get().unwrap();
+        assert_eq!(if_none_match.0, EntityTagList::Any);
+        assert!(!if_none_match.precondition_passes(&EntityTag::strong("c")));
+    }
+
+    #[test]
+    fn test_if_match() {
+        let headers = HeaderMap::from([("If-Match", "\"a\", W/\"b\"")]);
+        let if_match: IfMatch = headers.typed_get().unwrap();
+        assert!(if_match.precondition_passes(&EntityTag::strong("a")));
+        assert!(!if_match.precondition_passes(&EntityTag::strong("b")));
+        assert_eq!(if_match.encode(), "\"a\", W/\"b\"");
+    }
+
+    #[test]
+    fn test_string_headers() {
+        let headers = HeaderMap::from([("User-Agent", "curl/8.4.0")]);
+        let user_agent: UserAgent = headers.typed_get().unwrap();
+        assert_eq!(user_agent.as_str(), "curl/8.4.0");
+
+        let headers = HeaderMap::from([("Vary", "Origin, Accept-Encoding")]);
+        let vary: Vary = headers.typed_get().unwrap();
+        assert_eq!(vary.0, vec!["Origin", "Accept-Encoding"]);
+    }
+
+    #[test]
+    fn test_date_headers() {
+        let headers = HeaderMap::from([("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        let last_modified: LastModified = headers.typed_get().unwrap();
+        assert_eq!(last_modified.0, HttpDate::from_unix_secs(784_111_777));
+        assert_eq!(last_modified.encode(), "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        let headers = HeaderMap::from([("Last-Modified", "yesterday")]);
+        assert_eq!(headers.typed_get::<LastModified>(), None);
+
+        let headers = HeaderMap::from([("If-Modified-Since", "Sunday, 06-Nov-94 08:49:37 GMT")]);
+        let if_modified_since: IfModifiedSince = headers.typed_get().unwrap();
+        assert_eq!(if_modified_since.0, HttpDate::from_unix_secs(784_111_777));
+    }
+
+    #[test]
+    fn test_range() {
+        let headers = HeaderMap::from([("Range", "bytes=0-499, 500-, -100")]);
+        let range: Range = headers.typed_get().unwrap();
+        assert_eq!(
+            range.0,
+            vec![
+                ByteRange::Bounded(0, 499),
+                ByteRange::From(500),
+                ByteRange::Last(100)
+            ]
+        );
+        assert_eq!(range.encode(), "bytes=0-499, 500-, -100");
+
+        for invalid in [
+            "bytes=5-1",
+            "bytes=",
+            "bytes=-",
+            "items=0-1",
+            "bytes=a-b",
+            "bytes=+1-2",
+        ] {
+            let headers = HeaderMap::from([("Range", invalid)]);
+            assert_eq!(headers.typed_get::<Range>(), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_byte_range_to_bounds() {
+        assert_eq!(ByteRange::Bounded(0, 499).to_bounds(1000), Some((0, 499)));
+        assert_eq!(
+            ByteRange::Bounded(900, 1999).to_bounds(1000),
+            Some((900, 999))
+        );
+        assert_eq!(ByteRange::Bounded(1000, 1999).to_bounds(1000), None);
+        assert_eq!(ByteRange::From(10).to_bounds(1000), Some((10, 999)));
+        assert_eq!(ByteRange::Last(100).to_bounds(1000), Some((900, 999)));
+        assert_eq!(ByteRange::Last(5000).to_bounds(1000), Some((0, 999)));
+        assert_eq!(ByteRange::Last(0).to_bounds(1000), None);
+        assert_eq!(ByteRange::Last(10).to_bounds(0), None);
+    }
+
+    #[test]
+    fn test_content_range() {
+        let headers = HeaderMap::from([("Content-Range", "bytes 0-499/1000")]);
+        let content_range: ContentRange = headers.typed_get().unwrap();
+        assert_eq!(content_range.range, Some((0, 499)));
+        assert_eq!(content_range.complete_length, 1000);
+
+        let unsatisfied = ContentRange {
+            range: None,
+            complete_length: 1000,
+        };
+        assert_eq!(unsatisfied.encode(), "bytes */1000");
+
+        let headers = HeaderMap::from([("Content-Range", "bytes 0-1000/1000")]);
+        assert_eq!(headers.typed_get::<ContentRange>(), None);
+    }
+
+    #[test]
+    fn test_if_range() {
+        let etag = EntityTag::strong("abc");
+        let date = HttpDate::from_unix_secs(784_111_777);
+
+        let headers = HeaderMap::from([("If-Range", "\"abc\"")]);
+        let if_range: IfRange = headers.typed_get().unwrap();
+        assert!(if_range.matches(Some(&etag), None));
+        assert!(!if_range.matches(Some(&EntityTag::weak("abc")), None));
+        assert!(!if_range.matches(None, Some(date)));
+
+        let headers = HeaderMap::from([("If-Range", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        let if_range: IfRange = headers.typed_get().unwrap();
+        assert!(if_range.matches(None, Some(date)));
+        assert!(!if_range.matches(None, Some(HttpDate::from_unix_secs(0))));
+    }
+}
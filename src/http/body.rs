@@ -0,0 +1,392 @@
+use std::{
+    fmt,
+    io::{self, IoSlice, Read, Write},
+};
+
+use crate::core::write_all_vectored;
+
+/// The size of the buffer used when copying a streaming body to a writer.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// The different sources a [`Body`] can produce its bytes from.
+enum Kind {
+    /// Every byte of the body is already held in memory.
+    Full(Vec<u8>),
+    /// The bytes are pulled from a reader as the response is written. `len` is `None` when the
+    /// total size is not known ahead of time.
+    Reader {
+        reader: Box<dyn Read + Send>,
+        len: Option<u64>,
+    },
+    /// The bytes are produced one chunk at a time by an iterator. The total size is never known.
+    Chunks(Box<dyn Iterator<Item = Vec<u8>> + Send>),
+}
+
+/// Represents the body of an HTTP `Request` or `Response`.
+///
+/// A body can either be fully buffered in memory, or be backed by a `Read`er or an iterator of
+/// chunks so that large payloads never need to sit in memory all at once. When a streaming body
+/// does not know its length, it is sent using `Transfer-Encoding: chunked`.
+pub struct Body {
+    kind: Kind,
+}
+
+impl Body {
+    /// Create a `Body` with no content.
+    pub fn empty() -> Self {
+        Self::from(Vec::new())
+    }
+
+    /// Create a `Body` which streams from `reader` until it reaches EOF. Since the length is not
+    /// known up front, it will be sent using chunked transfer encoding.
+    pub fn from_reader<R: Read + Send + 'static>(reader: R) -> Self {
+        Self {
+            kind: Kind::Reader {
+                reader: Box::new(reader),
+                len: None,
+            },
+        }
+    }
+
+    /// Create a `Body` which streams exactly `len` bytes from `reader`. This is sent with a
+    /// `Content-Length` header rather than chunked transfer encoding.
+    pub fn sized_reader<R: Read + Send + 'static>(reader: R, len: u64) -> Self {
+        Self {
+            kind: Kind::Reader {
+                reader: Box::new(reader.take(len)),
+                len: Some(len),
+            },
+        }
+    }
+
+    /// Create a `Body` which is produced one chunk at a time by `chunks`.
+    pub fn from_chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        I::IntoIter: Send + 'static,
+    {
+        Self {
+            kind: Kind::Chunks(Box::new(chunks.into_iter())),
+        }
+    }
+
+    /// Return the length of the body in bytes, if it is known before the body is written.
+    pub fn len(&self) -> Option<u64> {
+        match &self.kind {
+            Kind::Full(bytes) => Some(bytes.len() as u64),
+            Kind::Reader { len, .. } => *len,
+            Kind::Chunks(_) => None,
+        }
+    }
+
+    /// Return `true` if the body is known to contain no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// Return `true` if the body is streamed rather than held in memory.
+    pub fn is_streaming(&self) -> bool {
+        !matches!(self.kind, Kind::Full(_))
+    }
+
+    /// Return the bytes of a body which is held in memory, or `None` if it is streamed.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match &self.kind {
+            Kind::Full(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Consume the body and collect all of its bytes into memory. For a streaming body, this
+    /// drains the underlying reader or iterator.
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self.kind {
+            Kind::Full(bytes) => Ok(bytes),
+            Kind::Reader { mut reader, .. } => {
+                let mut bytes = vec![];
+                reader.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+            Kind::Chunks(chunks) => Ok(chunks.flatten().collect()),
+        }
+    }
+
+    /// Consume the body and return a reader over its bytes. Nothing is buffered beyond what the
+    /// underlying source already holds.
+    pub fn into_reader(self) -> Box<dyn Read + Send> {
+        match self.kind {
+            Kind::Full(bytes) => Box::new(io::Cursor::new(bytes)),
+            Kind::Reader { reader, .. } => reader,
+            Kind::Chunks(chunks) => Box::new(ChunksReader {
+                chunks,
+                current: io::Cursor::new(vec![]),
+            }),
+        }
+    }
+
+    /// Write `head` followed by the body to `writer`, returning the number of bytes written. When
+    /// the length of the body is not known, each piece is framed as an HTTP/1.1 chunk and the
+    /// terminating zero-length chunk is written at the end.
+    ///
+    /// The head is sent in the same vectored write as the first piece of the body, so a response
+    /// with a small body usually reaches the socket in a single call without ever being copied
+    /// into one buffer.
+    pub(crate) fn write_after<W: Write>(self, writer: &mut W, head: &[u8]) -> io::Result<usize> {
+        let chunked = self.len().is_none();
+        match self.kind {
+            Kind::Full(bytes) => {
+                write_all_vectored(writer, &mut [IoSlice::new(head), IoSlice::new(&bytes)])
+            }
+            Kind::Reader { mut reader, .. } => {
+                let mut buffer = [0; CHUNK_SIZE];
+                let mut head = head;
+                let mut total = 0;
+                loop {
+                    let num_bytes_read = match reader.read(&mut buffer) {
+                        Ok(0) => break,
+                        Ok(n) => n,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(e),
+                    };
+                    total += write_piece(writer, head, &buffer[..num_bytes_read], chunked)?;
+                    head = &[];
+                }
+                total += write_end(writer, head, chunked)?;
+                Ok(total)
+            }
+            Kind::Chunks(chunks) => {
+                let mut head = head;
+                let mut total = 0;
+                // An empty chunk would be read by the client as the end of the body, so we skip
+                // them.
+                for chunk in chunks.filter(|chunk| !chunk.is_empty()) {
+                    total += write_piece(writer, head, &chunk, true)?;
+                    head = &[];
+                }
+                total += write_end(writer, head, true)?;
+                Ok(total)
+            }
+        }
+    }
+}
+
+/// Adapts an iterator of chunks into a `Read`er by handing out one chunk at a time.
+struct ChunksReader {
+    chunks: Box<dyn Iterator<Item = Vec<u8>> + Send>,
+    current: io::Cursor<Vec<u8>>,
+}
+
+impl Read for ChunksReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let num_bytes_read = self.current.read(buf)?;
+            if num_bytes_read > 0 || buf.is_empty() {
+                return Ok(num_bytes_read);
+            }
+
+            match self.chunks.next() {
+                Some(chunk) => self.current = io::Cursor::new(chunk),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Write a piece of a body, optionally framing it as a chunk. Anything in `prefix` which has not
+/// been sent yet is written first.
+fn write_piece<W: Write>(
+    writer: &mut W,
+    prefix: &[u8],
+    piece: &[u8],
+    chunked: bool,
+) -> io::Result<usize> {
+    if !chunked {
+        return write_all_vectored(writer, &mut [IoSlice::new(prefix), IoSlice::new(piece)]);
+    }
+
+    let size_line = format!("{:X}\r\n", piece.len());
+    write_all_vectored(
+        writer,
+        &mut [
+            IoSlice::new(prefix),
+            IoSlice::new(size_line.as_bytes()),
+            IoSlice::new(piece),
+            IoSlice::new(b"\r\n"),
+        ],
+    )
+}
+
+/// Finish writing a body. For a chunked body this is the zero-length chunk which marks its end.
+/// Anything in `prefix` which has not been sent yet is written first.
+fn write_end<W: Write>(writer: &mut W, prefix: &[u8], chunked: bool) -> io::Result<usize> {
+    let last_chunk: &[u8] = if chunked { b"0\r\n\r\n" } else { b"" };
+    write_all_vectored(
+        writer,
+        &mut [IoSlice::new(prefix), IoSlice::new(last_chunk)],
+    )
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            Kind::Full(bytes) => f.debug_tuple("Full").field(&bytes.len()).finish(),
+            Kind::Reader { len, .. } => f.debug_struct("Reader").field("len", len).finish(),
+            Kind::Chunks(_) => f.write_str("Chunks"),
+        }
+    }
+}
+
+/// Two bodies are only considered equal when both are held in memory with the same bytes, since a
+/// stream cannot be compared without consuming it.
+impl PartialEq for Body {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.as_bytes(), other.as_bytes()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Self {
+            kind: Kind::Full(value),
+        }
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(value: &[u8]) -> Self {
+        value.to_vec().into()
+    }
+}
+
+impl From<String> for Body {
+    fn from(value: String) -> Self {
+        value.into_bytes().into()
+    }
+}
+
+impl From<&str> for Body {
+    fn from(value: &str) -> Self {
+        value.to_string().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::core::TrickleWriter;
+
+    #[test]
+    fn test_body_full() {
+        let body = Body::from("Hello");
+        assert_eq!(body.len(), Some(5));
+        assert!(!body.is_streaming());
+        assert_eq!(body.as_bytes(), Some(&b"Hello"[..]));
+
+        let mut output = vec![];
+        assert_eq!(body.write_after(&mut output, b"").unwrap(), 5);
+        assert_eq!(output, b"Hello");
+    }
+
+    #[test]
+    fn test_body_empty() {
+        let body = Body::empty();
+        assert!(body.is_empty());
+        assert_eq!(body.into_bytes().unwrap(), b"");
+    }
+
+    #[test]
+    fn test_body_sized_reader() {
+        let body = Body::sized_reader(Cursor::new(b"Hello, World!".to_vec()), 5);
+        assert_eq!(body.len(), Some(5));
+        assert!(body.is_streaming());
+        assert_eq!(body.as_bytes(), None);
+
+        let mut output = vec![];
+        assert_eq!(body.write_after(&mut output, b"").unwrap(), 5);
+        assert_eq!(output, b"Hello");
+    }
+
+    #[test]
+    fn test_body_unsized_reader_is_chunked() {
+        let body = Body::from_reader(Cursor::new(b"Hello, World!".to_vec()));
+        assert_eq!(body.len(), None);
+
+        let mut output = vec![];
+        let num_bytes_written = body.write_after(&mut output, b"").unwrap();
+        assert_eq!(output, b"D\r\nHello, World!\r\n0\r\n\r\n");
+        assert_eq!(num_bytes_written, output.len());
+    }
+
+    #[test]
+    fn test_body_chunks() {
+        let chunks = vec![b"Hello".to_vec(), vec![], b", World!".to_vec()];
+        let body = Body::from_chunks(chunks);
+        assert_eq!(body.len(), None);
+
+        let mut output = vec![];
+        body.write_after(&mut output, b"").unwrap();
+        assert_eq!(output, b"5\r\nHello\r\n8\r\n, World!\r\n0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_body_write_after_partial_writes() {
+        let mut writer = TrickleWriter::new();
+        let body = Body::from_chunks(vec![b"Hello".to_vec(), b", World!".to_vec()]);
+        let num_bytes_written = body.write_after(&mut writer, b"HEAD\r\n").unwrap();
+        assert_eq!(
+            writer.output,
+            b"HEAD\r\n5\r\nHello\r\n8\r\n, World!\r\n0\r\n\r\n"
+        );
+        assert_eq!(num_bytes_written, writer.output.len());
+
+        let mut writer = TrickleWriter::new();
+        let body = Body::sized_reader(Cursor::new(vec![]), 0);
+        body.write_after(&mut writer, b"HEAD\r\n").unwrap();
+        assert_eq!(writer.output, b"HEAD\r\n");
+    }
+
+    #[test]
+    fn test_body_into_reader() {
+        let mut output = String::new();
+        let body = Body::from_chunks(vec![b"Hello".to_vec(), vec![], b", World!".to_vec()]);
+        body.into_reader().read_to_string(&mut output).unwrap();
+        assert_eq!(output, "Hello, World!");
+
+        let mut output = String::new();
+        Body::from("Hello")
+            .into_reader()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "Hello");
+    }
+
+    #[test]
+    fn test_body_eq() {
+        assert_eq!(Body::from("Hello"), Body::from(b"Hello".to_vec()));
+        assert_ne!(Body::from("Hello"), Body::from("World"));
+        assert_ne!(
+            Body::from_reader(Cursor::new(b"Hello".to_vec())),
+            Body::from("Hello")
+        );
+    }
+
+    #[test]
+    fn test_body_into_bytes_streaming() {
+        let body = Body::from_chunks(vec![b"ab".to_vec(), b"cd".to_vec()]);
+        assert_eq!(body.into_bytes().unwrap(), b"abcd");
+
+        let body = Body::from_reader(Cursor::new(b"efgh".to_vec()));
+        assert_eq!(body.into_bytes().unwrap(), b"efgh");
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    error, fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Error type for a string which is not a valid HTTP date.
+#[derive(Debug, PartialEq)]
+pub struct InvalidDateError;
+
+impl fmt::Display for InvalidDateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid HTTP date")
+    }
+}
+
+impl error::Error for InvalidDateError {}
+
+/// A timestamp as used by HTTP headers such as `Last-Modified` and `Expires`. These only have a
+/// resolution of one second, so any fraction of a second is dropped.
+///
+/// It is written in the preferred IMF-fixdate format, `Sun, 06 Nov 1994 08:49:37 GMT`. When
+/// parsing, the obsolete RFC 850 and asctime formats are also accepted, as RFC 9110 requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HttpDate {
+    /// Seconds since the Unix epoch.
+    secs: u64,
+}
+
+impl HttpDate {
+    /// Return the current time.
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    /// Create an `HttpDate` from a number of seconds since the Unix epoch.
+    pub fn from_unix_secs(secs: u64) -> Self {
+        Self { secs }
+    }
+
+    /// Return the number of seconds since the Unix epoch.
+    pub fn unix_secs(&self) -> u64 {
+        self.secs
+    }
+
+    /// Format the timestamp as in the Common Log Format, such as `06/Nov/1994:08:49:37 +0000`.
+    pub(crate) fn to_common_log(self) -> String {
+        let parts = self.to_parts();
+        format!(
+            "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+            parts.day,
+            MONTH_NAMES[(parts.month - 1) as usize],
+            parts.year,
+            parts.hour,
+            parts.minute,
+            parts.second,
+        )
+    }
+
+    /// Format the timestamp as in RFC 3339, such as `1994-11-06T08:49:37Z`.
+    pub(crate) fn to_rfc3339(self) -> String {
+        let parts = self.to_parts();
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            parts.year, parts.month, parts.day, parts.hour, parts.minute, parts.second,
+        )
+    }
+
+    /// Split the timestamp into its calendar date and time of day.
+    fn to_parts(self) -> Parts {
+        let days = self.secs / SECONDS_PER_DAY;
+        let secs_of_day = self.secs % SECONDS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+        Parts {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day % 3600 / 60,
+            second: secs_of_day % 60,
+            weekday: (days % 7) as usize,
+        }
+    }
+}
+
+/// The calendar fields of an `HttpDate`. Months and days start at 1.
+struct Parts {
+    year: u64,
+    month: u64,
+    day: u64,
+    hour: u64,
+    minute: u64,
+    second: u64,
+    /// Days since a Thursday, which is what 1970-01-01 was.
+    weekday: usize,
+}
+
+/// Convert days since the Unix epoch to a `(year, month, day)` in the proleptic Gregorian
+/// calendar. This is Howard Hinnant's `civil_from_days` algorithm, restricted to dates on or
+/// after the epoch.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01 so that leap days fall at the end of each 400-year era.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a `(year, month, day)` to days since the Unix epoch. This is the inverse of
+/// [`civil_from_days`], and returns `None` for dates before the epoch.
+fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era).checked_sub(719_468)
+}
+
+/// Return the number of days in the given month.
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        _ => 31,
+    }
+}
+
+impl From<SystemTime> for HttpDate {
+    /// Convert a `SystemTime`, dropping any fraction of a second. Times before the Unix epoch are
+    /// clamped to it.
+    fn from(value: SystemTime) -> Self {
+        let secs = value
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Self { secs }
+    }
+}
+
+impl From<HttpDate> for SystemTime {
+    fn from(value: HttpDate) -> Self {
+        UNIX_EPOCH + Duration::from_secs(value.secs)
+    }
+}
+
+impl fmt::Display for HttpDate {
+    /// Write the date in the IMF-fixdate format.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = self.to_parts();
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            DAY_NAMES[parts.weekday],
+            parts.day,
+            MONTH_NAMES[(parts.month - 1) as usize],
+            parts.year,
+            parts.hour,
+            parts.minute,
+            parts.second,
+        )
+    }
+}
+
+impl FromStr for HttpDate {
+    type Err = InvalidDateError;
+
+    /// Parse a date in the IMF-fixdate, RFC 850 or asctime format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let (day, month, year, time) = match fields.as_slice() {
+            // IMF-fixdate: Sun, 06 Nov 1994 08:49:37 GMT
+            [_, day, month, year, time, "GMT"] => (*day, *month, parse_number(year)?, *time),
+            // RFC 850: Sunday, 06-Nov-94 08:49:37 GMT
+            [_, date, time, "GMT"] => {
+                let mut date_parts = date.split('-');
+                let day = date_parts.next().ok_or(InvalidDateError)?;
+                let month = date_parts.next().ok_or(InvalidDateError)?;
+                let year = parse_number(date_parts.next().ok_or(InvalidDateError)?)?;
+                // Two-digit years which appear to be more than 50 years in the future are
+                // interpreted as being in the past.
+                let year = if year < 70 { year + 2000 } else { year + 1900 };
+                (day, month, year, *time)
+            }
+            // asctime: Sun Nov  6 08:49:37 1994
+            [_, month, day, time, year] => (*day, *month, parse_number(year)?, *time),
+            _ => return Err(InvalidDateError),
+        };
+
+        let day = parse_number(day)?;
+        let month = MONTH_NAMES
+            .iter()
+            .position(|name| *name == month)
+            .ok_or(InvalidDateError)? as u64
+            + 1;
+        if day == 0 || day > days_in_month(year, month) {
+            return Err(InvalidDateError);
+        }
+
+        let mut time_parts = time.split(':').map(parse_number);
+        let (Some(hour), Some(minute), Some(second), None) = (
+            time_parts.next(),
+            time_parts.next(),
+            time_parts.next(),
+            time_parts.next(),
+        ) else {
+            return Err(InvalidDateError);
+        };
+        let (hour, minute, second) = (hour?, minute?, second?);
+        // A leap second of 60 is allowed by the grammar, which we fold into the next minute.
+        if hour > 23 || minute > 59 || second > 60 {
+            return Err(InvalidDateError);
+        }
+
+        let days = days_from_civil(year, month, day).ok_or(InvalidDateError)?;
+        Ok(Self {
+            secs: days * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second,
+        })
+    }
+}
+
+/// Parse a field made up only of ASCII digits.
+fn parse_number(s: &str) -> Result<u64, InvalidDateError> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(InvalidDateError);
+    }
+    s.parse().map_err(|_| InvalidDateError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_date_display() {
+        assert_eq!(
+            HttpDate::from_unix_secs(0).to_string(),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert_eq!(
+            HttpDate::from_unix_secs(784_111_777).to_string(),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            HttpDate::from_unix_secs(951_782_400).to_string(),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+        assert_eq!(
+            HttpDate::from_unix_secs(4_102_444_799).to_string(),
+            "Thu, 31 Dec 2099 23:59:59 GMT"
+        );
+    }
+
+    #[test]
+    fn test_http_date_log_formats() {
+        let date = HttpDate::from_unix_secs(784_111_777);
+        assert_eq!(date.to_common_log(), "06/Nov/1994:08:49:37 +0000");
+        assert_eq!(date.to_rfc3339(), "1994-11-06T08:49:37Z");
+    }
+
+    #[test]
+    fn test_http_date_parse_formats() {
+        let expected = HttpDate::from_unix_secs(784_111_777);
+        assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT".parse(), Ok(expected));
+        assert_eq!("Sunday, 06-Nov-94 08:49:37 GMT".parse(), Ok(expected));
+        assert_eq!("Sun Nov  6 08:49:37 1994".parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_http_date_round_trip() {
+        for secs in [0, 59, 86_399, 951_868_799, 1_700_000_000, 4_102_444_799] {
+            let date = HttpDate::from_unix_secs(secs);
+            assert_eq!(date.to_string().parse(), Ok(date));
+        }
+    }
+
+    #[test]
+    fn test_http_date_parse_invalid() {
+        for invalid in [
+            "",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 31 Nov 1994 08:49:37 GMT",
+            "Sun, 29 Feb 1900 08:49:37 GMT",
+            "Sun, 06 Foo 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 06 Nov 1969 08:49:37 GMT",
+            "Sun, +6 Nov 1994 08:49:37 GMT",
+        ] {
+            assert_eq!(
+                invalid.parse::<HttpDate>(),
+                Err(InvalidDateError),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_http_date_system_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_500);
+        let date = HttpDate::from(time);
+        assert_eq!(date.unix_secs(), 1);
+        assert_eq!(SystemTime::from(date), UNIX_EPOCH + Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::Arc,
+};
+
+/// A map from a type to a single value of that type, used to attach extra data to a request. The
+/// router stores its state here, and middleware can use it to pass values on to handlers.
+///
+/// Values are reference counted, so cloning an `Extensions` is cheap and shares its values.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Create an empty `Extensions`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert `value`, replacing any existing value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.map.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Return the value of type `T`, if there is one.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Return `true` if there is a value of type `T`.
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Copy every value from `other` into this map, replacing any of the same type.
+    pub fn extend(&mut self, other: &Extensions) {
+        for (type_id, value) in &other.map {
+            self.map.insert(*type_id, Arc::clone(value));
+        }
+    }
+
+    /// Return the number of values.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Return `true` if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    /// The values are not required to implement `Debug`, so only their number is shown.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+/// Two `Extensions` are equal if they share the very same values. The values are not required to
+/// implement `PartialEq`, so they are compared by identity.
+impl PartialEq for Extensions {
+    fn eq(&self, other: &Self) -> bool {
+        self.map.len() == other.map.len()
+            && self.map.iter().all(|(type_id, value)| {
+                other
+                    .map
+                    .get(type_id)
+                    .is_some_and(|other_value| Arc::ptr_eq(value, other_value))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Counter(usize);
+
+    #[test]
+    fn test_extensions_insert_and_get() {
+        let mut extensions = Extensions::new();
+        assert!(extensions.is_empty());
+        extensions.insert(Counter(1));
+        extensions.insert("name");
+        assert_eq!(extensions.get::<Counter>(), Some(&Counter(1)));
+        assert_eq!(extensions.get::<&str>(), Some(&"name"));
+        assert_eq!(extensions.get::<String>(), None);
+
+        extensions.insert(Counter(2));
+        assert_eq!(extensions.get::<Counter>(), Some(&Counter(2)));
+        assert_eq!(extensions.len(), 2);
+    }
+
+    #[test]
+    fn test_extensions_extend_shares_values() {
+        let mut state = Extensions::new();
+        state.insert(Counter(1));
+
+        let mut extensions = Extensions::new();
+        extensions.insert(Counter(0));
+        extensions.insert(5u8);
+        extensions.extend(&state);
+        assert_eq!(extensions.get::<Counter>(), Some(&Counter(1)));
+        assert!(extensions.contains::<u8>());
+
+        assert_eq!(state.clone(), state);
+        let mut other = Extensions::new();
+        other.insert(Counter(1));
+        assert_ne!(other, state);
+    }
+}
@@ -0,0 +1,341 @@
+use std::{error, fmt};
+
+/// Error type for a header name or value which cannot be sent in an HTTP message.
+#[derive(Debug, PartialEq)]
+pub struct InvalidHeaderError;
+
+impl fmt::Display for InvalidHeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid HTTP header")
+    }
+}
+
+impl error::Error for InvalidHeaderError {}
+
+/// Return `true` if `name` is a valid header name. Names are a "token" in RFC 9110, which is one
+/// or more visible ASCII characters excluding delimiters such as `:` and whitespace.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.bytes().all(|b| {
+            b.is_ascii_alphanumeric()
+                || matches!(
+                    b,
+                    b'!' | b'#'
+                        | b'$'
+                        | b'%'
+                        | b'&'
+                        | b'\''
+                        | b'*'
+                        | b'+'
+                        | b'-'
+                        | b'.'
+                        | b'^'
+                        | b'_'
+                        | b'`'
+                        | b'|'
+                        | b'~'
+                )
+        })
+}
+
+/// Return `true` if `value` is a valid header value. Control characters other than horizontal
+/// tab are rejected, most importantly CR and LF, which would otherwise let a value inject extra
+/// headers into a message.
+fn is_valid_value(value: &str) -> bool {
+    value.bytes().all(|b| b == b'\t' || !b.is_ascii_control())
+}
+
+/// A collection of HTTP headers.
+///
+/// Header names are case-insensitive, so `get("content-length")` will find a header which was
+/// sent as `Content-Length`. The original spelling is kept for when the headers are written back
+/// out. A name may appear more than once, and the order in which headers were added is preserved.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    /// Create an empty `HeaderMap`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the first value for `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return every value for `name`, in the order they were added.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return `true` if there is at least one value for `name`.
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Set `name` to `value`, replacing any values it already had. The header keeps the position
+    /// of its first existing value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or value is invalid. Use [`HeaderMap::try_insert`] for untrusted input.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.try_insert(name, value).expect("Invalid header.");
+    }
+
+    /// Add `value` to `name`, keeping any values it already had.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or value is invalid. Use [`HeaderMap::try_append`] for untrusted input.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.try_append(name, value).expect("Invalid header.");
+    }
+
+    /// Set `name` to `value`, replacing any values it already had, or return an error if the name
+    /// or value is invalid.
+    pub fn try_insert(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(), InvalidHeaderError> {
+        let (name, value) = validate(name.into(), value.into())?;
+        match self
+            .entries
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(&name))
+        {
+            Some(index) => {
+                // Drop any later values for this name. Everything up to `index` is kept, so the
+                // first value stays where it is.
+                let mut position = 0;
+                self.entries.retain(|(key, _)| {
+                    let keep = position <= index || !key.eq_ignore_ascii_case(&name);
+                    position += 1;
+                    keep
+                });
+                self.entries[index] = (name, value);
+            }
+            None => self.entries.push((name, value)),
+        }
+        Ok(())
+    }
+
+    /// Add `value` to `name`, keeping any values it already had, or return an error if the name or
+    /// value is invalid.
+    pub fn try_append(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(), InvalidHeaderError> {
+        let entry = validate(name.into(), value.into())?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Remove every value for `name`, returning the first one.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let mut removed = None;
+        self.entries.retain_mut(|(key, value)| {
+            if !key.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if removed.is_none() {
+                removed = Some(std::mem::take(value));
+            }
+            false
+        });
+        removed
+    }
+
+    /// Iterate over every name and value, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Return the number of values across all names.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return `true` if there are no headers.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Check a name and value before they are added to a `HeaderMap`. Surrounding whitespace is not
+/// part of a value, so it is trimmed off.
+fn validate(name: String, value: String) -> Result<(String, String), InvalidHeaderError> {
+    let value = value.trim_matches([' ', '\t']);
+    if !is_valid_name(&name) || !is_valid_value(value) {
+        return Err(InvalidHeaderError);
+    }
+
+    Ok((name, value.to_string()))
+}
+
+/// Two `HeaderMap`s are equal if they hold the same values in the same order. Names are compared
+/// case-insensitively.
+impl PartialEq for HeaderMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other.iter())
+                .all(|((a_name, a_value), (b_name, b_value))| {
+                    a_name.eq_ignore_ascii_case(b_name) && a_value == b_value
+                })
+    }
+}
+
+impl<N, V> FromIterator<(N, V)> for HeaderMap
+where
+    N: Into<String>,
+    V: Into<String>,
+{
+    /// Collect name and value pairs into a `HeaderMap`, appending each one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any name or value is invalid.
+    fn from_iter<T: IntoIterator<Item = (N, V)>>(iter: T) -> Self {
+        let mut headers = Self::new();
+        for (name, value) in iter {
+            headers.append(name, value);
+        }
+        headers
+    }
+}
+
+impl<N, V, const COUNT: usize> From<[(N, V); COUNT]> for HeaderMap
+where
+    N: Into<String>,
+    V: Into<String>,
+{
+    /// Create a `HeaderMap` from an array of name and value pairs, such as
+    /// `HeaderMap::from([("Content-Type", "text/plain")])`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any name or value is invalid.
+    fn from(value: [(N, V); COUNT]) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_map_get_is_case_insensitive() {
+        let headers = HeaderMap::from([("Content-Length", "5")]);
+        assert_eq!(headers.get("Content-Length"), Some("5"));
+        assert_eq!(headers.get("content-length"), Some("5"));
+        assert_eq!(headers.get("CONTENT-LENGTH"), Some("5"));
+        assert_eq!(headers.get("Content-Type"), None);
+        assert!(headers.contains_key("content-LENGTH"));
+    }
+
+    #[test]
+    fn test_header_map_append_and_get_all() {
+        let mut headers = HeaderMap::new();
+        headers.append("Accept", "text/html");
+        headers.append("accept", "application/json");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.get("Accept"), Some("text/html"));
+        assert_eq!(
+            headers.get_all("ACCEPT").collect::<Vec<_>>(),
+            vec!["text/html", "application/json"]
+        );
+    }
+
+    #[test]
+    fn test_header_map_insert_replaces() {
+        let mut headers = HeaderMap::new();
+        headers.append("Host", "a");
+        headers.append("Accept", "text/html");
+        headers.append("accept", "application/json");
+        headers.append("Connection", "close");
+
+        headers.insert("ACCEPT", "*/*");
+        assert_eq!(
+            headers.iter().collect::<Vec<_>>(),
+            vec![("Host", "a"), ("ACCEPT", "*/*"), ("Connection", "close")]
+        );
+
+        headers.insert("Vary", "Origin");
+        assert_eq!(headers.get("vary"), Some("Origin"));
+        assert_eq!(headers.len(), 4);
+    }
+
+    #[test]
+    fn test_header_map_remove() {
+        let mut headers = HeaderMap::from([("Accept", "a"), ("Host", "h"), ("accept", "b")]);
+        assert_eq!(headers.remove("ACCEPT"), Some("a".to_string()));
+        assert_eq!(headers.iter().collect::<Vec<_>>(), vec![("Host", "h")]);
+        assert_eq!(headers.remove("Accept"), None);
+    }
+
+    #[test]
+    fn test_header_map_trims_values() {
+        let mut headers = HeaderMap::new();
+        headers.append("Host", " \tlocalhost  ");
+        assert_eq!(headers.get("host"), Some("localhost"));
+    }
+
+    #[test]
+    fn test_header_map_rejects_invalid_names() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(headers.try_append("", "x"), Err(InvalidHeaderError));
+        assert_eq!(headers.try_append("Bad Name", "x"), Err(InvalidHeaderError));
+        assert_eq!(headers.try_append("Bad:Name", "x"), Err(InvalidHeaderError));
+        assert_eq!(headers.try_append("Bäd", "x"), Err(InvalidHeaderError));
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn test_header_map_rejects_invalid_values() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            headers.try_insert("X-Evil", "a\r\nSet-Cookie: b"),
+            Err(InvalidHeaderError)
+        );
+        assert_eq!(
+            headers.try_insert("X-Evil", "a\0b"),
+            Err(InvalidHeaderError)
+        );
+        assert!(headers.try_insert("X-Fine", "a\tb ü").is_ok());
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid header.")]
+    fn test_header_map_insert_invalid_panics() {
+        HeaderMap::new().insert("X-Evil", "\r\n");
+    }
+
+    #[test]
+    fn test_header_map_eq() {
+        assert_eq!(
+            HeaderMap::from([("Host", "a")]),
+            HeaderMap::from([("host", "a")])
+        );
+        assert_ne!(
+            HeaderMap::from([("Host", "a")]),
+            HeaderMap::from([("Host", "A")])
+        );
+    }
+}
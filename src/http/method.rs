@@ -12,6 +12,21 @@ pub enum Method {
     Patch,
 }
 
+impl Method {
+    /// Return the method as it is written in a request, such as `GET`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Options => "OPTIONS",
+            Method::Head => "HEAD",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+        }
+    }
+}
+
 /// Error type for invalid HTTP methods.
 #[derive(Debug, PartialEq)]
 pub struct InvalidMethodError;
@@ -100,4 +115,11 @@ mod tests {
         let method = Method::try_from("UNDELETE");
         assert_eq!(method, Err(InvalidMethodError));
     }
+
+    #[test]
+    fn test_as_str_round_trip() {
+        for name in ["GET", "POST", "OPTIONS", "HEAD", "PUT", "DELETE", "PATCH"] {
+            assert_eq!(Method::try_from(name).unwrap().as_str(), name);
+        }
+    }
 }
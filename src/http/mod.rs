@@ -1,9 +1,19 @@
 //! Utilities for manipulating common objects. In a production system, the `http` crate should be
 //! used instead.
+mod body;
+mod date;
+mod extensions;
+mod header_map;
 mod method;
 mod request;
 mod response;
 
+pub use body::Body;
+pub use date::{HttpDate, InvalidDateError};
+pub use extensions::Extensions;
+pub(crate) use header_map::is_valid_name;
+pub use header_map::{HeaderMap, InvalidHeaderError};
 pub use method::Method;
+pub(crate) use request::PROTOCOL;
 pub use request::{Parts, PathParams, Request};
 pub use response::Response;
@@ -1,12 +1,9 @@
 use std::{error, fmt};
 
-use crate::http::Method;
+use crate::http::{Body, Extensions, HeaderMap, Method};
 
 /// At this time, we only support HTTP/1. `hyper` supports HTTP/2.
-pub(super) const PROTOCOL: &str = "HTTP/1.1";
-
-/// Type alias representing the key-value pairs from a `Request` headers.
-pub type Headers = Vec<(String, String)>;
+pub(crate) const PROTOCOL: &str = "HTTP/1.1";
 
 /// Type alias representing the path parameters which are parsed from a request. This is not known
 /// until it is matched against a `Router` pattern; the initial `Request` parsing is unaware of
@@ -19,8 +16,24 @@ pub type PathParams = Vec<String>;
 pub struct Parts {
     pub method: Method,
     pub path: String,
-    pub headers: Headers,
+    pub headers: HeaderMap,
     pub path_params: PathParams,
+    /// Extra data attached to the request, such as the router state.
+    pub extensions: Extensions,
+}
+
+impl Parts {
+    /// Return the path without its query string.
+    pub fn path_without_query(&self) -> &str {
+        self.path
+            .split_once('?')
+            .map_or(&self.path, |(path, _)| path)
+    }
+
+    /// Return the query string, which is everything after the first `?` in the path.
+    pub fn query(&self) -> Option<&str> {
+        self.path.split_once('?').map(|(_, query)| query)
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -39,25 +52,29 @@ impl error::Error for InvalidRequestError {}
 #[derive(Debug, PartialEq)]
 pub struct Request {
     parts: Parts,
-    pub body: Option<String>,
+    body: Body,
 }
 
 impl Request {
     /// Create a new `Request` instance with the given method and path.
     pub fn new(method: Method, path: &str) -> Self {
-        Self::with_headers(method, path, Headers::default())
+        Self::with_headers(method, path, HeaderMap::new())
     }
 
     /// Create a new `Request` instance with the given method, path, and headers.
-    pub fn with_headers(method: Method, path: &str, headers: Headers) -> Self {
+    pub fn with_headers(method: Method, path: &str, headers: HeaderMap) -> Self {
         let parts = Parts {
             method,
             path: path.to_string(),
             headers,
             path_params: PathParams::default(),
+            extensions: Extensions::new(),
         };
 
-        Self { parts, body: None }
+        Self {
+            parts,
+            body: Body::empty(),
+        }
     }
 
     /// Set the path parameters for the request.
@@ -65,14 +82,25 @@ impl Request {
         self.parts.path_params = path_params;
     }
 
-    /// Set the path parameters for the request.
-    pub fn set_headers(&mut self, headers: Headers) {
+    /// Set the headers for the request.
+    pub fn set_headers(&mut self, headers: HeaderMap) {
         self.parts.headers = headers;
     }
 
-    /// Set the body for the request.
-    pub fn set_body(&mut self, body: String) {
-        self.body = Some(body);
+    /// Set the body for the request. This can be anything which converts into a [`Body`],
+    /// including a stream which has not been read from the connection yet.
+    pub fn set_body(&mut self, body: impl Into<Body>) {
+        self.body = body.into();
+    }
+
+    /// Remove the body from the request, leaving an empty one in its place.
+    pub fn take_body(&mut self) -> Body {
+        std::mem::take(&mut self.body)
+    }
+
+    /// Consume the request and return its body.
+    pub fn into_body(self) -> Body {
+        self.body
     }
 
     /// Retrieve the path parameters, returning an empty vector if none are set.
@@ -90,15 +118,35 @@ impl Request {
         &self.parts.path
     }
 
-    /// `Headers` accessor.
-    pub fn headers(&self) -> &Headers {
+    /// `HeaderMap` accessor.
+    pub fn headers(&self) -> &HeaderMap {
         &self.parts.headers
     }
 
+    /// Mutable `HeaderMap` accessor.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.parts.headers
+    }
+
     /// `PathParams` accessor.
     pub fn path_params(&self) -> &PathParams {
         &self.parts.path_params
     }
+
+    /// `Extensions` accessor.
+    pub fn extensions(&self) -> &Extensions {
+        &self.parts.extensions
+    }
+
+    /// Mutable `Extensions` accessor.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.parts.extensions
+    }
+
+    /// `Body` accessor.
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
 }
 
 impl TryFrom<&str> for Request {
@@ -117,6 +165,11 @@ impl TryFrom<&str> for Request {
             .try_into()
             .map_err(|_| InvalidRequestError)?;
         let path = request_line_parts.next().ok_or(InvalidRequestError)?;
+        // The target may only hold visible ASCII, anything else must be percent-encoded. Refusing
+        // control bytes here means the path and query are always safe to copy into a header.
+        if !path.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(InvalidRequestError);
+        }
         let version = request_line_parts.next().ok_or(InvalidRequestError)?;
         // We could move this into a Version enum
         if version == "HTTP/2.0" {
@@ -130,17 +183,20 @@ impl TryFrom<&str> for Request {
 
         let mut request = Request::new(method, path);
 
-        let mut headers = vec![];
+        let mut headers = HeaderMap::new();
         for line in &mut lines {
             // An empty line indicates the end of the headers
             if line.is_empty() {
                 break;
             }
 
-            let mut header_parts = line.splitn(2, ": ");
-            let name = header_parts.next().ok_or(InvalidRequestError)?.to_string();
-            let value = header_parts.next().ok_or(InvalidRequestError)?.to_string();
-            headers.push((name, value));
+            // Whitespace around the value is optional, so we only split on the colon and let the
+            // `HeaderMap` trim the value. Whitespace before the colon is not allowed and is
+            // rejected along with any other invalid name.
+            let (name, value) = line.split_once(':').ok_or(InvalidRequestError)?;
+            headers
+                .try_append(name, value)
+                .map_err(|_| InvalidRequestError)?;
         }
 
         request.set_headers(headers);
@@ -165,22 +221,14 @@ mod tests {
         let request = Request::try_from(stream).unwrap();
         assert_eq!(
             request,
-            Request::with_headers(
-                Method::Get,
-                "/",
-                vec![("Host".to_string(), "localhost".to_string())]
-            )
+            Request::with_headers(Method::Get, "/", HeaderMap::from([("Host", "localhost")]))
         );
 
         let stream = "POST / HTTP/1.1\r\nHost: localhost\r\n\r\n";
         let request = Request::try_from(stream).unwrap();
         assert_eq!(
             request,
-            Request::with_headers(
-                Method::Post,
-                "/",
-                vec![("Host".to_string(), "localhost".to_string())]
-            )
+            Request::with_headers(Method::Post, "/", HeaderMap::from([("Host", "localhost")]))
         );
     }
 
@@ -200,7 +248,7 @@ mod tests {
             Request::with_headers(
                 Method::Get,
                 "/path",
-                vec![("Host".to_string(), "localhost".to_string())]
+                HeaderMap::from([("Host", "localhost")])
             )
         );
     }
@@ -219,22 +267,48 @@ mod tests {
         let mut expected = Request::with_headers(
             Method::Post,
             "/post/5",
-            vec![
-                ("Host".to_string(), "127.0.0.1:7878".to_string()),
-                ("User-Agent".to_string(), "curl/8.4.0".to_string()),
-                ("Accept".to_string(), "*/*".to_string()),
-                ("Content-Length".to_string(), "10".to_string()),
-                (
-                    "Content-Type".to_string(),
-                    "application/x-www-form-urlencoded".to_string(),
-                ),
-            ],
+            HeaderMap::from([
+                ("Host", "127.0.0.1:7878"),
+                ("User-Agent", "curl/8.4.0"),
+                ("Accept", "*/*"),
+                ("Content-Length", "10"),
+                ("Content-Type", "application/x-www-form-urlencoded"),
+            ]),
         );
         expected.set_body("Hello Rust".to_string());
 
         assert_eq!(request, expected);
     }
 
+    #[test]
+    fn test_parse_request_header_whitespace() {
+        let stream = "GET / HTTP/1.1\r\nHost:x\r\ncontent-length:5\r\nAccept: \t*/* \r\n\r\n";
+        let request = Request::try_from(stream).unwrap();
+        assert_eq!(request.headers().get("host"), Some("x"));
+        assert_eq!(request.headers().get("Content-Length"), Some("5"));
+        assert_eq!(request.headers().get("Accept"), Some("*/*"));
+    }
+
+    #[test]
+    fn test_parse_request_invalid_header() {
+        let stream = "GET / HTTP/1.1\r\nHost : x\r\n\r\n";
+        assert!(Request::try_from(stream).is_err());
+
+        let stream = "GET / HTTP/1.1\r\nNoColon\r\n\r\n";
+        assert!(Request::try_from(stream).is_err());
+    }
+
+    #[test]
+    fn test_parts_query() {
+        let request = Request::new(Method::Get, "/search?q=rust&page=2");
+        assert_eq!(request.into_parts().path_without_query(), "/search");
+        assert_eq!(request.into_parts().query(), Some("q=rust&page=2"));
+
+        let request = Request::new(Method::Get, "/search");
+        assert_eq!(request.into_parts().path_without_query(), "/search");
+        assert_eq!(request.into_parts().query(), None);
+    }
+
     #[test]
     fn test_parse_request_empty() {
         let stream = "";
@@ -256,6 +330,17 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_request_invalid_target() {
+        for stream in [
+            "GET /a/sub?\x01 HTTP/1.1\r\n\r\n",
+            "GET /\x7f HTTP/1.1\r\n\r\n",
+            "GET /caf\u{e9} HTTP/1.1\r\n\r\n",
+        ] {
+            assert_eq!(Request::try_from(stream), Err(InvalidRequestError));
+        }
+    }
+
     #[test]
     fn test_parse_request_invalid_format() {
         let stream = "INVALID REQUEST\r\n";
@@ -1,4 +1,6 @@
-use super::request::{Headers, PROTOCOL};
+use std::io::{self, Write};
+
+use super::{request::PROTOCOL, Body, HeaderMap};
 
 /// Alias to represent the 3-digit HTTP status code. This will fall between 100 and 599, inclusive.
 type StatusCode = u16;
@@ -7,8 +9,24 @@ type StatusCode = u16;
 fn status_code_to_string(code: StatusCode) -> &'static str {
     match code {
         200 => "OK",
+        201 => "CREATED",
+        204 => "NO CONTENT",
+        206 => "PARTIAL CONTENT",
+        301 => "MOVED PERMANENTLY",
+        304 => "NOT MODIFIED",
         400 => "BAD REQUEST",
+        401 => "UNAUTHORIZED",
+        403 => "FORBIDDEN",
         404 => "NOT FOUND",
+        412 => "PRECONDITION FAILED",
+        413 => "PAYLOAD TOO LARGE",
+        415 => "UNSUPPORTED MEDIA TYPE",
+        416 => "RANGE NOT SATISFIABLE",
+        429 => "TOO MANY REQUESTS",
+        431 => "REQUEST HEADER FIELDS TOO LARGE",
+        500 => "INTERNAL SERVER ERROR",
+        503 => "SERVICE UNAVAILABLE",
+        504 => "GATEWAY TIMEOUT",
         _ => unimplemented!("Unsupported status code: {}", code),
     }
 }
@@ -16,23 +34,35 @@ fn status_code_to_string(code: StatusCode) -> &'static str {
 /// Represents an HTTP response.
 pub struct Response {
     status_code: StatusCode,
-    headers: Headers,
-    body: String,
+    headers: HeaderMap,
+    body: Body,
 }
 
 impl Response {
-    /// Create a new `Response` instance with the given status code, headers, and body.
-    pub fn new(status_code: StatusCode, headers: Headers, body: String) -> Self {
+    /// Create a new `Response` instance with the given status code, headers, and body. The body
+    /// can be anything which converts into a [`Body`], such as a `String` or a streaming reader.
+    pub fn new(status_code: StatusCode, headers: HeaderMap, body: impl Into<Body>) -> Self {
         Self {
             status_code,
             headers,
-            body,
+            body: body.into(),
         }
     }
 
-    /// Return the response body as a string.
+    /// Return the response body as a string. A streaming body has not been produced yet, so this
+    /// returns an empty string for those.
     pub fn text(&self) -> String {
-        self.body.clone()
+        String::from_utf8_lossy(self.body.as_bytes().unwrap_or_default()).into_owned()
+    }
+
+    /// Return the body.
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    /// Consume the `Response` and return its body.
+    pub fn into_body(self) -> Body {
+        self.body
     }
 
     /// Return the status code.
@@ -40,19 +70,41 @@ impl Response {
         self.status_code
     }
 
+    /// Set the status code.
+    pub fn set_status_code(&mut self, status_code: StatusCode) {
+        self.status_code = status_code;
+    }
+
     /// Return the headers.
-    pub fn headers(&self) -> &Headers {
+    pub fn headers(&self) -> &HeaderMap {
         &self.headers
     }
 
-    /// Return the entire HTTP response as a vector of bytes.
+    /// Return the headers for modification.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Return the entire HTTP response as a vector of bytes. A streaming body is not included,
+    /// use [`Response::write_to`] to send those.
     pub fn as_bytes(&self) -> Vec<u8> {
         self.stream().into_bytes()
     }
 
-    /// Construct the HTTP response as a string, including the status line, headers, and body.
-    fn stream(&self) -> String {
-        let headers = self
+    /// Write the entire HTTP response to `writer`, returning the number of bytes written. A
+    /// streaming body is copied to `writer` as it is produced rather than collected first.
+    pub fn write_to<W: Write>(self, writer: &mut W) -> io::Result<usize> {
+        let head = self.head();
+        self.body.write_after(writer, head.as_bytes())
+    }
+
+    /// Construct the status line and headers, including the blank line which separates them from
+    /// the body.
+    ///
+    /// A streaming body needs framing so the client knows where it ends: if its length is known we
+    /// send a `Content-Length`, otherwise we use `Transfer-Encoding: chunked`.
+    fn head(&self) -> String {
+        let mut headers = self
             .headers
             .iter()
             .fold(String::new(), |mut acc, (key, value)| {
@@ -60,41 +112,56 @@ impl Response {
                 acc
             });
 
+        let has_framing = self.headers.contains_key("Content-Length")
+            || self.headers.contains_key("Transfer-Encoding");
+        if self.body.is_streaming() && !has_framing {
+            match self.body.len() {
+                Some(len) => headers.push_str(&format!("Content-Length: {}\r\n", len)),
+                None => headers.push_str("Transfer-Encoding: chunked\r\n"),
+            }
+        }
+
         format!(
-            "{} {} {}\r\n{}\r\n{}",
+            "{} {} {}\r\n{}\r\n",
             PROTOCOL,
             self.status_code,
             status_code_to_string(self.status_code),
             headers,
-            self.body,
         )
     }
+
+    /// Construct the HTTP response as a string, including the status line, headers, and body.
+    fn stream(&self) -> String {
+        format!("{}{}", self.head(), self.text())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Cursor;
+
     use super::*;
 
     #[test]
     fn test_response_new() {
-        let headers = vec![("Content-Type".to_string(), "text/plain".to_string())];
+        let headers = HeaderMap::from([("Content-Type", "text/plain")]);
         let response = Response::new(200, headers.clone(), "Hello, World!".to_string());
         assert_eq!(response.status_code, 200);
         assert_eq!(response.headers, headers);
-        assert_eq!(response.body, "Hello, World!".to_string());
+        assert_eq!(response.body.as_bytes(), Some(&b"Hello, World!"[..]));
     }
 
     #[test]
     fn test_response_text() {
-        let response = Response::new(200, vec![], "Hello, World!".to_string());
+        let response = Response::new(200, HeaderMap::new(), "Hello, World!".to_string());
         assert_eq!(response.status_code(), 200);
         assert_eq!(response.text(), "Hello, World!".to_string());
-        assert_eq!(response.headers(), &vec![]);
+        assert!(response.headers().is_empty());
     }
 
     #[test]
     fn test_response_as_bytes() {
-        let response = Response::new(200, vec![], "Hello, World!".to_string());
+        let response = Response::new(200, HeaderMap::new(), "Hello, World!".to_string());
         let expected = "HTTP/1.1 200 OK\r\n\r\nHello, World!".as_bytes().to_vec();
         assert_eq!(response.as_bytes(), expected);
     }
@@ -103,7 +170,7 @@ mod tests {
     fn test_response_stream() {
         let response = Response::new(
             200,
-            vec![("Content-Type".to_string(), "text/plain".to_string())],
+            HeaderMap::from([("Content-Type", "text/plain")]),
             "Hello, World!".to_string(),
         );
         let expected =
@@ -111,6 +178,35 @@ mod tests {
         assert_eq!(response.stream(), expected);
     }
 
+    #[test]
+    fn test_response_write_to_sized_reader() {
+        let body = Body::sized_reader(Cursor::new(b"Hello, World!".to_vec()), 13);
+        let response = Response::new(200, HeaderMap::new(), body);
+        assert_eq!(response.text(), "");
+
+        let mut output = vec![];
+        let num_bytes_written = response.write_to(&mut output).unwrap();
+        assert_eq!(
+            output,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello, World!"
+        );
+        assert_eq!(num_bytes_written, output.len());
+    }
+
+    #[test]
+    fn test_response_write_to_chunked() {
+        let body = Body::from_chunks(vec![b"Hello".to_vec(), b", World!".to_vec()]);
+        let response = Response::new(200, HeaderMap::new(), body);
+
+        let mut output = vec![];
+        response.write_to(&mut output).unwrap();
+        assert_eq!(
+            output,
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5\r\nHello\r\n8\r\n, World!\r\n0\r\n\r\n"
+        );
+    }
+
     #[test]
     fn test_status_code_to_string_200() {
         assert_eq!(status_code_to_string(200), "OK");
@@ -121,9 +217,15 @@ mod tests {
         assert_eq!(status_code_to_string(404), "NOT FOUND");
     }
 
+    #[test]
+    fn test_status_code_to_string_201_500() {
+        assert_eq!(status_code_to_string(201), "CREATED");
+        assert_eq!(status_code_to_string(500), "INTERNAL SERVER ERROR");
+    }
+
     #[test]
     #[should_panic]
     fn test_status_code_to_string_unimplemented() {
-        status_code_to_string(500);
+        status_code_to_string(418);
     }
 }
@@ -1,10 +1,28 @@
-use crate::http::Response;
+use crate::{
+    extract::TypedHeader,
+    headers::Header,
+    http::{Body, HeaderMap, Response},
+};
 
 /// Trait to convert a value into a `Response`.
 pub trait IntoResponse {
     fn into_response(self) -> Response;
 }
 
+/// Trait for values which modify part of a `Response`, such as its headers, without providing the
+/// body. These can be returned in a tuple before the body, for example `(headers, "Hello")` or
+/// `(201, headers, "Hello")`.
+pub trait IntoResponseParts {
+    fn into_response_parts(self, response: &mut Response);
+}
+
+impl IntoResponse for Response {
+    /// A `Response` is already a `Response`.
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
 impl IntoResponse for &str {
     /// Convert a `&str` into a `Response`, by way of a `String`.
     fn into_response(self) -> Response {
@@ -14,24 +32,86 @@ impl IntoResponse for &str {
 
 impl IntoResponse for String {
     /// Convert a `String` into a `Response`, using its value as the body.
+    fn into_response(self) -> Response {
+        Response::new(200, HeaderMap::from([("Content-Type", "text/plain")]), self)
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    /// Convert raw bytes into a `Response`, using them as the body.
+    fn into_response(self) -> Response {
+        Body::from(self).into_response()
+    }
+}
+
+impl IntoResponse for Body {
+    /// Convert a [`Body`] into a `Response`. Since we cannot know what the bytes represent, they
+    /// are sent as `application/octet-stream`.
     fn into_response(self) -> Response {
         Response::new(
             200,
-            vec![("Content-Type".to_string(), "text/plain".to_string())],
+            HeaderMap::from([("Content-Type", "application/octet-stream")]),
             self,
         )
     }
 }
 
-impl IntoResponse for (u16, &str) {
-    /// Convert a `(u16, String)` into a `Response`, using its value as the HTTP response code and
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    /// Convert whichever of the success or error values is present into a `Response`. This lets a
+    /// handler use `?` with any error type which implements `IntoResponse`.
+    fn into_response(self) -> Response {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+impl<R: IntoResponse> IntoResponse for (u16, R) {
+    /// Convert a `(u16, R)` into a `Response`, using its values as the HTTP response code and
     /// body.
     fn into_response(self) -> Response {
-        Response::new(
-            self.0,
-            vec![("Content-Type".to_string(), "text/plain".to_string())],
-            self.1.to_string(),
-        )
+        let mut response = self.1.into_response();
+        response.set_status_code(self.0);
+        response
+    }
+}
+
+impl<P: IntoResponseParts, R: IntoResponse> IntoResponse for (P, R) {
+    /// Convert the body `R` into a `Response` and then apply `P` to it.
+    fn into_response(self) -> Response {
+        let mut response = self.1.into_response();
+        self.0.into_response_parts(&mut response);
+        response
+    }
+}
+
+impl<P: IntoResponseParts, R: IntoResponse> IntoResponse for (u16, P, R) {
+    /// Convert the body `R` into a `Response`, apply `P` to it, and set the status code.
+    fn into_response(self) -> Response {
+        let mut response = (self.1, self.2).into_response();
+        response.set_status_code(self.0);
+        response
+    }
+}
+
+impl IntoResponseParts for HeaderMap {
+    /// Set each of these headers on the response, replacing any it already had with the same name.
+    fn into_response_parts(self, response: &mut Response) {
+        let headers = response.headers_mut();
+        for (name, _) in self.iter() {
+            headers.remove(name);
+        }
+        for (name, value) in self.iter() {
+            headers.append(name, value);
+        }
+    }
+}
+
+impl<H: Header> IntoResponseParts for TypedHeader<H> {
+    /// Set the typed header on the response.
+    fn into_response_parts(self, response: &mut Response) {
+        response.headers_mut().typed_insert(self.0);
     }
 }
 
@@ -47,8 +127,57 @@ mod tests {
         assert_eq!(response.text(), body);
         assert_eq!(
             response.headers(),
-            &vec![("Content-Type".to_string(), "text/plain".to_string())]
+            &HeaderMap::from([("Content-Type", "text/plain")])
+        );
+    }
+
+    #[test]
+    fn test_body_into_response() {
+        let body = Body::from_chunks(vec![b"Hello".to_vec()]);
+        let response = body.into_response();
+        assert_eq!(response.status_code(), 200);
+        assert!(response.body().is_streaming());
+        assert_eq!(
+            response.headers(),
+            &HeaderMap::from([("Content-Type", "application/octet-stream")])
+        );
+    }
+
+    #[test]
+    fn test_headers_tuple_into_response() {
+        use crate::headers::{ContentType, Location};
+
+        let response = (TypedHeader(ContentType::html()), "<p>Hi</p>").into_response();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(
+            response.headers(),
+            &HeaderMap::from([("Content-Type", "text/html; charset=utf-8")])
+        );
+
+        let headers = HeaderMap::from([("Location", "/a"), ("X-Extra", "1"), ("X-Extra", "2")]);
+        let response = (404, headers, "Gone").into_response();
+        assert_eq!(response.status_code(), 404);
+        assert_eq!(response.headers().len(), 4);
+        assert_eq!(
+            response.headers().typed_get(),
+            Some(Location("/a".to_string()))
         );
+        assert_eq!(response.text(), "Gone");
+
+        let headers = HeaderMap::from([("Location", "/items/1")]);
+        let response = (201, headers, "Hello").into_response();
+        assert!(response.as_bytes().starts_with(b"HTTP/1.1 201 CREATED\r\n"));
+    }
+
+    #[test]
+    fn test_result_into_response() {
+        let ok: Result<&str, (u16, &str)> = Ok("Hello");
+        assert_eq!(ok.into_response().status_code(), 200);
+
+        let err: Result<&str, (u16, &str)> = Err((404, "Missing"));
+        let response = err.into_response();
+        assert_eq!(response.status_code(), 404);
+        assert_eq!(response.text(), "Missing");
     }
 
     #[test]
@@ -60,7 +189,7 @@ mod tests {
         assert_eq!(response.text(), body);
         assert_eq!(
             response.headers(),
-            &vec![("Content-Type".to_string(), "text/plain".to_string())]
+            &HeaderMap::from([("Content-Type", "text/plain")])
         );
     }
 }
@@ -7,13 +7,20 @@ mod core;
 mod handler;
 mod into_response;
 mod path_router;
+mod ranged;
 mod router;
 mod server;
 
 pub use router::Router;
 pub use server::serve;
+pub mod cookie;
 pub mod extract;
+pub mod headers;
 pub mod http;
+pub mod log;
+pub mod middleware;
+pub mod services;
+pub mod session;
 
 pub mod routing {
     //! Routing from requests to handlers
@@ -26,5 +33,6 @@ pub mod response {
     //! Utilities for generating responses
     use super::*;
 
-    pub use into_response::IntoResponse;
+    pub use into_response::{IntoResponse, IntoResponseParts};
+    pub use ranged::Ranged;
 }
@@ -0,0 +1,174 @@
+//! Diagnostic logging for the server and middleware.
+//!
+//! Nothing is logged until a [`Logger`] is installed with [`set_logger`], so the server is silent
+//! by default. Messages never include request bodies.
+//!
+//! ```
+//! use cairo::log::{set_logger, Level, StderrLogger};
+//!
+//! set_logger(StderrLogger::new(Level::Info)).expect("No logger has been set yet");
+//! ```
+use std::{
+    error, fmt,
+    io::{self, Write},
+    sync::OnceLock,
+};
+
+use crate::http::HttpDate;
+
+/// How important a message is, from the most to the least.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Something failed, such as a connection which could not be accepted.
+    Error,
+    /// Something unexpected happened which the server recovered from.
+    Warn,
+    /// Normal operation worth recording, such as each request in an access log.
+    Info,
+    /// Details useful when debugging, such as connections being closed.
+    Debug,
+    /// Everything, including how many bytes each read and write moved.
+    Trace,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        f.pad(name)
+    }
+}
+
+/// A destination for log messages.
+pub trait Logger: Send + Sync + 'static {
+    /// Return `true` if messages at `level` should be logged. Messages are only formatted when
+    /// this returns `true`.
+    fn enabled(&self, level: Level) -> bool;
+
+    /// Record a message.
+    fn log(&self, level: Level, message: fmt::Arguments<'_>);
+}
+
+/// A [`Logger`] which writes each message at or above a minimum level to standard error, as a
+/// line with a timestamp and the level.
+#[derive(Debug, Clone)]
+pub struct StderrLogger {
+    level: Level,
+}
+
+impl StderrLogger {
+    /// Create a `StderrLogger` which logs messages at `level` and above.
+    pub fn new(level: Level) -> Self {
+        Self { level }
+    }
+}
+
+impl Logger for StderrLogger {
+    fn enabled(&self, level: Level) -> bool {
+        level <= self.level
+    }
+
+    fn log(&self, level: Level, message: fmt::Arguments<'_>) {
+        let line = format!(
+            "{} {:<5} {}\n",
+            HttpDate::now().to_rfc3339(),
+            level,
+            message
+        );
+        // Locking keeps lines from different threads whole. Logging must never bring the server
+        // down, so a failed write is ignored.
+        let _ = io::stderr().lock().write_all(line.as_bytes());
+    }
+}
+
+/// Error type for [`set_logger`] being called when a logger is already installed.
+#[derive(Debug, PartialEq)]
+pub struct SetLoggerError;
+
+impl fmt::Display for SetLoggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "A logger has already been set")
+    }
+}
+
+impl error::Error for SetLoggerError {}
+
+static LOGGER: OnceLock<Box<dyn Logger>> = OnceLock::new();
+
+/// Install the logger used by the server and middleware. It can only be set once, and should be
+/// set before calling [`serve`](crate::serve).
+pub fn set_logger(logger: impl Logger) -> Result<(), SetLoggerError> {
+    LOGGER.set(Box::new(logger)).map_err(|_| SetLoggerError)
+}
+
+/// Return the installed logger, if there is one.
+pub(crate) fn logger() -> Option<&'static dyn Logger> {
+    LOGGER.get().map(Box::as_ref)
+}
+
+/// Send a message to `logger`, if there is one and it accepts `level`.
+pub(crate) fn log_to(logger: Option<&dyn Logger>, level: Level, message: fmt::Arguments<'_>) {
+    if let Some(logger) = logger.filter(|logger| logger.enabled(level)) {
+        logger.log(level, message);
+    }
+}
+
+/// Log a message with the installed logger, formatting it only if it will be logged.
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        $crate::log::log_to($crate::log::logger(), $level, format_args!($($arg)+))
+    };
+}
+
+pub(crate) use log;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// A logger which keeps its messages, for tests to check.
+    #[derive(Default)]
+    pub(crate) struct MemoryLogger {
+        pub(crate) lines: Mutex<Vec<(Level, String)>>,
+    }
+
+    impl Logger for MemoryLogger {
+        fn enabled(&self, level: Level) -> bool {
+            level <= Level::Info
+        }
+
+        fn log(&self, level: Level, message: fmt::Arguments<'_>) {
+            self.lines
+                .lock()
+                .unwrap()
+                .push((level, message.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_log_filters_by_level() {
+        let logger = MemoryLogger::default();
+        log_to(Some(&logger), Level::Error, format_args!("failed: {}", 42));
+        log_to(Some(&logger), Level::Debug, format_args!("hidden"));
+        log_to(None, Level::Error, format_args!("nowhere"));
+        assert_eq!(
+            *logger.lines.lock().unwrap(),
+            vec![(Level::Error, "failed: 42".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_level_order_and_display() {
+        assert!(Level::Error < Level::Warn && Level::Debug < Level::Trace);
+        assert_eq!(format!("[{:<5}]", Level::Warn), "[WARN ]");
+        assert!(StderrLogger::new(Level::Info).enabled(Level::Warn));
+        assert!(!StderrLogger::new(Level::Info).enabled(Level::Debug));
+    }
+}
@@ -0,0 +1,199 @@
+//! Middleware which wraps the handlers of a [`Router`](crate::Router).
+//!
+//! A [`Layer`] receives each request before the handler does, and decides whether and how to pass
+//! it on by calling [`Next::run`]. It can change the request on the way in, the response on the
+//! way out, or answer the request itself without calling the handler at all.
+//!
+//! ```
+//! use cairo::{
+//!     http::{Request, Response},
+//!     middleware::Next,
+//!     routing::get,
+//!     Router,
+//! };
+//!
+//! fn powered_by(req: Request, next: Next) -> Response {
+//!     let mut response = next.run(req);
+//!     response.headers_mut().insert("X-Powered-By", "cairo");
+//!     response
+//! }
+//!
+//! let router = Router::new()
+//!     .route("/", get(|| "Hello, world!"))
+//!     .layer(powered_by);
+//! ```
+mod access_log;
+mod auth;
+mod compression;
+mod conditional;
+mod cors;
+mod csrf;
+mod decompression;
+mod jwt;
+mod metrics;
+mod rate_limit;
+mod request_id;
+mod timeout;
+mod trusted_proxy;
+
+use std::sync::Arc;
+
+use crate::{
+    handler::{BoxedHandler, Handler},
+    headers::Vary,
+    http::{HeaderMap, Request, Response},
+    response::IntoResponse,
+};
+
+pub use access_log::{AccessLogLayer, LogFormat};
+pub use auth::{Authenticated, BasicAuthLayer, BearerAuthLayer};
+pub use compression::CompressionLayer;
+pub use conditional::{ConditionFailed, ConditionalLayer, Preconditions};
+pub use cors::CorsLayer;
+pub use csrf::{CsrfLayer, CsrfToken};
+pub use decompression::DecompressionLayer;
+pub use jwt::{Claims, JwtError, JwtLayer};
+pub use metrics::{Metrics, MetricsHandler, MetricsLayer};
+pub use rate_limit::RateLimitLayer;
+pub use request_id::{RequestId, RequestIdLayer};
+pub use timeout::{Deadline, TimeoutLayer};
+pub(crate) use trusted_proxy::client_ip;
+pub use trusted_proxy::{ClientIp, ForwardedHeader, TrustedProxyLayer};
+
+/// A piece of middleware. Any function or closure taking a [`Request`] and [`Next`] and returning a
+/// [`Response`] is a `Layer`, and types which need configuration can implement it directly.
+pub trait Layer: Send + Sync + 'static {
+    fn call(&self, req: Request, next: Next) -> Response;
+}
+
+impl<F> Layer for F
+where
+    F: Fn(Request, Next) -> Response + Send + Sync + 'static,
+{
+    fn call(&self, req: Request, next: Next) -> Response {
+        self(req, next)
+    }
+}
+
+/// The layers stored by a `Router`, in the order they were added.
+pub(crate) type Layers = Arc<Vec<Arc<dyn Layer>>>;
+
+/// The rest of the middleware chain, ending with the handler for the request. It owns everything it
+/// needs, so it can be moved to another thread before being run.
+pub struct Next {
+    layers: Layers,
+    /// How many of `layers` have not been run yet. Layers run from the last added to the first.
+    remaining: usize,
+    /// The handler which matched the request, or `None` if the router found no route.
+    endpoint: Option<BoxedHandler>,
+}
+
+impl Next {
+    pub(crate) fn new(layers: Layers, endpoint: Option<BoxedHandler>) -> Self {
+        Self {
+            remaining: layers.len(),
+            layers,
+            endpoint,
+        }
+    }
+
+    /// Pass the request to the next layer, or to the handler once every layer has run.
+    pub fn run(mut self, req: Request) -> Response {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            let layer = Arc::clone(&self.layers[self.remaining]);
+            return layer.call(req, self);
+        }
+
+        match self.endpoint {
+            Some(handler) => handler.call_handler(req),
+            None => (404, "Not Found").into_response(),
+        }
+    }
+}
+
+/// A route's handler wrapped in a layer by [`Router::route_layer`](crate::Router::route_layer).
+pub(crate) struct Layered {
+    layer: Arc<dyn Layer>,
+    handler: BoxedHandler,
+}
+
+impl Layered {
+    pub(crate) fn new(layer: Arc<dyn Layer>, handler: BoxedHandler) -> Self {
+        Self { layer, handler }
+    }
+}
+
+impl Handler<()> for Layered {
+    fn call_handler(&self, req: Request) -> Response {
+        let layers: Layers = Arc::new(vec![Arc::clone(&self.layer)]);
+        Next::new(layers, Some(self.handler.clone())).run(req)
+    }
+}
+
+/// Add `name` to the `Vary` header of a response, unless it is already listed or the header is
+/// `*`.
+pub(crate) fn add_vary(headers: &mut HeaderMap, name: &str) {
+    let mut vary = headers.typed_get::<Vary>().unwrap_or(Vary(vec![]));
+    if !vary
+        .0
+        .iter()
+        .any(|listed| listed == "*" || listed.eq_ignore_ascii_case(name))
+    {
+        vary.0.push(name.to_string());
+        headers.typed_insert(vary);
+    }
+}
+
+/// Run `req` through `layer` alone, with `handler` at the end of the chain.
+#[cfg(test)]
+pub(crate) fn run_layer(layer: impl Layer, req: Request, handler: BoxedHandler) -> Response {
+    let layers: Layers = Arc::new(vec![Arc::new(layer)]);
+    Next::new(layers, Some(handler)).run(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Method;
+
+    fn tag(name: &'static str) -> impl Layer {
+        move |req: Request, next: Next| {
+            let mut response = next.run(req);
+            response.headers_mut().append("X-Order", name);
+            response
+        }
+    }
+
+    #[test]
+    fn test_next_runs_layers_outermost_last_added() {
+        let layers: Layers = Arc::new(vec![Arc::new(tag("inner")), Arc::new(tag("outer"))]);
+        let handler = BoxedHandler::from_handler(|| "Handler");
+
+        // The outer layer sees the response last, so adds its header after the inner one.
+        let response = Next::new(layers, Some(handler)).run(Request::new(Method::Get, "/"));
+        assert_eq!(response.text(), "Handler");
+        assert_eq!(
+            response.headers().get_all("X-Order").collect::<Vec<_>>(),
+            vec!["inner", "outer"]
+        );
+    }
+
+    #[test]
+    fn test_layer_can_short_circuit() {
+        let deny = |_req: Request, _next: Next| (400, "Denied").into_response();
+        let layers: Layers = Arc::new(vec![Arc::new(deny)]);
+        let handler = BoxedHandler::from_handler(|| "Handler");
+
+        let response = Next::new(layers, Some(handler)).run(Request::new(Method::Get, "/"));
+        assert_eq!(response.text(), "Denied");
+    }
+
+    #[test]
+    fn test_next_without_endpoint_is_not_found() {
+        let layers: Layers = Arc::new(vec![Arc::new(tag("only"))]);
+        let response = Next::new(layers, None).run(Request::new(Method::Get, "/"));
+        assert_eq!(response.status_code(), 404);
+        assert_eq!(response.headers().get("X-Order"), Some("only"));
+    }
+}
@@ -0,0 +1,387 @@
+use std::{fmt, fmt::Write, sync::Arc, time::Instant};
+
+use crate::{
+    headers::{Referer, UserAgent},
+    http::{HttpDate, Request, Response, PROTOCOL},
+    log::{self, Level, Logger},
+    middleware::{client_ip, Layer, Next, RequestId},
+};
+
+/// The format of each line written by an [`AccessLogLayer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format, such as
+    /// `127.0.0.1 - - [06/Nov/1994:08:49:37 +0000] "GET /index.html HTTP/1.1" 200 2326`.
+    Common,
+    /// The Common Log Format followed by the quoted `Referer` and `User-Agent` headers.
+    Combined,
+    /// One JSON object per line, which also includes how long the handler took in milliseconds.
+    Json,
+}
+
+/// A [`Layer`] which logs one line for each request, with the client's address, the method and
+/// path, the status code and the size of the body.
+///
+/// Lines are logged at [`Level::Info`] with the logger installed by
+/// [`set_logger`](crate::log::set_logger), or with the one given to
+/// [`with_logger`](AccessLogLayer::with_logger). The size of a streaming body of unknown length is
+/// logged as `-`, or `null` in JSON.
+///
+/// If a [`RequestIdLayer`](super::RequestIdLayer) has run first, the request's ID is logged too,
+/// as a quoted field at the end of the Common and Combined formats.
+///
+/// ```
+/// use cairo::{
+///     middleware::{AccessLogLayer, LogFormat},
+///     routing::get,
+///     Router,
+/// };
+///
+/// let router = Router::new()
+///     .route("/", get(|| "Hello, world!"))
+///     .layer(AccessLogLayer::new(LogFormat::Combined));
+/// ```
+#[derive(Clone)]
+pub struct AccessLogLayer {
+    format: LogFormat,
+    logger: Option<Arc<dyn Logger>>,
+}
+
+impl AccessLogLayer {
+    /// Create an `AccessLogLayer` which writes lines in `format` to the installed logger.
+    pub fn new(format: LogFormat) -> Self {
+        Self {
+            format,
+            logger: None,
+        }
+    }
+
+    /// Write to `logger` instead of the installed logger, to keep the access log separate from
+    /// other messages.
+    pub fn with_logger(mut self, logger: impl Logger) -> Self {
+        self.logger = Some(Arc::new(logger));
+        self
+    }
+
+    fn logger(&self) -> Option<&dyn Logger> {
+        match &self.logger {
+            Some(logger) => Some(logger.as_ref()),
+            None => log::logger(),
+        }
+    }
+}
+
+impl Default for AccessLogLayer {
+    fn default() -> Self {
+        Self::new(LogFormat::Common)
+    }
+}
+
+impl fmt::Debug for AccessLogLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLogLayer")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What is known about a request once its response has been produced.
+struct Entry {
+    time: HttpDate,
+    peer: Option<String>,
+    method: &'static str,
+    path: String,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    status: u16,
+    bytes: Option<u64>,
+    latency_ms: f64,
+    request_id: Option<String>,
+}
+
+/// Write `value` between double quotes, escaping quotes, backslashes and control characters as
+/// Apache does, so that a client cannot forge a line.
+fn write_quoted(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                line.push('\\');
+                line.push(c);
+            }
+            c if c.is_control() => {
+                let _ = write!(line, "\\x{:02x}", c as u32);
+            }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+/// Write `value` as a JSON string.
+fn write_json_string(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => line.push_str("\\\""),
+            '\\' => line.push_str("\\\\"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\t' => line.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(line, "\\u{:04x}", c as u32);
+            }
+            c => line.push(c),
+        }
+    }
+    line.push('"');
+}
+
+fn write_json_option(line: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => write_json_string(line, value),
+        None => line.push_str("null"),
+    }
+}
+
+impl Entry {
+    fn format(&self, format: LogFormat) -> String {
+        let mut line = String::new();
+        if format == LogFormat::Json {
+            line.push_str("{\"time\":");
+            write_json_string(&mut line, &self.time.to_rfc3339());
+            line.push_str(",\"peer\":");
+            write_json_option(&mut line, self.peer.as_deref());
+            line.push_str(",\"method\":");
+            write_json_string(&mut line, self.method);
+            line.push_str(",\"path\":");
+            write_json_string(&mut line, &self.path);
+            let _ = write!(line, ",\"status\":{},\"bytes\":", self.status);
+            match self.bytes {
+                Some(bytes) => line.push_str(&bytes.to_string()),
+                None => line.push_str("null"),
+            }
+            let _ = write!(line, ",\"latency_ms\":{:.3}", self.latency_ms);
+            line.push_str(",\"referer\":");
+            write_json_option(&mut line, self.referer.as_deref());
+            line.push_str(",\"user_agent\":");
+            write_json_option(&mut line, self.user_agent.as_deref());
+            line.push_str(",\"request_id\":");
+            write_json_option(&mut line, self.request_id.as_deref());
+            line.push('}');
+            return line;
+        }
+
+        let _ = write!(
+            line,
+            "{} - - [{}] ",
+            self.peer.as_deref().unwrap_or("-"),
+            self.time.to_common_log()
+        );
+        write_quoted(
+            &mut line,
+            &format!("{} {} {}", self.method, self.path, PROTOCOL),
+        );
+        let _ = write!(line, " {} ", self.status);
+        match self.bytes {
+            Some(bytes) => line.push_str(&bytes.to_string()),
+            None => line.push('-'),
+        }
+        if format == LogFormat::Combined {
+            line.push(' ');
+            write_quoted(&mut line, self.referer.as_deref().unwrap_or("-"));
+            line.push(' ');
+            write_quoted(&mut line, self.user_agent.as_deref().unwrap_or("-"));
+        }
+        if let Some(request_id) = &self.request_id {
+            line.push(' ');
+            write_quoted(&mut line, request_id);
+        }
+        line
+    }
+}
+
+impl Layer for AccessLogLayer {
+    fn call(&self, req: Request, next: Next) -> Response {
+        let logger = self.logger();
+        if !logger.is_some_and(|logger| logger.enabled(Level::Info)) {
+            return next.run(req);
+        }
+
+        let start = Instant::now();
+        let time = HttpDate::now();
+        let peer = client_ip(req.extensions()).map(|ip| ip.to_string());
+        let method = req.method().as_str();
+        let path = req.path().clone();
+        let referer = req.headers().typed_get().map(|Referer(referer)| referer);
+        let user_agent = req.headers().typed_get().map(|UserAgent(agent)| agent);
+        let request_id = req
+            .extensions()
+            .get::<RequestId>()
+            .map(|id| id.as_str().to_string());
+
+        let response = next.run(req);
+
+        let entry = Entry {
+            time,
+            peer,
+            method,
+            path,
+            referer,
+            user_agent,
+            status: response.status_code(),
+            bytes: response.body().len(),
+            latency_ms: start.elapsed().as_secs_f64() * 1000.0,
+            request_id,
+        };
+        log::log_to(
+            logger,
+            Level::Info,
+            format_args!("{}", entry.format(self.format)),
+        );
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use super::*;
+    use crate::{
+        extract::ConnectInfo,
+        handler::BoxedHandler,
+        http::{Body, HeaderMap, Method},
+        log::tests::MemoryLogger,
+        middleware::{run_layer, Layers, RequestIdLayer},
+    };
+
+    /// A logger which shares its lines with the test after being moved into the layer.
+    #[derive(Clone, Default)]
+    struct SharedLogger(Arc<MemoryLogger>);
+
+    impl Logger for SharedLogger {
+        fn enabled(&self, level: Level) -> bool {
+            self.0.enabled(level)
+        }
+
+        fn log(&self, level: Level, message: fmt::Arguments<'_>) {
+            self.0.log(level, message);
+        }
+    }
+
+    fn call(format: LogFormat, req: Request, handler: BoxedHandler) -> String {
+        let logger = SharedLogger::default();
+        let layer = AccessLogLayer::new(format).with_logger(logger.clone());
+        run_layer(layer, req, handler);
+
+        let lines = logger.0.lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].0, Level::Info);
+        lines[0].1.clone()
+    }
+
+    fn request() -> Request {
+        let headers = HeaderMap::from([
+            ("Referer", "https://example.com/"),
+            ("User-Agent", "curl/8.0 \"quoted\""),
+        ]);
+        let mut req = Request::with_headers(Method::Get, "/users?page=2", headers);
+        req.extensions_mut().insert(ConnectInfo {
+            peer_addr: "192.0.2.7:51234".parse().unwrap(),
+            local_addr: "127.0.0.1:8080".parse().unwrap(),
+        });
+        req
+    }
+
+    /// Remove the timestamp, which changes from run to run.
+    fn without_time(line: &str) -> String {
+        let start = line.find('[').unwrap();
+        let end = line.find(']').unwrap();
+        format!("{}{}", &line[..start], &line[end + 1..])
+    }
+
+    #[test]
+    fn test_access_log_common() {
+        let line = call(
+            LogFormat::Common,
+            request(),
+            BoxedHandler::from_handler(|| "Hello"),
+        );
+        assert_eq!(
+            without_time(&line),
+            "192.0.2.7 - -  \"GET /users?page=2 HTTP/1.1\" 200 5"
+        );
+    }
+
+    #[test]
+    fn test_access_log_combined() {
+        let line = call(
+            LogFormat::Combined,
+            Request::new(Method::Post, "/"),
+            BoxedHandler::from_handler(|| (404, "Not Found")),
+        );
+        assert_eq!(
+            without_time(&line),
+            "- - -  \"POST / HTTP/1.1\" 404 9 \"-\" \"-\""
+        );
+
+        let line = call(
+            LogFormat::Combined,
+            request(),
+            BoxedHandler::from_handler(|| "Hello"),
+        );
+        assert!(line.ends_with(r#" 200 5 "https://example.com/" "curl/8.0 \"quoted\"""#));
+    }
+
+    #[test]
+    fn test_access_log_json() {
+        let handler = BoxedHandler::from_handler(|| {
+            Response::new(
+                200,
+                HeaderMap::new(),
+                Body::from_reader(io::repeat(b'a').take(10)),
+            )
+        });
+        let line = call(LogFormat::Json, request(), handler);
+        let time_end = line.find("\",\"peer\"").unwrap();
+        let latency_start = line.find(",\"latency_ms\":").unwrap();
+        let latency_end = line.find(",\"referer\"").unwrap();
+        assert_eq!(&line[..9], "{\"time\":\"");
+        assert_eq!(
+            &line[time_end..latency_start],
+            r#"","peer":"192.0.2.7","method":"GET","path":"/users?page=2","status":200,"bytes":null"#
+        );
+        assert_eq!(
+            &line[latency_end..],
+            r#","referer":"https://example.com/","user_agent":"curl/8.0 \"quoted\"","request_id":null}"#
+        );
+    }
+
+    #[test]
+    fn test_access_log_request_id() {
+        let logger = SharedLogger::default();
+        let layers: Layers = Arc::new(vec![
+            Arc::new(AccessLogLayer::new(LogFormat::Common).with_logger(logger.clone())),
+            Arc::new(RequestIdLayer::new()),
+        ]);
+        let mut req = request();
+        req.headers_mut().insert("X-Request-Id", "req-42");
+        let handler = BoxedHandler::from_handler(|| "Hello");
+        Next::new(layers, Some(handler)).run(req);
+
+        let lines = logger.0.lines.lock().unwrap();
+        assert!(lines[0].1.ends_with(" 200 5 \"req-42\""));
+    }
+
+    #[test]
+    fn test_access_log_escapes_control_characters() {
+        let mut line = String::new();
+        write_quoted(&mut line, "a\nb\"");
+        assert_eq!(line, r#""a\x0ab\"""#);
+
+        let mut line = String::new();
+        write_json_string(&mut line, "a\nb\u{1}");
+        assert_eq!(line, r#""a\nb\u0001""#);
+    }
+}
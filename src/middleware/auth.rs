@@ -0,0 +1,346 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    headers::Authorization,
+    http::{HeaderMap, Parts, Request, Response},
+    middleware::{Layer, Next},
+};
+
+/// A function which checks a username and password.
+type BasicValidator<P> = Arc<dyn Fn(&str, &str) -> Option<P> + Send + Sync>;
+
+/// A function which checks a bearer token.
+type BearerValidator<P> = Arc<dyn Fn(&str) -> Option<P> + Send + Sync>;
+
+/// The principal which a [`BasicAuthLayer`] or [`BearerAuthLayer`] authenticated, such as a user
+/// ID or a set of permissions.
+///
+/// `P` must be the type returned by the layer's validator, otherwise the request is rejected.
+///
+/// ```
+/// use cairo::{
+///     middleware::{Authenticated, BearerAuthLayer},
+///     routing::get,
+///     Router,
+/// };
+///
+/// fn whoami(Authenticated(user): Authenticated<String>) -> String {
+///     format!("Hello, {}", user)
+/// }
+///
+/// let router = Router::new()
+///     .route("/whoami", get(whoami))
+///     .route_layer(BearerAuthLayer::new(|token| {
+///         (token == "secret-token").then(|| "admin".to_string())
+///     }));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authenticated<P>(pub P);
+
+impl<P: Clone + Send + Sync + 'static> FromRequestParts for Authenticated<P> {
+    /// Clone the principal out of the request extensions, where the layer put it.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .extensions
+            .get::<Authenticated<P>>()
+            .cloned()
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl<P: Clone + Send + Sync + 'static> FromRequest for Authenticated<P> {
+    /// When an `Authenticated` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// Quote `value` for use as a parameter of a `WWW-Authenticate` challenge.
+pub(super) fn quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Return a `401 Unauthorized` response which asks the client to authenticate with `challenge`.
+pub(super) fn unauthorized(challenge: String) -> Response {
+    let headers = HeaderMap::from([
+        ("WWW-Authenticate", challenge),
+        ("Content-Type", "text/plain".to_string()),
+    ]);
+    Response::new(401, headers, "Unauthorized")
+}
+
+/// A [`Layer`] which requires HTTP Basic authentication, as described in RFC 7617.
+///
+/// The username and password from the `Authorization` header are given to a validator, which
+/// returns the principal they belong to, or `None` to refuse them. The principal is stored in the
+/// request extensions, where handlers can extract it as an [`Authenticated`]. Requests without
+/// valid credentials are answered with `401 Unauthorized` and a `WWW-Authenticate` challenge, which
+/// makes browsers ask the user to log in.
+///
+/// Basic authentication sends the password with every request, so it should only be used over
+/// HTTPS. Validators should compare secrets in constant time, or compare hashes of them.
+///
+/// Add the layer with [`Router::route_layer`](crate::Router::route_layer), so that it only protects
+/// the routes added before it and paths which match no route are still answered with a 404.
+///
+/// ```
+/// use cairo::{middleware::BasicAuthLayer, routing::get, Router};
+///
+/// let router = Router::new()
+///     .route("/admin", get(|| "Welcome"))
+///     .route_layer(BasicAuthLayer::new("Admin", |username, password| {
+///         (username == "admin" && password == "hunter2").then(|| username.to_string())
+///     }))
+///     .route("/", get(|| "Hello, world!"));
+/// ```
+#[derive(Clone)]
+pub struct BasicAuthLayer<P> {
+    realm: String,
+    validator: BasicValidator<P>,
+}
+
+impl<P> BasicAuthLayer<P> {
+    /// Create a `BasicAuthLayer` for `realm`, which browsers may show when asking for a login.
+    pub fn new<F>(realm: impl Into<String>, validator: F) -> Self
+    where
+        F: Fn(&str, &str) -> Option<P> + Send + Sync + 'static,
+    {
+        Self {
+            realm: realm.into(),
+            validator: Arc::new(validator),
+        }
+    }
+
+    fn challenge(&self) -> String {
+        format!("Basic realm={}, charset=\"UTF-8\"", quoted(&self.realm))
+    }
+}
+
+impl<P> fmt::Debug for BasicAuthLayer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicAuthLayer")
+            .field("realm", &self.realm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P: Clone + Send + Sync + 'static> Layer for BasicAuthLayer<P> {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let principal = req
+            .headers()
+            .typed_get::<Authorization>()
+            .and_then(|authorization| authorization.basic_credentials())
+            .and_then(|(username, password)| (self.validator)(&username, &password));
+
+        match principal {
+            Some(principal) => {
+                req.extensions_mut().insert(Authenticated(principal));
+                next.run(req)
+            }
+            None => unauthorized(self.challenge()),
+        }
+    }
+}
+
+/// A [`Layer`] which requires a bearer token, as described in RFC 6750, such as an API key or an
+/// access token.
+///
+/// The token from the `Authorization` header is given to a validator, which returns the principal
+/// it belongs to, or `None` to refuse it. The principal is stored in the request extensions, where
+/// handlers can extract it as an [`Authenticated`]. Requests without a token are answered with
+/// `401 Unauthorized` and a `WWW-Authenticate: Bearer` challenge, which also has
+/// `error="invalid_token"` when a token was given but refused.
+///
+/// ```
+/// use cairo::{middleware::BearerAuthLayer, routing::get, Router};
+///
+/// let router = Router::new()
+///     .route("/api/users", get(|| "[]"))
+///     .route_layer(BearerAuthLayer::new(|token| (token == "secret-token").then_some(())))
+///     .route("/", get(|| "Hello, world!"));
+/// ```
+#[derive(Clone)]
+pub struct BearerAuthLayer<P> {
+    realm: Option<String>,
+    validator: BearerValidator<P>,
+}
+
+impl<P> BearerAuthLayer<P> {
+    /// Create a `BearerAuthLayer` which checks tokens with `validator`.
+    pub fn new<F>(validator: F) -> Self
+    where
+        F: Fn(&str) -> Option<P> + Send + Sync + 'static,
+    {
+        Self {
+            realm: None,
+            validator: Arc::new(validator),
+        }
+    }
+
+    /// Name the protected area in the challenge.
+    pub fn with_realm(mut self, realm: impl Into<String>) -> Self {
+        self.realm = Some(realm.into());
+        self
+    }
+
+    fn challenge(&self, invalid_token: bool) -> String {
+        let mut params = vec![];
+        if let Some(realm) = &self.realm {
+            params.push(format!("realm={}", quoted(realm)));
+        }
+        if invalid_token {
+            params.push("error=\"invalid_token\"".to_string());
+        }
+        if params.is_empty() {
+            "Bearer".to_string()
+        } else {
+            format!("Bearer {}", params.join(", "))
+        }
+    }
+}
+
+impl<P> fmt::Debug for BearerAuthLayer<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BearerAuthLayer")
+            .field("realm", &self.realm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P: Clone + Send + Sync + 'static> Layer for BearerAuthLayer<P> {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let authorization = req.headers().typed_get::<Authorization>();
+        let Some(token) = authorization
+            .as_ref()
+            .and_then(|authorization| authorization.bearer_token())
+        else {
+            return unauthorized(self.challenge(false));
+        };
+
+        match (self.validator)(token) {
+            Some(principal) => {
+                req.extensions_mut().insert(Authenticated(principal));
+                next.run(req)
+            }
+            None => unauthorized(self.challenge(true)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handler::BoxedHandler, headers::Header, http::Method, middleware::run_layer, routing::get,
+        Router,
+    };
+
+    fn call(layer: impl Layer, headers: &[(&str, &str)]) -> Response {
+        let headers = headers.iter().copied().collect::<HeaderMap>();
+        let handler = BoxedHandler::from_handler(|Authenticated(user): Authenticated<String>| {
+            format!("Hello, {}", user)
+        });
+        let req = Request::with_headers(Method::Get, "/", headers);
+        run_layer(layer, req, handler)
+    }
+
+    fn basic_layer() -> BasicAuthLayer<String> {
+        BasicAuthLayer::new("Admin \"area\"", |username, password| {
+            (username == "admin" && password == "hunter2").then(|| username.to_string())
+        })
+    }
+
+    fn bearer_layer() -> BearerAuthLayer<String> {
+        BearerAuthLayer::new(|token| (token == "abc.def").then(|| "service".to_string()))
+    }
+
+    #[test]
+    fn test_basic_auth_accepts_valid_credentials() {
+        let authorization = Authorization::basic("admin", "hunter2").encode();
+        let response = call(basic_layer(), &[("Authorization", &authorization)]);
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), "Hello, admin");
+    }
+
+    #[test]
+    fn test_basic_auth_rejects() {
+        let wrong = Authorization::basic("admin", "wrong").encode();
+        for headers in [
+            vec![],
+            vec![("Authorization", wrong.as_str())],
+            vec![("Authorization", "Basic !!!")],
+            vec![("Authorization", "Bearer abc.def")],
+        ] {
+            let response = call(basic_layer(), &headers);
+            assert_eq!(response.status_code(), 401);
+            assert_eq!(
+                response.headers().get("WWW-Authenticate"),
+                Some(r#"Basic realm="Admin \"area\"", charset="UTF-8""#)
+            );
+        }
+    }
+
+    #[test]
+    fn test_bearer_auth() {
+        let response = call(bearer_layer(), &[("Authorization", "Bearer abc.def")]);
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), "Hello, service");
+
+        let response = call(bearer_layer(), &[]);
+        assert_eq!(response.status_code(), 401);
+        assert_eq!(response.headers().get("WWW-Authenticate"), Some("Bearer"));
+
+        let layer = bearer_layer().with_realm("api");
+        let response = call(layer, &[("Authorization", "Bearer wrong")]);
+        assert_eq!(response.status_code(), 401);
+        assert_eq!(
+            response.headers().get("WWW-Authenticate"),
+            Some(r#"Bearer realm="api", error="invalid_token""#)
+        );
+    }
+
+    #[test]
+    fn test_auth_route_layer() {
+        let router = Router::new()
+            .route(
+                "/admin",
+                get(|Authenticated(user): Authenticated<String>| format!("Hello, {}", user)),
+            )
+            .route_layer(basic_layer())
+            .route("/", get(|| "Public"));
+
+        let response = router.call(Request::new(Method::Get, "/"));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), "Public");
+
+        let response = router.call(Request::new(Method::Get, "/admin"));
+        assert_eq!(response.status_code(), 401);
+
+        let authorization = Authorization::basic("admin", "hunter2").encode();
+        let headers = HeaderMap::from([("Authorization", authorization.as_str())]);
+        let response = router.call(Request::with_headers(Method::Get, "/admin", headers));
+        assert_eq!(response.text(), "Hello, admin");
+
+        // Unknown paths are not found, rather than asking for credentials.
+        let response = router.call(Request::new(Method::Get, "/missing"));
+        assert_eq!(response.status_code(), 404);
+        assert_eq!(response.headers().get("WWW-Authenticate"), None);
+    }
+
+    #[test]
+    fn test_authenticated_without_layer() {
+        let req = Request::new(Method::Get, "/");
+        assert!(Authenticated::<String>::from_request(req).is_err());
+    }
+}
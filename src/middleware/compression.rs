@@ -0,0 +1,293 @@
+use crate::{
+    core::deflate::{self, Encoder, Format},
+    headers::{AcceptEncoding, ContentEncoding, ContentType, ETag, EntityTag, Header},
+    http::{Body, Request, Response},
+    middleware::{add_vary, Layer, Next},
+};
+
+/// Bodies smaller than this are sent as they are by default, since compressing them saves little
+/// and can even make them bigger.
+const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// The content types which are compressed by default. Images, video and archives are already
+/// compressed, so they are left alone.
+const DEFAULT_CONTENT_TYPES: [&str; 12] = [
+    "text/html",
+    "text/css",
+    "text/plain",
+    "text/javascript",
+    "text/csv",
+    "text/markdown",
+    "text/xml",
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "application/wasm",
+    "image/svg+xml",
+];
+
+/// A [`Layer`] which compresses response bodies with gzip or deflate, whichever the client prefers
+/// in its `Accept-Encoding` header.
+///
+/// Only successful responses with an allowed `Content-Type` and a body of at least the minimum size
+/// are compressed. A streaming body of unknown length is assumed to be large enough, and is
+/// compressed 32 KiB at a time as it is sent. Partial content is never compressed, since its
+/// `Content-Range` counts the bytes of the uncompressed body.
+///
+/// Any response which could have been compressed gets `Vary: Accept-Encoding`, so that caches
+/// keep the compressed and uncompressed forms apart. A strong `ETag` is made weak, because the
+/// bytes sent are no longer the bytes it was computed from.
+///
+/// ```
+/// use cairo::{middleware::CompressionLayer, routing::get, Router};
+///
+/// let router = Router::new()
+///     .route("/", get(|| "Hello, world!"))
+///     .layer(CompressionLayer::new().with_min_size(256));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CompressionLayer {
+    min_size: u64,
+    content_types: Vec<String>,
+}
+
+impl CompressionLayer {
+    /// Create a `CompressionLayer` with the default minimum size of 1 KiB, which compresses text,
+    /// JSON, JavaScript, XML, WebAssembly and SVG.
+    pub fn new() -> Self {
+        Self {
+            min_size: DEFAULT_MIN_SIZE,
+            content_types: DEFAULT_CONTENT_TYPES.map(String::from).to_vec(),
+        }
+    }
+
+    /// Set the smallest body, in bytes, which will be compressed.
+    pub fn with_min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Replace the content types which will be compressed. A type such as `text/*` allows every
+    /// subtype. Parameters such as `charset` are ignored when matching.
+    pub fn with_content_types<I, T>(mut self, content_types: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.content_types = content_types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Return `true` if `response` is one which should be compressed for a client that asks.
+    fn is_compressible(&self, response: &Response) -> bool {
+        if response.status_code() != 200 || response.headers().contains_key(ContentEncoding::NAME) {
+            return false;
+        }
+        if response.body().len().is_some_and(|len| len < self.min_size) {
+            return false;
+        }
+
+        let Some(ContentType(content_type)) = response.headers().typed_get() else {
+            return false;
+        };
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.content_types.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            match allowed.strip_suffix('*') {
+                Some(prefix) => essence.starts_with(prefix),
+                None => essence == allowed,
+            }
+        })
+    }
+}
+
+impl Default for CompressionLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Choose the coding to use from the client's `Accept-Encoding`, preferring gzip on a tie, or
+/// `None` if it accepts neither.
+fn negotiate(accept_encoding: Option<AcceptEncoding>) -> Option<(Format, &'static str)> {
+    let accept_encoding = accept_encoding?;
+    let gzip = accept_encoding.quality("gzip");
+    let deflate = accept_encoding.quality("deflate");
+    if gzip == 0 && deflate == 0 {
+        None
+    } else if gzip >= deflate {
+        Some((Format::Gzip, "gzip"))
+    } else {
+        Some((Format::Zlib, "deflate"))
+    }
+}
+
+impl Layer for CompressionLayer {
+    fn call(&self, req: Request, next: Next) -> Response {
+        let coding = negotiate(req.headers().typed_get());
+        let mut response = next.run(req);
+        if !self.is_compressible(&response) {
+            return response;
+        }
+
+        add_vary(response.headers_mut(), AcceptEncoding::NAME);
+
+        let Some((format, coding)) = coding else {
+            return response;
+        };
+
+        let mut headers = response.headers().clone();
+        headers.remove("Content-Length");
+        headers.typed_insert(ContentEncoding(coding.to_string()));
+        if let Some(ETag(etag)) = headers.typed_get() {
+            if !etag.is_weak() {
+                headers.typed_insert(ETag(EntityTag::weak(etag.tag())));
+            }
+        }
+
+        let status_code = response.status_code();
+        let body = response.into_body();
+        let body = match body.as_bytes() {
+            Some(bytes) => Body::from(deflate::compress(format, bytes)),
+            None => Body::from_reader(Encoder::new(body.into_reader(), format)),
+        };
+        Response::new(status_code, headers, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::Arc};
+
+    use super::*;
+    use crate::{
+        core::deflate::tests::{decompress, sample_text},
+        handler::BoxedHandler,
+        http::{HeaderMap, Method},
+        middleware::run_layer,
+    };
+
+    fn call(layer: CompressionLayer, accept_encoding: &str, response: Response) -> Response {
+        let response = Arc::new(std::sync::Mutex::new(Some(response)));
+        let handler = BoxedHandler::from_handler(move || response.lock().unwrap().take().unwrap());
+        let headers = HeaderMap::from([("Accept-Encoding", accept_encoding)]);
+        let req = Request::with_headers(Method::Get, "/", headers);
+        run_layer(layer, req, handler)
+    }
+
+    fn text_response(body: impl Into<Body>) -> Response {
+        let headers = HeaderMap::from([("Content-Type", "text/html; charset=utf-8")]);
+        Response::new(200, headers, body)
+    }
+
+    #[test]
+    fn test_compression_gzip() {
+        let text = sample_text(50_000);
+        let mut response = text_response(text.clone());
+        response
+            .headers_mut()
+            .typed_insert(ETag(EntityTag::strong("abc")));
+
+        let response = call(CompressionLayer::new(), "deflate;q=0.5, gzip", response);
+        assert_eq!(response.headers().get("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.headers().get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers().get("ETag"), Some("W/\"abc\""));
+        let compressed = response.into_body().into_bytes().unwrap();
+        assert!(compressed.len() < text.len() / 4);
+        assert_eq!(decompress(Format::Gzip, &compressed), text);
+    }
+
+    #[test]
+    fn test_compression_deflate_streaming() {
+        let text = sample_text(100_000);
+        let response = text_response(Body::sized_reader(Cursor::new(text.clone()), 100_000));
+
+        let response = call(CompressionLayer::new(), "gzip;q=0.1, deflate", response);
+        assert_eq!(response.headers().get("Content-Encoding"), Some("deflate"));
+        assert!(response.body().is_streaming());
+        assert_eq!(response.body().len(), None);
+        let compressed = response.into_body().into_bytes().unwrap();
+        assert_eq!(decompress(Format::Zlib, &compressed), text);
+    }
+
+    #[test]
+    fn test_compression_negotiation() {
+        let accepts = |value: &str| {
+            let headers = HeaderMap::from([("Accept-Encoding", value)]);
+            negotiate(headers.typed_get()).map(|(_, coding)| coding)
+        };
+        assert_eq!(accepts("gzip, deflate"), Some("gzip"));
+        assert_eq!(accepts("deflate"), Some("deflate"));
+        assert_eq!(accepts("*"), Some("gzip"));
+        assert_eq!(accepts("*, gzip;q=0"), Some("deflate"));
+        assert_eq!(accepts("br, identity"), None);
+        assert_eq!(accepts("gzip;q=0"), None);
+        assert_eq!(negotiate(None), None);
+    }
+
+    #[test]
+    fn test_compression_skips() {
+        let text = sample_text(5_000);
+
+        // The client did not ask, but the response could have been compressed.
+        let response = call(
+            CompressionLayer::new(),
+            "identity",
+            text_response(text.clone()),
+        );
+        assert_eq!(response.headers().get("Content-Encoding"), None);
+        assert_eq!(response.headers().get("Vary"), Some("Accept-Encoding"));
+
+        let response = call(CompressionLayer::new(), "gzip", text_response("tiny"));
+        assert_eq!(response.headers().get("Content-Encoding"), None);
+        assert_eq!(response.headers().get("Vary"), None);
+
+        let png = Response::new(
+            200,
+            HeaderMap::from([("Content-Type", "image/png")]),
+            text.clone(),
+        );
+        let response = call(CompressionLayer::new(), "gzip", png);
+        assert_eq!(response.headers().get("Content-Encoding"), None);
+
+        let mut partial = text_response(text.clone());
+        partial.set_status_code(206);
+        let response = call(CompressionLayer::new(), "gzip", partial);
+        assert_eq!(response.headers().get("Content-Encoding"), None);
+
+        let mut encoded = text_response(text.clone());
+        encoded.headers_mut().insert("Content-Encoding", "br");
+        let response = call(CompressionLayer::new(), "gzip", encoded);
+        assert_eq!(response.headers().get("Content-Encoding"), Some("br"));
+    }
+
+    #[test]
+    fn test_compression_options() {
+        let layer = CompressionLayer::new()
+            .with_min_size(0)
+            .with_content_types(["image/*"]);
+
+        let png = Response::new(200, HeaderMap::from([("Content-Type", "image/png")]), "x");
+        let response = call(layer.clone(), "gzip", png);
+        assert_eq!(response.headers().get("Content-Encoding"), Some("gzip"));
+
+        let response = call(layer, "gzip", text_response("x"));
+        assert_eq!(response.headers().get("Content-Encoding"), None);
+    }
+
+    #[test]
+    fn test_compression_keeps_vary() {
+        let mut response = text_response(sample_text(5_000));
+        response.headers_mut().insert("Vary", "Origin");
+        let response = call(CompressionLayer::new(), "gzip", response);
+        assert_eq!(
+            response.headers().get("Vary"),
+            Some("Origin, Accept-Encoding")
+        );
+    }
+}
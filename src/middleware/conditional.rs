@@ -0,0 +1,415 @@
+use crate::{
+    core::sha256::Sha256,
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    headers::{
+        ETag, EntityTag, EntityTagList, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince,
+        LastModified,
+    },
+    http::{Body, HeaderMap, HttpDate, Method, Parts, Request, Response},
+    middleware::{Layer, Next},
+    response::IntoResponse,
+};
+
+/// The headers which a 304 response repeats from the response it replaces, so that caches can
+/// update what they stored.
+const NOT_MODIFIED_HEADERS: [&str; 7] = [
+    "Cache-Control",
+    "Content-Location",
+    "Date",
+    "ETag",
+    "Expires",
+    "Last-Modified",
+    "Vary",
+];
+
+/// How a request's preconditions were not met.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionFailed {
+    /// The client already has the current representation, so a 304 should be sent without a body.
+    NotModified,
+    /// The resource is not in the state the client expected, so a 412 should be sent.
+    PreconditionFailed,
+}
+
+impl IntoResponse for ConditionFailed {
+    fn into_response(self) -> Response {
+        match self {
+            Self::NotModified => Response::new(304, HeaderMap::new(), Body::empty()),
+            Self::PreconditionFailed => (412, "Precondition Failed").into_response(),
+        }
+    }
+}
+
+/// An extractor for the conditional headers of a request: `If-Match`, `If-Unmodified-Since`,
+/// `If-None-Match` and `If-Modified-Since`.
+///
+/// The [`ConditionalLayer`] checks these after the handler has run, which is too late for a request
+/// which changes the resource. A handler for `PUT` or `DELETE` should instead look up the current
+/// validators and call [`evaluate`](Self::evaluate) before making any change.
+///
+/// ```
+/// use cairo::{
+///     headers::EntityTag,
+///     middleware::{ConditionFailed, Preconditions},
+///     routing::put,
+///     Router,
+/// };
+///
+/// fn update(preconditions: Preconditions, body: String) -> Result<String, ConditionFailed> {
+///     let current = EntityTag::strong("v1");
+///     preconditions.evaluate(Some(&current), None)?;
+///     Ok(format!("Saved {} bytes", body.len()))
+/// }
+///
+/// let router = Router::new().route("/document", put(update));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preconditions {
+    /// Whether the request was a `GET` or `HEAD`, which may be answered with a 304.
+    is_read: bool,
+    if_match: Option<IfMatch>,
+    if_unmodified_since: Option<IfUnmodifiedSince>,
+    if_none_match: Option<IfNoneMatch>,
+    if_modified_since: Option<IfModifiedSince>,
+}
+
+impl Preconditions {
+    /// Read the conditional headers of a request. Any which cannot be parsed are ignored.
+    pub fn new(method: &Method, headers: &HeaderMap) -> Self {
+        Self {
+            is_read: matches!(method, Method::Get | Method::Head),
+            if_match: headers.typed_get(),
+            if_unmodified_since: headers.typed_get(),
+            if_none_match: headers.typed_get(),
+            if_modified_since: headers.typed_get(),
+        }
+    }
+
+    /// Return `true` if the request has no conditional headers.
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none()
+            && self.if_unmodified_since.is_none()
+            && self.if_none_match.is_none()
+            && self.if_modified_since.is_none()
+    }
+
+    /// Check the preconditions against the current validators of the resource, in the order given
+    /// by RFC 9110. Returns `Ok` if the request should go ahead.
+    ///
+    /// The resource is assumed to exist, so `*` always matches. A handler for a resource which does
+    /// not exist yet should check for `If-Match: *` itself.
+    pub fn evaluate(
+        &self,
+        etag: Option<&EntityTag>,
+        last_modified: Option<HttpDate>,
+    ) -> Result<(), ConditionFailed> {
+        // `If-Unmodified-Since` is only a fallback for clients which do not have an entity tag.
+        if let Some(if_match) = &self.if_match {
+            let passes = match (&if_match.0, etag) {
+                (EntityTagList::Any, _) => true,
+                (_, Some(etag)) => if_match.precondition_passes(etag),
+                (_, None) => false,
+            };
+            if !passes {
+                return Err(ConditionFailed::PreconditionFailed);
+            }
+        } else if let (Some(since), Some(last_modified)) =
+            (&self.if_unmodified_since, last_modified)
+        {
+            if last_modified > since.0 {
+                return Err(ConditionFailed::PreconditionFailed);
+            }
+        }
+
+        let not_modified = if let Some(if_none_match) = &self.if_none_match {
+            match (&if_none_match.0, etag) {
+                (EntityTagList::Any, _) => true,
+                (_, Some(etag)) => !if_none_match.precondition_passes(etag),
+                (_, None) => false,
+            }
+        } else if let (Some(since), Some(last_modified)) = (&self.if_modified_since, last_modified)
+        {
+            self.is_read && last_modified <= since.0
+        } else {
+            false
+        };
+
+        match (not_modified, self.is_read) {
+            (false, _) => Ok(()),
+            (true, true) => Err(ConditionFailed::NotModified),
+            (true, false) => Err(ConditionFailed::PreconditionFailed),
+        }
+    }
+}
+
+impl FromRequestParts for Preconditions {
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        Ok(Self::new(&parts.method, &parts.headers))
+    }
+}
+
+impl FromRequest for Preconditions {
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// A [`Layer`] which answers conditional `GET` and `HEAD` requests with a 304 Not Modified, or a
+/// 412 Precondition Failed, so that clients do not download a representation they already have.
+///
+/// The validators come from the `ETag` and `Last-Modified` headers of the handler's response. If
+/// the handler did not set an `ETag`, one is computed by hashing a buffered body; a streaming body
+/// is never read ahead just to tag it. Only successful responses are checked.
+///
+/// Other methods pass straight through, since by the time the response exists the change has been
+/// made. Their handlers can use the [`Preconditions`] extractor instead.
+#[derive(Debug, Clone, Default)]
+pub struct ConditionalLayer {
+    weak: bool,
+}
+
+impl ConditionalLayer {
+    /// Create a `ConditionalLayer` which computes strong entity tags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Choose whether computed entity tags are weak. A weak tag only promises the content is
+    /// equivalent, which suits a body that may be encoded differently, such as when it is
+    /// compressed. Tags set by a handler are never changed.
+    pub fn with_weak_etags(mut self, weak: bool) -> Self {
+        self.weak = weak;
+        self
+    }
+
+    /// Compute an entity tag from the body of `response`, if it is held in memory.
+    fn compute_etag(&self, response: &Response) -> Option<EntityTag> {
+        let bytes = response.body().as_bytes()?;
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        let tag: String = hasher.finalize()[..16]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Some(if self.weak {
+            EntityTag::weak(&tag)
+        } else {
+            EntityTag::strong(&tag)
+        })
+    }
+}
+
+impl Layer for ConditionalLayer {
+    fn call(&self, req: Request, next: Next) -> Response {
+        let preconditions = Preconditions::new(req.method(), req.headers());
+        if !preconditions.is_read {
+            return next.run(req);
+        }
+
+        let mut response = next.run(req);
+        if !(200..300).contains(&response.status_code()) {
+            return response;
+        }
+
+        let etag = match response.headers().typed_get::<ETag>() {
+            Some(ETag(etag)) => Some(etag),
+            None => {
+                let etag = self.compute_etag(&response);
+                if let Some(etag) = &etag {
+                    response.headers_mut().typed_insert(ETag(etag.clone()));
+                }
+                etag
+            }
+        };
+        let last_modified = response
+            .headers()
+            .typed_get::<LastModified>()
+            .map(|LastModified(date)| date);
+
+        match preconditions.evaluate(etag.as_ref(), last_modified) {
+            Ok(()) => response,
+            Err(ConditionFailed::NotModified) => {
+                let headers = NOT_MODIFIED_HEADERS
+                    .iter()
+                    .flat_map(|&name| {
+                        response
+                            .headers()
+                            .get_all(name)
+                            .map(move |value| (name, value))
+                    })
+                    .collect();
+                Response::new(304, headers, Body::empty())
+            }
+            Err(failed) => failed.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{handler::BoxedHandler, headers::Header, middleware::run_layer};
+
+    const MODIFIED: &str = "Sun, 06 Nov 1994 08:49:37 GMT";
+    const BEFORE: &str = "Sat, 05 Nov 1994 08:49:37 GMT";
+    const AFTER: &str = "Mon, 07 Nov 1994 08:49:37 GMT";
+
+    fn call(layer: ConditionalLayer, handler: BoxedHandler, req: Request) -> Response {
+        run_layer(layer, req, handler)
+    }
+
+    fn get(headers: &[(&str, &str)]) -> Response {
+        let handler = BoxedHandler::from_handler(|| {
+            let mut headers = HeaderMap::from([("Cache-Control", "max-age=60")]);
+            headers.typed_insert(LastModified(MODIFIED.parse().unwrap()));
+            (headers, "Hello, World!")
+        });
+        let headers: HeaderMap = headers.iter().copied().collect();
+        let req = Request::with_headers(Method::Get, "/", headers);
+        call(ConditionalLayer::new(), handler, req)
+    }
+
+    #[test]
+    fn test_conditional_layer_computes_etag() {
+        let response = get(&[]);
+        assert_eq!(response.status_code(), 200);
+        let ETag(etag) = response.headers().typed_get().unwrap();
+        assert!(!etag.is_weak());
+        assert_eq!(etag.tag().len(), 32);
+
+        // The same body always has the same tag.
+        assert_eq!(
+            get(&[]).headers().get("ETag"),
+            response.headers().get("ETag")
+        );
+
+        let handler = BoxedHandler::from_handler(|| "Hello");
+        let layer = ConditionalLayer::new().with_weak_etags(true);
+        let response = call(layer, handler, Request::new(Method::Get, "/"));
+        assert!(response.headers().get("ETag").unwrap().starts_with("W/"));
+    }
+
+    #[test]
+    fn test_conditional_layer_if_none_match() {
+        let etag = get(&[]).headers().get("ETag").unwrap().to_string();
+
+        let response = get(&[("If-None-Match", &format!("\"other\", W/{}", etag))]);
+        assert_eq!(response.status_code(), 304);
+        assert!(response.body().is_empty());
+        assert_eq!(response.headers().get("ETag"), Some(etag.as_str()));
+        assert_eq!(response.headers().get("Cache-Control"), Some("max-age=60"));
+        assert_eq!(response.headers().get("Content-Type"), None);
+
+        assert_eq!(get(&[("If-None-Match", "\"other\"")]).status_code(), 200);
+        assert_eq!(get(&[("If-None-Match", "*")]).status_code(), 304);
+
+        // `If-Modified-Since` is ignored when there is an `If-None-Match`.
+        let headers = [("If-None-Match", "\"other\""), ("If-Modified-Since", AFTER)];
+        assert_eq!(get(&headers).status_code(), 200);
+    }
+
+    #[test]
+    fn test_conditional_layer_if_modified_since() {
+        assert_eq!(get(&[("If-Modified-Since", MODIFIED)]).status_code(), 304);
+        assert_eq!(get(&[("If-Modified-Since", AFTER)]).status_code(), 304);
+        assert_eq!(get(&[("If-Modified-Since", BEFORE)]).status_code(), 200);
+        assert_eq!(get(&[("If-Modified-Since", "garbage")]).status_code(), 200);
+    }
+
+    #[test]
+    fn test_conditional_layer_if_match() {
+        let etag = get(&[]).headers().get("ETag").unwrap().to_string();
+
+        assert_eq!(get(&[("If-Match", &etag)]).status_code(), 200);
+        assert_eq!(get(&[("If-Match", "*")]).status_code(), 200);
+        let response = get(&[("If-Match", "\"other\"")]);
+        assert_eq!(response.status_code(), 412);
+        assert_eq!(response.text(), "Precondition Failed");
+
+        // A weak tag never passes the strong comparison.
+        assert_eq!(
+            get(&[("If-Match", &format!("W/{}", etag))]).status_code(),
+            412
+        );
+    }
+
+    #[test]
+    fn test_conditional_layer_if_unmodified_since() {
+        assert_eq!(get(&[("If-Unmodified-Since", AFTER)]).status_code(), 200);
+        assert_eq!(get(&[("If-Unmodified-Since", BEFORE)]).status_code(), 412);
+
+        // `If-Unmodified-Since` is ignored when there is an `If-Match`.
+        let headers = [("If-Match", "*"), ("If-Unmodified-Since", BEFORE)];
+        assert_eq!(get(&headers).status_code(), 200);
+    }
+
+    #[test]
+    fn test_conditional_layer_uses_handler_validators() {
+        let handler = BoxedHandler::from_handler(|| {
+            let mut headers = HeaderMap::new();
+            headers.typed_insert(ETag(EntityTag::weak("v2")));
+            let body = Body::from_reader(Cursor::new(b"streamed".to_vec()));
+            (headers, body)
+        });
+        let headers = HeaderMap::from([(IfNoneMatch::NAME, "\"v2\"")]);
+        let req = Request::with_headers(Method::Get, "/", headers);
+        let response = call(ConditionalLayer::new(), handler, req);
+        assert_eq!(response.status_code(), 304);
+        assert_eq!(response.headers().get("ETag"), Some("W/\"v2\""));
+
+        // A streaming body without a tag is not buffered to compute one.
+        let handler =
+            BoxedHandler::from_handler(|| Body::from_reader(Cursor::new(b"streamed".to_vec())));
+        let response = call(
+            ConditionalLayer::new(),
+            handler,
+            Request::new(Method::Get, "/"),
+        );
+        assert_eq!(response.headers().get("ETag"), None);
+        assert!(response.body().is_streaming());
+    }
+
+    #[test]
+    fn test_conditional_layer_skips_other_responses() {
+        let handler = BoxedHandler::from_handler(|| (404, "Not Found"));
+        let headers = HeaderMap::from([("If-None-Match", "*")]);
+        let req = Request::with_headers(Method::Get, "/", headers);
+        assert_eq!(
+            call(ConditionalLayer::new(), handler, req).status_code(),
+            404
+        );
+
+        let handler = BoxedHandler::from_handler(|| "Saved");
+        let headers = HeaderMap::from([("If-Match", "\"other\"")]);
+        let req = Request::with_headers(Method::Put, "/", headers);
+        assert_eq!(call(ConditionalLayer::new(), handler, req).text(), "Saved");
+    }
+
+    #[test]
+    fn test_preconditions_for_unsafe_methods() {
+        let current = EntityTag::strong("v1");
+        let headers = HeaderMap::from([("If-None-Match", "\"v1\"")]);
+        let preconditions = Preconditions::new(&Method::Put, &headers);
+        assert_eq!(
+            preconditions.evaluate(Some(&current), None),
+            Err(ConditionFailed::PreconditionFailed)
+        );
+
+        let headers = HeaderMap::from([("If-Match", "\"v1\"")]);
+        let preconditions = Preconditions::new(&Method::Delete, &headers);
+        assert_eq!(preconditions.evaluate(Some(&current), None), Ok(()));
+        assert_eq!(
+            preconditions.evaluate(None, None),
+            Err(ConditionFailed::PreconditionFailed)
+        );
+
+        // `If-Modified-Since` only applies to reads.
+        let headers = HeaderMap::from([("If-Modified-Since", AFTER)]);
+        let preconditions = Preconditions::new(&Method::Post, &headers);
+        let modified = MODIFIED.parse().ok();
+        assert_eq!(preconditions.evaluate(None, modified), Ok(()));
+        assert!(Preconditions::new(&Method::Get, &HeaderMap::new()).is_empty());
+    }
+}
@@ -0,0 +1,455 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{
+    headers::{Header, Origin},
+    http::{Body, HeaderMap, Method, Request, Response},
+    middleware::{add_vary, Layer, Next},
+};
+
+const ALLOW_ORIGIN: &str = "Access-Control-Allow-Origin";
+const ALLOW_CREDENTIALS: &str = "Access-Control-Allow-Credentials";
+const ALLOW_METHODS: &str = "Access-Control-Allow-Methods";
+const ALLOW_HEADERS: &str = "Access-Control-Allow-Headers";
+const EXPOSE_HEADERS: &str = "Access-Control-Expose-Headers";
+const MAX_AGE: &str = "Access-Control-Max-Age";
+const REQUEST_METHOD: &str = "Access-Control-Request-Method";
+const REQUEST_HEADERS: &str = "Access-Control-Request-Headers";
+
+/// Which origins may read responses.
+#[derive(Clone)]
+enum AllowOrigin {
+    Any,
+    List(Vec<String>),
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync>),
+}
+
+impl fmt::Debug for AllowOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowOrigin::Any => write!(f, "Any"),
+            AllowOrigin::List(origins) => f.debug_tuple("List").field(origins).finish(),
+            AllowOrigin::Predicate(_) => write!(f, "Predicate(..)"),
+        }
+    }
+}
+
+/// Which request headers a preflight allows.
+#[derive(Debug, Clone)]
+enum AllowHeaders {
+    List(Vec<String>),
+    /// Allow whatever the browser asks for in `Access-Control-Request-Headers`.
+    Mirror,
+}
+
+/// A [`Layer`] which lets browsers on other origins call the router, by adding the
+/// Cross-Origin Resource Sharing headers to responses.
+///
+/// Preflight requests, which are `OPTIONS` requests with an `Access-Control-Request-Method`
+/// header, are answered by the layer with `204 No Content` and never reach a handler, so routes do
+/// not need an `OPTIONS` handler. Other requests from an allowed origin are passed on as usual and
+/// the response is given `Access-Control-Allow-Origin` and, if configured, the credentials and
+/// exposed headers.
+///
+/// A request from an origin which is not allowed is still served, but without the headers, so
+/// the browser refuses to give the response to the page. Whenever the response depends on the
+/// `Origin` header it gets `Vary: Origin`, so caches do not serve one origin's response to
+/// another.
+///
+/// Nothing is allowed until origins are configured. `GET`, `HEAD` and `POST` are allowed by
+/// default.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use cairo::{http::Method, middleware::CorsLayer, routing::get, Router};
+///
+/// let router = Router::new()
+///     .route("/api/users", get(|| "[]"))
+///     .layer(
+///         CorsLayer::new()
+///             .with_allowed_origins(["https://app.example.com"])
+///             .with_allowed_methods([Method::Get, Method::Post, Method::Delete])
+///             .with_allowed_headers(["Content-Type", "Authorization"])
+///             .with_credentials()
+///             .with_max_age(Duration::from_secs(600)),
+///     );
+/// ```
+#[derive(Debug, Clone)]
+pub struct CorsLayer {
+    origins: AllowOrigin,
+    methods: Vec<&'static str>,
+    headers: AllowHeaders,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl CorsLayer {
+    /// Create a `CorsLayer` which allows no origins yet.
+    pub fn new() -> Self {
+        Self {
+            origins: AllowOrigin::List(vec![]),
+            methods: vec!["GET", "HEAD", "POST"],
+            headers: AllowHeaders::List(vec![]),
+            exposed_headers: vec![],
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allow every origin, with `Access-Control-Allow-Origin: *`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if credentials are allowed, since that would let any site make requests with the
+    /// user's cookies and read the responses. List the trusted origins instead.
+    pub fn with_any_origin(mut self) -> Self {
+        assert!(
+            !self.credentials,
+            "Credentials cannot be allowed for any origin"
+        );
+        self.origins = AllowOrigin::Any;
+        self
+    }
+
+    /// Allow the given origins, such as `https://example.com`. Each is compared with the whole of
+    /// the request's `Origin`, ignoring ASCII case.
+    pub fn with_allowed_origins<I, T>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.origins = AllowOrigin::List(origins.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Allow the origins for which `predicate` returns `true`, such as every subdomain of a site.
+    pub fn with_origin_predicate<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.origins = AllowOrigin::Predicate(Arc::new(predicate));
+        self
+    }
+
+    /// Replace the methods which preflights allow.
+    pub fn with_allowed_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.methods = methods.into_iter().map(|method| method.as_str()).collect();
+        self
+    }
+
+    /// Set the request headers which preflights allow, beyond those browsers always allow.
+    pub fn with_allowed_headers<I, T>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.headers = AllowHeaders::List(headers.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Allow any request headers, by echoing those the preflight asks for.
+    pub fn with_any_header(mut self) -> Self {
+        self.headers = AllowHeaders::Mirror;
+        self
+    }
+
+    /// Set the response headers which scripts may read, beyond those browsers always expose.
+    pub fn with_exposed_headers<I, T>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.exposed_headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Allow requests with cookies and HTTP authentication, and let scripts read their responses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any origin is allowed, for the same reason as
+    /// [`with_any_origin`](Self::with_any_origin).
+    pub fn with_credentials(mut self) -> Self {
+        assert!(
+            !matches!(self.origins, AllowOrigin::Any),
+            "Credentials cannot be allowed for any origin"
+        );
+        self.credentials = true;
+        self
+    }
+
+    /// Set how long browsers may cache the result of a preflight.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    fn is_allowed(&self, origin: &str) -> bool {
+        match &self.origins {
+            AllowOrigin::Any => true,
+            AllowOrigin::List(origins) => origins
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(origin)),
+            AllowOrigin::Predicate(predicate) => predicate(origin),
+        }
+    }
+
+    /// Return `true` unless every origin gets the same `Access-Control-Allow-Origin: *`.
+    fn varies_by_origin(&self) -> bool {
+        !matches!(self.origins, AllowOrigin::Any)
+    }
+
+    /// Add the headers which both preflights and other requests from an allowed origin get.
+    fn allow_origin(&self, headers: &mut HeaderMap, origin: &str) {
+        if self.varies_by_origin() {
+            headers.insert(ALLOW_ORIGIN, origin);
+        } else {
+            headers.insert(ALLOW_ORIGIN, "*");
+        }
+        if self.credentials {
+            headers.insert(ALLOW_CREDENTIALS, "true");
+        }
+    }
+
+    fn preflight(&self, req: &Request, origin: &str) -> Response {
+        let mut headers = HeaderMap::new();
+        if self.varies_by_origin() {
+            add_vary(&mut headers, Origin::NAME);
+        }
+        add_vary(&mut headers, REQUEST_METHOD);
+        add_vary(&mut headers, REQUEST_HEADERS);
+
+        if self.is_allowed(origin) {
+            self.allow_origin(&mut headers, origin);
+            headers.insert(ALLOW_METHODS, self.methods.join(", "));
+            let allowed_headers = match &self.headers {
+                AllowHeaders::List(allowed) => allowed.join(", "),
+                AllowHeaders::Mirror => req
+                    .headers()
+                    .get_all(REQUEST_HEADERS)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+            if !allowed_headers.is_empty() {
+                headers.insert(ALLOW_HEADERS, allowed_headers);
+            }
+            if let Some(max_age) = self.max_age {
+                headers.insert(MAX_AGE, max_age.as_secs().to_string());
+            }
+        }
+        Response::new(204, headers, Body::empty())
+    }
+}
+
+impl Default for CorsLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layer for CorsLayer {
+    fn call(&self, req: Request, next: Next) -> Response {
+        let origin = req
+            .headers()
+            .typed_get::<Origin>()
+            .map(|Origin(origin)| origin);
+        let Some(origin) = origin else {
+            let mut response = next.run(req);
+            if self.varies_by_origin() {
+                add_vary(response.headers_mut(), Origin::NAME);
+            }
+            return response;
+        };
+
+        if *req.method() == Method::Options && req.headers().contains_key(REQUEST_METHOD) {
+            return self.preflight(&req, &origin);
+        }
+
+        let mut response = next.run(req);
+        if self.varies_by_origin() {
+            add_vary(response.headers_mut(), Origin::NAME);
+        }
+        if self.is_allowed(&origin) {
+            self.allow_origin(response.headers_mut(), &origin);
+            if !self.exposed_headers.is_empty() {
+                response
+                    .headers_mut()
+                    .insert(EXPOSE_HEADERS, self.exposed_headers.join(", "));
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler::BoxedHandler, middleware::run_layer};
+
+    fn call(layer: CorsLayer, method: Method, headers: &[(&str, &str)]) -> Response {
+        // Like a router without an `OPTIONS` route, so the layer must answer preflights itself.
+        let handler = if method == Method::Options {
+            BoxedHandler::from_handler(|| (404, "Not Found"))
+        } else {
+            BoxedHandler::from_handler(|| {
+                Response::new(200, HeaderMap::from([("Vary", "Accept")]), "Handler")
+            })
+        };
+        let headers = headers.iter().copied().collect::<HeaderMap>();
+        run_layer(
+            layer,
+            Request::with_headers(method, "/api", headers),
+            handler,
+        )
+    }
+
+    fn app_layer() -> CorsLayer {
+        CorsLayer::new()
+            .with_allowed_origins(["https://app.example.com"])
+            .with_allowed_methods([Method::Get, Method::Delete])
+            .with_allowed_headers(["Content-Type", "Authorization"])
+            .with_exposed_headers(["X-Total-Count"])
+            .with_credentials()
+            .with_max_age(Duration::from_secs(600))
+    }
+
+    #[test]
+    fn test_cors_preflight() {
+        let response = call(
+            app_layer(),
+            Method::Options,
+            &[
+                ("Origin", "https://app.example.com"),
+                ("Access-Control-Request-Method", "DELETE"),
+                ("Access-Control-Request-Headers", "authorization"),
+            ],
+        );
+        assert_eq!(response.status_code(), 204);
+        assert_eq!(response.text(), "");
+        let headers = response.headers();
+        assert_eq!(headers.get(ALLOW_ORIGIN), Some("https://app.example.com"));
+        assert_eq!(headers.get(ALLOW_CREDENTIALS), Some("true"));
+        assert_eq!(headers.get(ALLOW_METHODS), Some("GET, DELETE"));
+        assert_eq!(
+            headers.get(ALLOW_HEADERS),
+            Some("Content-Type, Authorization")
+        );
+        assert_eq!(headers.get(MAX_AGE), Some("600"));
+        assert_eq!(
+            headers.get("Vary"),
+            Some("Origin, Access-Control-Request-Method, Access-Control-Request-Headers")
+        );
+    }
+
+    #[test]
+    fn test_cors_preflight_disallowed_origin() {
+        let response = call(
+            app_layer(),
+            Method::Options,
+            &[
+                ("Origin", "https://evil.example.com"),
+                ("Access-Control-Request-Method", "DELETE"),
+            ],
+        );
+        assert_eq!(response.status_code(), 204);
+        assert_eq!(response.headers().get(ALLOW_ORIGIN), None);
+        assert_eq!(response.headers().get(ALLOW_METHODS), None);
+    }
+
+    #[test]
+    fn test_cors_options_without_request_method_is_not_preflight() {
+        let response = call(
+            app_layer(),
+            Method::Options,
+            &[("Origin", "https://app.example.com")],
+        );
+        assert_eq!(response.status_code(), 404);
+    }
+
+    #[test]
+    fn test_cors_actual_request() {
+        let response = call(
+            app_layer(),
+            Method::Get,
+            &[("Origin", "https://APP.example.com")],
+        );
+        assert_eq!(response.text(), "Handler");
+        let headers = response.headers();
+        assert_eq!(headers.get(ALLOW_ORIGIN), Some("https://APP.example.com"));
+        assert_eq!(headers.get(ALLOW_CREDENTIALS), Some("true"));
+        assert_eq!(headers.get(EXPOSE_HEADERS), Some("X-Total-Count"));
+        assert_eq!(headers.get("Vary"), Some("Accept, Origin"));
+
+        let response = call(
+            app_layer(),
+            Method::Get,
+            &[("Origin", "https://evil.example.com")],
+        );
+        assert_eq!(response.text(), "Handler");
+        assert_eq!(response.headers().get(ALLOW_ORIGIN), None);
+        assert_eq!(response.headers().get("Vary"), Some("Accept, Origin"));
+
+        // A cache may see this response first, so it must still vary on the origin.
+        let response = call(app_layer(), Method::Get, &[]);
+        assert_eq!(response.headers().get(ALLOW_ORIGIN), None);
+        assert_eq!(response.headers().get("Vary"), Some("Accept, Origin"));
+    }
+
+    #[test]
+    fn test_cors_any_origin() {
+        let layer = CorsLayer::new().with_any_origin().with_any_header();
+        let response = call(
+            layer.clone(),
+            Method::Get,
+            &[("Origin", "https://anywhere.example")],
+        );
+        assert_eq!(response.headers().get(ALLOW_ORIGIN), Some("*"));
+        assert_eq!(response.headers().get("Vary"), Some("Accept"));
+
+        let response = call(
+            layer.clone(),
+            Method::Options,
+            &[
+                ("Origin", "https://anywhere.example"),
+                ("Access-Control-Request-Method", "POST"),
+                ("Access-Control-Request-Headers", "x-custom, content-type"),
+            ],
+        );
+        assert_eq!(
+            response.headers().get(ALLOW_HEADERS),
+            Some("x-custom, content-type")
+        );
+        assert_eq!(response.headers().get(ALLOW_CREDENTIALS), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Credentials cannot be allowed for any origin")]
+    fn test_cors_any_origin_with_credentials() {
+        let _layer = CorsLayer::new().with_any_origin().with_credentials();
+    }
+
+    #[test]
+    #[should_panic(expected = "Credentials cannot be allowed for any origin")]
+    fn test_cors_credentials_with_any_origin() {
+        let _layer = CorsLayer::new().with_credentials().with_any_origin();
+    }
+
+    #[test]
+    fn test_cors_origin_predicate() {
+        let layer =
+            CorsLayer::new().with_origin_predicate(|origin| origin.ends_with(".example.com"));
+        let response = call(
+            layer.clone(),
+            Method::Get,
+            &[("Origin", "https://docs.example.com")],
+        );
+        assert_eq!(
+            response.headers().get(ALLOW_ORIGIN),
+            Some("https://docs.example.com")
+        );
+
+        let response = call(layer, Method::Get, &[("Origin", "https://example.org")]);
+        assert_eq!(response.headers().get(ALLOW_ORIGIN), None);
+    }
+}
@@ -0,0 +1,419 @@
+use std::fmt;
+
+use crate::{
+    cookie::{Cookie, CookieJar, SameSite},
+    core::{base64, fill_random, sha256::constant_time_eq},
+    extract::{read_body, ExtractError, FormData, FromRequest, FromRequestParts},
+    headers::ContentType,
+    http::{Method, Parts, Request, Response},
+    log::{log, Level},
+    middleware::{Layer, Next},
+    response::{IntoResponse, IntoResponseParts},
+};
+
+/// The number of random bytes in a token.
+const TOKEN_BYTES: usize = 32;
+
+/// The CSRF token for a request's client, set by a [`CsrfLayer`].
+///
+/// Pages which contain forms should include it in a hidden field named after the layer's form
+/// field, `csrf_token` by default. Scripts can send it in the `X-CSRF-Token` header instead.
+///
+/// ```
+/// use cairo::{middleware::CsrfToken, routing::get, Router};
+///
+/// fn form(token: CsrfToken) -> String {
+///     format!(
+///         r#"<form method="post"><input type="hidden" name="csrf_token" value="{}"></form>"#,
+///         token
+///     )
+/// }
+///
+/// let router = Router::new().route("/", get(form));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    /// Return the token.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromRequestParts for CsrfToken {
+    /// Take the token which the [`CsrfLayer`] added to the request extensions.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .extensions
+            .get::<CsrfToken>()
+            .cloned()
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl FromRequest for CsrfToken {
+    /// When a `CsrfToken` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// Generate a new random token.
+fn generate_token() -> std::io::Result<String> {
+    let mut bytes = [0; TOKEN_BYTES];
+    fill_random(&mut bytes)?;
+    Ok(base64::encode_url(&bytes))
+}
+
+/// Return `true` if `token` could have come from [`generate_token`].
+fn is_valid_token(token: &str) -> bool {
+    base64::decode_url(token).is_some_and(|bytes| bytes.len() == TOKEN_BYTES)
+}
+
+/// Return `true` for the methods which change state, and so need protecting.
+fn is_unsafe(method: &Method) -> bool {
+    matches!(
+        method,
+        Method::Post | Method::Put | Method::Patch | Method::Delete
+    )
+}
+
+/// A [`Layer`] which protects against cross-site request forgery, where another site makes a
+/// user's browser send a request which acts on their behalf.
+///
+/// It uses the double-submit cookie pattern. Each client is given a random token in a cookie,
+/// which handlers can extract as a [`CsrfToken`] to put in their forms. `POST`, `PUT`, `PATCH` and
+/// `DELETE` requests must send the same token back in the `X-CSRF-Token` header or, for
+/// URL-encoded forms, the `csrf_token` field. Another site can make the browser send the cookie,
+/// but cannot read it to send the token too.
+///
+/// Unsafe requests are also refused if the browser says they came from another site, through the
+/// `Sec-Fetch-Site` header, or if their `Origin` is neither the request's `Host` nor a trusted
+/// origin. Refused requests are answered with `403 Forbidden` and never reach a handler.
+///
+/// ```
+/// use cairo::{
+///     middleware::CsrfLayer,
+///     routing::{get, post},
+///     Router,
+/// };
+///
+/// let router = Router::new()
+///     .route("/", get(|| "Form"))
+///     .route("/submit", post(|| "Submitted"))
+///     .layer(CsrfLayer::new().with_secure(true));
+/// ```
+#[derive(Debug, Clone)]
+pub struct CsrfLayer {
+    cookie_name: String,
+    header_name: String,
+    form_field: String,
+    trusted_origins: Vec<String>,
+    secure: bool,
+}
+
+impl CsrfLayer {
+    /// Create a `CsrfLayer` with the default names.
+    pub fn new() -> Self {
+        Self {
+            cookie_name: "csrf_token".to_string(),
+            header_name: "X-CSRF-Token".to_string(),
+            form_field: "csrf_token".to_string(),
+            trusted_origins: vec![],
+            secure: false,
+        }
+    }
+
+    /// Set the name of the token cookie, which is `csrf_token` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is not a valid cookie name.
+    pub fn with_cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = Cookie::new(name, "").name().to_string();
+        self
+    }
+
+    /// Set the header which scripts send the token in, which is `X-CSRF-Token` by default.
+    pub fn with_header_name(mut self, name: impl Into<String>) -> Self {
+        self.header_name = name.into();
+        self
+    }
+
+    /// Set the form field which forms send the token in, which is `csrf_token` by default.
+    pub fn with_form_field(mut self, field: impl Into<String>) -> Self {
+        self.form_field = field.into();
+        self
+    }
+
+    /// Also accept requests from `origin`, such as `https://app.example.com`, for a front end
+    /// served from another host.
+    pub fn with_trusted_origin(mut self, origin: impl Into<String>) -> Self {
+        self.trusted_origins.push(origin.into());
+        self
+    }
+
+    /// Set whether the token cookie is only sent over HTTPS. This should be enabled whenever the
+    /// site is served over HTTPS.
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Create the token cookie. Handlers receive the token through the extractor, so scripts have
+    /// no need to read the cookie.
+    fn cookie(&self, token: &str) -> Cookie {
+        Cookie::new(self.cookie_name.clone(), token)
+            .with_path("/")
+            .with_http_only(true)
+            .with_secure(self.secure)
+            .with_same_site(SameSite::Strict)
+    }
+
+    /// Return `true` if the browser's headers say the request may have come from another site.
+    fn is_cross_origin(&self, req: &Request) -> bool {
+        let headers = req.headers();
+        let origin = headers.get("Origin");
+        if origin.is_some_and(|origin| self.trusted_origins.iter().any(|trusted| trusted == origin))
+        {
+            return false;
+        }
+        // `none` means the user started the request themselves, such as from a bookmark.
+        if headers
+            .get("Sec-Fetch-Site")
+            .is_some_and(|site| site != "same-origin" && site != "none")
+        {
+            return true;
+        }
+        match origin {
+            Some(origin) => match (origin.split_once("://"), headers.get("Host")) {
+                (Some((_, origin_host)), Some(host)) => !origin_host.eq_ignore_ascii_case(host),
+                // An opaque origin such as `null`, or no host to compare it with.
+                _ => true,
+            },
+            // Older browsers do not always send `Origin`, so the token has to be enough.
+            None => false,
+        }
+    }
+
+    /// Find the token the request submitted, in the header or else in a URL-encoded form body.
+    /// The body is put back for the handler after it has been read.
+    fn submitted_token(&self, req: &mut Request) -> Option<String> {
+        if let Some(token) = req.headers().get(&self.header_name) {
+            return Some(token.to_string());
+        }
+
+        let content_type: ContentType = req.headers().typed_get()?;
+        if content_type.mime_type() != "application/x-www-form-urlencoded" {
+            return None;
+        }
+        let bytes = read_body(req.take_body()).ok()?;
+        let token = std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|body| FormData::parse(body).ok())
+            .and_then(|form| form.get(&self.form_field).map(str::to_string));
+        req.set_body(bytes);
+        token
+    }
+}
+
+impl Default for CsrfLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layer for CsrfLayer {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let jar = CookieJar::from_headers(req.headers());
+        let existing = jar
+            .get(&self.cookie_name)
+            .map(Cookie::value)
+            .filter(|token| is_valid_token(token))
+            .map(str::to_string);
+
+        if is_unsafe(req.method()) {
+            let matches = match (&existing, self.submitted_token(&mut req)) {
+                (Some(expected), Some(submitted)) => {
+                    constant_time_eq(expected.as_bytes(), submitted.as_bytes())
+                }
+                _ => false,
+            };
+            if !matches || self.is_cross_origin(&req) {
+                log!(Level::Debug, "Refused a request which failed CSRF checks");
+                return (403, "Forbidden").into_response();
+            }
+        }
+
+        let (token, is_new) = match existing {
+            Some(token) => (token, false),
+            None => match generate_token() {
+                Ok(token) => (token, true),
+                Err(e) => {
+                    log!(Level::Error, "Failed to generate a CSRF token: {}", e);
+                    return next.run(req);
+                }
+            },
+        };
+        req.extensions_mut().insert(CsrfToken(token.clone()));
+
+        let mut response = next.run(req);
+        if is_new {
+            self.cookie(&token).into_response_parts(&mut response);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler::BoxedHandler, http::HeaderMap, middleware::run_layer};
+
+    fn call(layer: CsrfLayer, req: Request) -> Response {
+        let handler = BoxedHandler::from_handler(|token: CsrfToken, body: String| {
+            format!("{} {}", token, body)
+        });
+        run_layer(layer, req, handler)
+    }
+
+    fn token() -> String {
+        generate_token().unwrap()
+    }
+
+    fn request(method: Method, headers: &[(&str, &str)], body: &str) -> Request {
+        let headers = headers.iter().copied().collect::<HeaderMap>();
+        let mut req = Request::with_headers(method, "/", headers);
+        req.set_body(body.to_string());
+        req
+    }
+
+    #[test]
+    fn test_csrf_issues_token() {
+        let response = call(CsrfLayer::new(), request(Method::Get, &[], "page"));
+        assert_eq!(response.status_code(), 200);
+        let cookie: Cookie = response
+            .headers()
+            .get("Set-Cookie")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(cookie.name(), "csrf_token");
+        assert!(is_valid_token(cookie.value()));
+        assert!(cookie.http_only());
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(response.text(), format!("{} page", cookie.value()));
+
+        // A client which already has a token keeps it.
+        let cookie = format!("csrf_token={}", cookie.value());
+        let response = call(
+            CsrfLayer::new(),
+            request(Method::Get, &[("Cookie", &cookie)], "page"),
+        );
+        assert_eq!(response.headers().get("Set-Cookie"), None);
+    }
+
+    #[test]
+    fn test_csrf_accepts_matching_token() {
+        let token = token();
+        let cookie = format!("csrf_token={}", token);
+
+        let headers = [("Cookie", cookie.as_str()), ("X-CSRF-Token", &token)];
+        let response = call(CsrfLayer::new(), request(Method::Post, &headers, "data"));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), format!("{} data", token));
+
+        // The form body is still there for the handler after the layer has read it.
+        let body = format!("name=ferris&csrf_token={}", token);
+        let headers = [
+            ("Cookie", cookie.as_str()),
+            ("Content-Type", "application/x-www-form-urlencoded"),
+            ("Origin", "https://example.com"),
+            ("Host", "example.com"),
+            ("Sec-Fetch-Site", "same-origin"),
+        ];
+        let response = call(CsrfLayer::new(), request(Method::Delete, &headers, &body));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), format!("{} {}", token, body));
+    }
+
+    #[test]
+    fn test_csrf_rejects_missing_or_wrong_token() {
+        let token = token();
+        let cookie = format!("csrf_token={}", token);
+        let other = self::token();
+        for headers in [
+            vec![],
+            vec![("X-CSRF-Token", token.as_str())],
+            vec![("Cookie", cookie.as_str())],
+            vec![("Cookie", cookie.as_str()), ("X-CSRF-Token", &other)],
+            vec![("Cookie", "csrf_token=short"), ("X-CSRF-Token", "short")],
+        ] {
+            for method in [Method::Post, Method::Put, Method::Patch, Method::Delete] {
+                let response = call(CsrfLayer::new(), request(method, &headers, "data"));
+                assert_eq!(response.status_code(), 403);
+                assert_eq!(response.text(), "Forbidden");
+            }
+        }
+
+        // Safe methods are never checked.
+        for method in [Method::Get, Method::Head, Method::Options] {
+            let response = call(CsrfLayer::new(), request(method, &[], "data"));
+            assert_eq!(response.status_code(), 200);
+        }
+    }
+
+    #[test]
+    fn test_csrf_checks_origin() {
+        let token = token();
+        let cookie = format!("csrf_token={}", token);
+        let call_with = |layer: CsrfLayer, extra: &[(&str, &str)]| {
+            let mut headers = vec![
+                ("Cookie", cookie.as_str()),
+                ("X-CSRF-Token", token.as_str()),
+                ("Host", "example.com"),
+            ];
+            headers.extend_from_slice(extra);
+            call(layer, request(Method::Post, &headers, "data")).status_code()
+        };
+
+        assert_eq!(
+            call_with(CsrfLayer::new(), &[("Sec-Fetch-Site", "none")]),
+            200
+        );
+        assert_eq!(
+            call_with(CsrfLayer::new(), &[("Sec-Fetch-Site", "cross-site")]),
+            403
+        );
+        assert_eq!(
+            call_with(CsrfLayer::new(), &[("Sec-Fetch-Site", "same-site")]),
+            403
+        );
+        assert_eq!(
+            call_with(CsrfLayer::new(), &[("Origin", "https://evil.com")]),
+            403
+        );
+        assert_eq!(call_with(CsrfLayer::new(), &[("Origin", "null")]), 403);
+
+        let layer = CsrfLayer::new().with_trusted_origin("https://app.example.com");
+        let extra = [
+            ("Origin", "https://app.example.com"),
+            ("Sec-Fetch-Site", "same-site"),
+        ];
+        assert_eq!(call_with(layer, &extra), 200);
+    }
+
+    #[test]
+    fn test_csrf_token_without_layer() {
+        let req = Request::new(Method::Get, "/");
+        assert!(CsrfToken::from_request(req).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{
+    core::{deflate::Format, inflate::Decoder},
+    headers::{split_list, ContentEncoding, Header},
+    http::{Body, HeaderMap, Request, Response},
+    middleware::{Layer, Next},
+    response::IntoResponse,
+};
+
+/// The default limit on how many times larger than the compressed body the decompressed body may
+/// be. Text rarely compresses by more than 20 times, while a body of zeros compresses by 1000.
+const DEFAULT_MAX_RATIO: u64 = 100;
+
+/// A [`Layer`] which decompresses request bodies sent with `Content-Encoding: gzip` or `deflate`,
+/// so that extractors such as [`String`] and [`BodyStream`](crate::extract::BodyStream) see the
+/// original bytes.
+///
+/// The body is decompressed as it is read, and the `Content-Encoding` and `Content-Length` headers
+/// are removed since they describe the compressed body. A request in any other coding is answered
+/// with `415 Unsupported Media Type` and an `Accept-Encoding` header listing the codings which are
+/// understood.
+///
+/// To stop a small body from decompressing into gigabytes, reading fails once the decompressed
+/// body is more than the maximum ratio times larger than the compressed bytes read so far. The
+/// first megabyte is always allowed. Corrupt data fails to read in the same way, which extractors
+/// reject with `400 Bad Request`.
+///
+/// ```
+/// use cairo::{middleware::DecompressionLayer, routing::post, Router};
+///
+/// let router = Router::new()
+///     .route("/upload", post(|body: String| body))
+///     .layer(DecompressionLayer::new());
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecompressionLayer {
+    max_ratio: u64,
+}
+
+impl DecompressionLayer {
+    /// Create a `DecompressionLayer` which allows bodies to expand up to 100 times.
+    pub fn new() -> Self {
+        Self {
+            max_ratio: DEFAULT_MAX_RATIO,
+        }
+    }
+
+    /// Set how many times larger than the compressed body the decompressed body may be.
+    pub fn with_max_ratio(mut self, max_ratio: u64) -> Self {
+        self.max_ratio = max_ratio;
+        self
+    }
+}
+
+impl Default for DecompressionLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn unsupported_media_type() -> Response {
+    let headers = HeaderMap::from([("Accept-Encoding", "gzip, deflate")]);
+    (415, headers, "Unsupported Media Type").into_response()
+}
+
+impl Layer for DecompressionLayer {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let codings: Vec<String> = req
+            .headers()
+            .get_all(ContentEncoding::NAME)
+            .flat_map(split_list)
+            .map(str::to_ascii_lowercase)
+            .filter(|coding| coding != "identity")
+            .collect();
+
+        // Several codings would each need undoing in turn, which no client does in practice.
+        let format = match codings.as_slice() {
+            [] => return next.run(req),
+            [coding] if coding == "gzip" || coding == "x-gzip" => Format::Gzip,
+            [coding] if coding == "deflate" => Format::Zlib,
+            _ => return unsupported_media_type(),
+        };
+
+        req.headers_mut().remove(ContentEncoding::NAME);
+        req.headers_mut().remove("Content-Length");
+        let body = req.take_body().into_reader();
+        let decoder = Decoder::new(body, format).with_max_ratio(self.max_ratio);
+        req.set_body(Body::from_reader(decoder));
+        next.run(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::deflate::{compress, tests::sample_text},
+        handler::BoxedHandler,
+        http::Method,
+        middleware::run_layer,
+    };
+
+    fn call(layer: DecompressionLayer, content_encoding: &str, body: Vec<u8>) -> Response {
+        let handler = BoxedHandler::from_handler(|headers: HeaderMap, body: String| {
+            assert_eq!(headers.get("Content-Encoding"), None);
+            assert_eq!(headers.get("Content-Length"), None);
+            body
+        });
+        let len = body.len().to_string();
+        let headers = HeaderMap::from([
+            ("Content-Encoding", content_encoding),
+            ("Content-Length", len.as_str()),
+        ]);
+        let mut req = Request::with_headers(Method::Post, "/", headers);
+        req.set_body(body);
+        run_layer(layer, req, handler)
+    }
+
+    fn body_string(response: Response) -> String {
+        String::from_utf8(response.into_body().into_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_decompression() {
+        let text = sample_text(10_000);
+        let expected = String::from_utf8(text.clone()).unwrap();
+
+        let response = call(
+            DecompressionLayer::new(),
+            "gzip",
+            compress(Format::Gzip, &text),
+        );
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(body_string(response), expected);
+
+        let response = call(
+            DecompressionLayer::new(),
+            "identity, Deflate",
+            compress(Format::Zlib, &text),
+        );
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(body_string(response), expected);
+    }
+
+    #[test]
+    fn test_decompression_identity() {
+        let handler = BoxedHandler::from_handler(|body: String| body);
+        let mut req = Request::new(Method::Post, "/");
+        req.set_body("plain");
+        let response = run_layer(DecompressionLayer::new(), req, handler);
+        assert_eq!(body_string(response), "plain");
+    }
+
+    #[test]
+    fn test_decompression_unsupported() {
+        for coding in ["br", "gzip, gzip", "compress"] {
+            let response = call(DecompressionLayer::new(), coding, b"data".to_vec());
+            assert_eq!(response.status_code(), 415);
+            assert_eq!(
+                response.headers().get("Accept-Encoding"),
+                Some("gzip, deflate")
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompression_rejects_bad_bodies() {
+        let response = call(DecompressionLayer::new(), "gzip", b"not gzip".to_vec());
+        assert_eq!(response.status_code(), 400);
+
+        let bomb = compress(Format::Gzip, &vec![b'a'; 1536 * 1024]);
+        let response = call(DecompressionLayer::new(), "gzip", bomb.clone());
+        assert_eq!(response.status_code(), 400);
+
+        let response = call(DecompressionLayer::new().with_max_ratio(2000), "gzip", bomb);
+        assert_eq!(response.status_code(), 200);
+    }
+}
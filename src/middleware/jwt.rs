@@ -0,0 +1,497 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    core::{
+        base64,
+        sha256::{constant_time_eq, hmac_sha256},
+    },
+    extract::{ExtractError, FromJson, FromRequest, FromRequestParts, JsonValue},
+    headers::Authorization,
+    http::{HeaderMap, Parts, Request, Response},
+    middleware::{auth::quoted, Layer, Next},
+    response::IntoResponse,
+};
+
+/// How far apart our clock and the issuer's may be by default.
+const DEFAULT_LEEWAY: Duration = Duration::from_secs(60);
+
+/// Why a JSON Web Token was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwtError {
+    /// The request has no bearer token.
+    Missing,
+    /// The token is not three base64url segments, or its header or claims are not JSON objects
+    /// with claims of the right types.
+    Malformed,
+    /// The token is signed with an algorithm other than HS256.
+    UnsupportedAlgorithm,
+    /// The signature does not match the secret.
+    InvalidSignature,
+    /// The `exp` claim has passed.
+    Expired,
+    /// The `nbf` claim has not been reached.
+    NotYetValid,
+    /// The `iss` claim is missing or is not the expected issuer.
+    InvalidIssuer,
+    /// The `aud` claim is missing or does not include the expected audience.
+    InvalidAudience,
+    /// The claims cannot be parsed into the type a handler extracts with [`Claims`], or no
+    /// [`JwtLayer`] verified them.
+    InvalidClaims,
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::Missing => "Missing bearer token",
+            Self::Malformed => "Malformed token",
+            Self::UnsupportedAlgorithm => "Unsupported signing algorithm",
+            Self::InvalidSignature => "Invalid signature",
+            Self::Expired => "Token has expired",
+            Self::NotYetValid => "Token is not valid yet",
+            Self::InvalidIssuer => "Invalid issuer",
+            Self::InvalidAudience => "Invalid audience",
+            Self::InvalidClaims => "Invalid claims",
+        };
+        f.write_str(description)
+    }
+}
+
+impl std::error::Error for JwtError {}
+
+impl IntoResponse for JwtError {
+    /// Answer with `401 Unauthorized` and a `WWW-Authenticate` challenge saying what was wrong, as
+    /// RFC 6750 describes. A request without a token gets a challenge without an error.
+    fn into_response(self) -> Response {
+        let challenge = match self {
+            Self::Missing => "Bearer".to_string(),
+            _ => format!(
+                "Bearer error=\"invalid_token\", error_description={}",
+                quoted(&self.to_string())
+            ),
+        };
+        let headers = HeaderMap::from([
+            ("WWW-Authenticate", challenge),
+            ("Content-Type", "text/plain".to_string()),
+        ]);
+        Response::new(401, headers, self.to_string())
+    }
+}
+
+/// The verified claims of a request's JSON Web Token, parsed into `T`.
+///
+/// A [`JwtLayer`] must have verified the token first. If it has not, or the claims cannot be parsed
+/// into `T`, the request is rejected with [`JwtError::InvalidClaims`], which is a
+/// `401 Unauthorized` like the layer's own rejections.
+///
+/// ```
+/// use cairo::{
+///     extract::{ExtractError, FromJson, JsonValue},
+///     middleware::{Claims, JwtLayer},
+///     routing::get,
+///     Router,
+/// };
+///
+/// struct User {
+///     sub: String,
+/// }
+///
+/// impl FromJson for User {
+///     fn from_json(value: &JsonValue) -> Result<Self, ExtractError> {
+///         let sub = value.get("sub").and_then(JsonValue::as_str).ok_or(ExtractError::BadRequest)?;
+///         Ok(Self { sub: sub.to_string() })
+///     }
+/// }
+///
+/// fn whoami(Claims(user): Claims<User>) -> String {
+///     format!("Hello, {}", user.sub)
+/// }
+///
+/// let router = Router::new()
+///     .route("/whoami", get(whoami))
+///     .layer(JwtLayer::new("secret").with_issuer("auth.example.com"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims<T>(pub T);
+
+/// The claims stored in the request extensions by the [`JwtLayer`].
+#[derive(Debug, Clone)]
+struct VerifiedClaims(JsonValue);
+
+impl<T: FromJson> FromRequestParts for Claims<T> {
+    /// Parse the claims which the [`JwtLayer`] added to the request extensions.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        let VerifiedClaims(claims) = parts
+            .extensions
+            .get::<VerifiedClaims>()
+            .ok_or(ExtractError::BadRequest)?;
+        T::from_json(claims).map(Self)
+    }
+
+    fn rejection(_: ExtractError) -> Response {
+        JwtError::InvalidClaims.into_response()
+    }
+}
+
+impl<T: FromJson> FromRequest for Claims<T> {
+    /// When a `Claims` is requested as the last parameter, we pull it from the parts like normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+
+    fn rejection(_: ExtractError) -> Response {
+        JwtError::InvalidClaims.into_response()
+    }
+}
+
+/// Decode a base64url segment of a token into a JSON object.
+fn decode_object(segment: &str) -> Result<JsonValue, JwtError> {
+    let bytes = base64::decode_url(segment).ok_or(JwtError::Malformed)?;
+    let text = std::str::from_utf8(&bytes).map_err(|_| JwtError::Malformed)?;
+    match JsonValue::parse(text) {
+        Ok(object @ JsonValue::Object(_)) => Ok(object),
+        _ => Err(JwtError::Malformed),
+    }
+}
+
+/// Return the time claim `name` in seconds since the epoch, if the claims have it.
+fn time_claim(claims: &JsonValue, name: &str) -> Result<Option<f64>, JwtError> {
+    match claims.get(name) {
+        None => Ok(None),
+        Some(value) => value.as_f64().map(Some).ok_or(JwtError::Malformed),
+    }
+}
+
+/// A [`Layer`] which requires a JSON Web Token signed with HMAC-SHA256, as described in RFC 7519,
+/// in the `Authorization: Bearer` header.
+///
+/// The signature is checked against the shared secret, and then the time claims: the token is
+/// refused after its `exp` and before its `nbf`, give or take a leeway for clocks which disagree,
+/// 60 seconds by default. Tokens without these claims do not expire. If an issuer or audience is
+/// configured, the `iss` claim must match it and the `aud` claim must include it.
+///
+/// A token which fails any check is answered with `401 Unauthorized`, saying why in the
+/// `WWW-Authenticate` header and the body. The claims of a valid token are stored in the request
+/// extensions, where handlers can extract them with [`Claims`].
+#[derive(Clone)]
+pub struct JwtLayer {
+    secret: Vec<u8>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: Duration,
+}
+
+impl JwtLayer {
+    /// Create a `JwtLayer` which verifies tokens signed with `secret`.
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            issuer: None,
+            audience: None,
+            leeway: DEFAULT_LEEWAY,
+        }
+    }
+
+    /// Only accept tokens whose `iss` claim is `issuer`.
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Only accept tokens whose `aud` claim is or includes `audience`.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Allow for clocks which are up to `leeway` apart when checking `exp` and `nbf`.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Verify `token` and return its claims.
+    pub fn verify(&self, token: &str) -> Result<JsonValue, JwtError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.verify_at(token, now.as_secs_f64())
+    }
+
+    /// Verify `token` as if it were `now` seconds since the epoch.
+    fn verify_at(&self, token: &str, now: f64) -> Result<JsonValue, JwtError> {
+        let mut segments = token.split('.');
+        let (Some(header_segment), Some(payload), Some(signature), None) = (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) else {
+            return Err(JwtError::Malformed);
+        };
+
+        // The algorithm is fixed rather than taken on trust from the header, so a token cannot
+        // choose a weaker one such as `none`.
+        let header = decode_object(header_segment)?;
+        if header.get("alg").and_then(JsonValue::as_str) != Some("HS256") {
+            return Err(JwtError::UnsupportedAlgorithm);
+        }
+        let signature = base64::decode_url(signature).ok_or(JwtError::Malformed)?;
+        // The signature covers the encoded header and payload and the dot between them.
+        let signing_input = &token[..header_segment.len() + 1 + payload.len()];
+        if !constant_time_eq(
+            &hmac_sha256(&self.secret, signing_input.as_bytes()),
+            &signature,
+        ) {
+            return Err(JwtError::InvalidSignature);
+        }
+
+        let claims = decode_object(payload)?;
+        let leeway = self.leeway.as_secs_f64();
+        if time_claim(&claims, "exp")?.is_some_and(|exp| now >= exp + leeway) {
+            return Err(JwtError::Expired);
+        }
+        if time_claim(&claims, "nbf")?.is_some_and(|nbf| now + leeway < nbf) {
+            return Err(JwtError::NotYetValid);
+        }
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(JsonValue::as_str) != Some(issuer) {
+                return Err(JwtError::InvalidIssuer);
+            }
+        }
+        if let Some(audience) = &self.audience {
+            // The audience may be a single string or an array of them.
+            let matches = match claims.get("aud") {
+                Some(JsonValue::String(aud)) => aud == audience,
+                Some(JsonValue::Array(auds)) => {
+                    auds.iter().any(|aud| aud.as_str() == Some(audience))
+                }
+                _ => false,
+            };
+            if !matches {
+                return Err(JwtError::InvalidAudience);
+            }
+        }
+        Ok(claims)
+    }
+}
+
+impl fmt::Debug for JwtLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtLayer")
+            .field("issuer", &self.issuer)
+            .field("audience", &self.audience)
+            .field("leeway", &self.leeway)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Layer for JwtLayer {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let authorization = req.headers().typed_get::<Authorization>();
+        let result = authorization
+            .as_ref()
+            .and_then(|authorization| authorization.bearer_token())
+            .ok_or(JwtError::Missing)
+            .and_then(|token| self.verify(token));
+
+        match result {
+            Ok(claims) => {
+                req.extensions_mut().insert(VerifiedClaims(claims));
+                next.run(req)
+            }
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler::BoxedHandler, http::Method, middleware::run_layer};
+
+    const SECRET: &str = "your-256-bit-secret";
+
+    /// Sign `claims` with `secret`, as an issuer would.
+    fn sign(header: &str, claims: &str, secret: &str) -> String {
+        let signing_input = format!(
+            "{}.{}",
+            base64::encode_url(header.as_bytes()),
+            base64::encode_url(claims.as_bytes())
+        );
+        let signature = hmac_sha256(secret.as_bytes(), signing_input.as_bytes());
+        format!("{}.{}", signing_input, base64::encode_url(&signature))
+    }
+
+    fn token(claims: &str) -> String {
+        sign(r#"{"alg":"HS256","typ":"JWT"}"#, claims, SECRET)
+    }
+
+    #[test]
+    fn test_jwt_known_token() {
+        // The example token from jwt.io.
+        let token = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+            eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+            SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+        let claims = JwtLayer::new(SECRET).verify(token).unwrap();
+        assert_eq!(
+            claims.get("name").and_then(JsonValue::as_str),
+            Some("John Doe")
+        );
+        assert_eq!(
+            claims.get("iat").and_then(JsonValue::as_i64),
+            Some(1516239022)
+        );
+
+        assert_eq!(
+            JwtLayer::new("wrong").verify(token),
+            Err(JwtError::InvalidSignature)
+        );
+        let tampered = token.replacen("eyJzdWIiOiIxMjM0", "eyJzdWIiOiIxMjM1", 1);
+        assert_eq!(
+            JwtLayer::new(SECRET).verify(&tampered),
+            Err(JwtError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_jwt_malformed() {
+        let layer = JwtLayer::new(SECRET);
+        let valid = token("{}");
+        for malformed in [
+            "",
+            "abc",
+            "a.b",
+            "a.b.c.d",
+            &format!("{}.", valid),
+            &token("[]"),
+            &token("not json"),
+            &token(r#"{"exp":"tomorrow"}"#),
+        ] {
+            assert_eq!(
+                layer.verify(malformed),
+                Err(JwtError::Malformed),
+                "{}",
+                malformed
+            );
+        }
+
+        let none = sign(r#"{"alg":"none"}"#, "{}", SECRET);
+        assert_eq!(layer.verify(&none), Err(JwtError::UnsupportedAlgorithm));
+        let hs512 = sign(r#"{"alg":"HS512"}"#, "{}", SECRET);
+        assert_eq!(layer.verify(&hs512), Err(JwtError::UnsupportedAlgorithm));
+    }
+
+    #[test]
+    fn test_jwt_time_claims() {
+        let layer = JwtLayer::new(SECRET).with_leeway(Duration::from_secs(10));
+        let token = token(r#"{"nbf":1000,"exp":2000}"#);
+        assert!(layer.verify_at(&token, 1500.0).is_ok());
+        // The leeway applies on both sides.
+        assert!(layer.verify_at(&token, 991.0).is_ok());
+        assert!(layer.verify_at(&token, 2009.0).is_ok());
+        assert_eq!(layer.verify_at(&token, 989.0), Err(JwtError::NotYetValid));
+        assert_eq!(layer.verify_at(&token, 2010.0), Err(JwtError::Expired));
+    }
+
+    #[test]
+    fn test_jwt_issuer_and_audience() {
+        let layer = JwtLayer::new(SECRET)
+            .with_issuer("auth.example.com")
+            .with_audience("api");
+        let valid = token(r#"{"iss":"auth.example.com","aud":["web","api"]}"#);
+        assert!(layer.verify(&valid).is_ok());
+        let valid = token(r#"{"iss":"auth.example.com","aud":"api"}"#);
+        assert!(layer.verify(&valid).is_ok());
+
+        let wrong_issuer = token(r#"{"iss":"evil.example.com","aud":"api"}"#);
+        assert_eq!(layer.verify(&wrong_issuer), Err(JwtError::InvalidIssuer));
+        let wrong_audience = token(r#"{"iss":"auth.example.com","aud":["web"]}"#);
+        assert_eq!(
+            layer.verify(&wrong_audience),
+            Err(JwtError::InvalidAudience)
+        );
+        let no_audience = token(r#"{"iss":"auth.example.com"}"#);
+        assert_eq!(layer.verify(&no_audience), Err(JwtError::InvalidAudience));
+    }
+
+    fn call(headers: &[(&str, &str)]) -> Response {
+        let headers = headers.iter().copied().collect::<HeaderMap>();
+        let handler = BoxedHandler::from_handler(|Claims(claims): Claims<JsonValue>| {
+            claims
+                .get("sub")
+                .and_then(JsonValue::as_str)
+                .unwrap_or_default()
+                .to_string()
+        });
+        let req = Request::with_headers(Method::Get, "/", headers);
+        run_layer(JwtLayer::new(SECRET), req, handler)
+    }
+
+    #[test]
+    fn test_jwt_layer() {
+        let bearer = format!("Bearer {}", token(r#"{"sub":"ferris"}"#));
+        let response = call(&[("Authorization", &bearer)]);
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), "ferris");
+
+        let response = call(&[]);
+        assert_eq!(response.status_code(), 401);
+        assert_eq!(response.headers().get("WWW-Authenticate"), Some("Bearer"));
+
+        let expired = format!("Bearer {}", token(r#"{"sub":"ferris","exp":1}"#));
+        let response = call(&[("Authorization", &expired)]);
+        assert_eq!(response.status_code(), 401);
+        assert_eq!(
+            response.headers().get("WWW-Authenticate"),
+            Some(r#"Bearer error="invalid_token", error_description="Token has expired""#)
+        );
+        assert_eq!(response.text(), "Token has expired");
+    }
+
+    const INVALID_CLAIMS: &str =
+        r#"Bearer error="invalid_token", error_description="Invalid claims""#;
+
+    #[test]
+    fn test_claims_without_layer() {
+        let req = Request::new(Method::Get, "/");
+        assert!(Claims::<JsonValue>::from_request(req).is_err());
+
+        let handler = BoxedHandler::from_handler(|Claims(_): Claims<JsonValue>| "Hello");
+        let response = handler.call_handler(Request::new(Method::Get, "/"));
+        assert_eq!(response.status_code(), 401);
+        assert_eq!(
+            response.headers().get("WWW-Authenticate"),
+            Some(INVALID_CLAIMS)
+        );
+    }
+
+    #[test]
+    fn test_claims_of_wrong_shape() {
+        struct Subject(String);
+
+        impl FromJson for Subject {
+            fn from_json(value: &JsonValue) -> Result<Self, ExtractError> {
+                let sub = value.get("sub").and_then(JsonValue::as_str);
+                sub.map(|sub| Self(sub.to_string()))
+                    .ok_or(ExtractError::BadRequest)
+            }
+        }
+
+        let handler =
+            BoxedHandler::from_handler(|Claims(Subject(sub)): Claims<Subject>, _body: String| sub);
+        let bearer = format!("Bearer {}", token(r#"{"sub":42}"#));
+        let headers = HeaderMap::from([("Authorization", bearer.as_str())]);
+        let req = Request::with_headers(Method::Get, "/", headers);
+        let response = run_layer(JwtLayer::new(SECRET), req, handler);
+        assert_eq!(response.status_code(), 401);
+        assert_eq!(
+            response.headers().get("WWW-Authenticate"),
+            Some(INVALID_CLAIMS)
+        );
+        assert_eq!(response.text(), "Invalid claims");
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Instant,
+};
+
+use crate::{
+    core::PoolStats,
+    handler::Handler,
+    http::{HeaderMap, Request, Response},
+    middleware::{Layer, Next},
+    router::MatchedPath,
+};
+
+/// The upper bounds, in seconds, of the latency histogram buckets. These are the Prometheus
+/// client defaults.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The route label for requests which matched no route. Patterns always start with `/`, so this
+/// cannot be confused with one.
+const UNMATCHED: &str = "unmatched";
+
+/// The content type of the Prometheus text exposition format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The labels requests are grouped by.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    method: &'static str,
+    route: String,
+    /// The status code's class, such as `2xx`.
+    status: String,
+}
+
+/// The count and latency histogram of one group of requests.
+#[derive(Debug, Default)]
+struct Series {
+    count: u64,
+    /// How many requests fell in each bucket, not counting those in earlier buckets.
+    buckets: [u64; BUCKETS.len()],
+    sum_secs: f64,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    series: Mutex<BTreeMap<Key, Series>>,
+    in_flight: AtomicI64,
+}
+
+/// Decrements the in-flight gauge when dropped, so a handler which panics is not counted forever.
+struct InFlight<'a>(&'a AtomicI64);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Request metrics for a router, rendered in the Prometheus text exposition format.
+///
+/// The [`MetricsLayer`] from [`Metrics::layer`] counts every request, grouped by method, route
+/// pattern and status class, and records how long the handler took. Grouping by the pattern
+/// rather than the path keeps the number of series small: `/users/1` and `/users/2` are both
+/// counted under `/users/:id`. The [`MetricsHandler`] from [`Metrics::handler`] serves the
+/// metrics, along with the number of requests in flight and how busy the server's worker threads
+/// are.
+///
+/// | Metric | Type |
+/// |---|---|
+/// | `cairo_http_requests_total` | counter |
+/// | `cairo_http_request_duration_seconds` | histogram |
+/// | `cairo_http_requests_in_flight` | gauge |
+/// | `cairo_thread_pool_workers` | gauge |
+/// | `cairo_thread_pool_busy_workers` | gauge |
+/// | `cairo_thread_pool_queued_jobs` | gauge |
+///
+/// ```
+/// use cairo::{middleware::Metrics, routing::get, Router};
+///
+/// let metrics = Metrics::new();
+/// let router = Router::new()
+///     .route("/users/:id", get(|| "A user"))
+///     .route("/metrics", get(metrics.handler()))
+///     .layer(metrics.layer());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+}
+
+impl Metrics {
+    /// Create an empty set of metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a layer which records every request in these metrics.
+    pub fn layer(&self) -> MetricsLayer {
+        MetricsLayer {
+            registry: Arc::clone(&self.registry),
+        }
+    }
+
+    /// Return a handler which serves these metrics.
+    pub fn handler(&self) -> MetricsHandler {
+        MetricsHandler {
+            registry: Arc::clone(&self.registry),
+        }
+    }
+
+    /// Render the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        self.registry.render(None)
+    }
+}
+
+impl Registry {
+    fn record(&self, key: Key, secs: f64) {
+        let mut series = self.series.lock().unwrap_or_else(PoisonError::into_inner);
+        let series = series.entry(key).or_default();
+        series.count += 1;
+        series.sum_secs += secs;
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| secs <= bound) {
+            series.buckets[bucket] += 1;
+        }
+    }
+
+    fn render(&self, pool: Option<&PoolStats>) -> String {
+        let series = self.series.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut out = String::new();
+
+        out.push_str("# HELP cairo_http_requests_total The number of HTTP requests handled.\n");
+        out.push_str("# TYPE cairo_http_requests_total counter\n");
+        for (key, series) in series.iter() {
+            let _ = writeln!(
+                out,
+                "cairo_http_requests_total{{{}}} {}",
+                labels(key),
+                series.count
+            );
+        }
+
+        out.push_str(
+            "# HELP cairo_http_request_duration_seconds How long handlers took to respond.\n",
+        );
+        out.push_str("# TYPE cairo_http_request_duration_seconds histogram\n");
+        for (key, series) in series.iter() {
+            let labels = labels(key);
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(series.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "cairo_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "cairo_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, series.count
+            );
+            let _ = writeln!(
+                out,
+                "cairo_http_request_duration_seconds_sum{{{}}} {}",
+                labels, series.sum_secs
+            );
+            let _ = writeln!(
+                out,
+                "cairo_http_request_duration_seconds_count{{{}}} {}",
+                labels, series.count
+            );
+        }
+
+        gauge(
+            &mut out,
+            "cairo_http_requests_in_flight",
+            "The number of requests being handled.",
+            self.in_flight.load(Ordering::Relaxed),
+        );
+        if let Some(pool) = pool {
+            gauge(
+                &mut out,
+                "cairo_thread_pool_workers",
+                "The number of worker threads.",
+                pool.size,
+            );
+            gauge(
+                &mut out,
+                "cairo_thread_pool_busy_workers",
+                "The number of worker threads handling a connection.",
+                pool.busy.load(Ordering::Relaxed),
+            );
+            gauge(
+                &mut out,
+                "cairo_thread_pool_queued_jobs",
+                "The number of connections waiting for a worker thread.",
+                pool.queued.load(Ordering::Relaxed),
+            );
+        }
+        out
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    let _ = write!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n"
+    );
+}
+
+/// Format the labels of `key`, escaping their values as the exposition format requires.
+fn labels(key: &Key) -> String {
+    let escape = |value: &str| {
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    };
+    format!(
+        "method=\"{}\",route=\"{}\",status=\"{}\"",
+        key.method,
+        escape(&key.route),
+        key.status
+    )
+}
+
+/// A [`Layer`] which records requests in a set of [`Metrics`].
+#[derive(Debug, Clone)]
+pub struct MetricsLayer {
+    registry: Arc<Registry>,
+}
+
+impl Layer for MetricsLayer {
+    fn call(&self, req: Request, next: Next) -> Response {
+        let method = req.method().as_str();
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(UNMATCHED.to_string(), |MatchedPath(route)| route.clone());
+
+        let start = Instant::now();
+        self.registry.in_flight.fetch_add(1, Ordering::Relaxed);
+        let in_flight = InFlight(&self.registry.in_flight);
+        let response = next.run(req);
+        drop(in_flight);
+
+        let key = Key {
+            method,
+            route,
+            status: format!("{}xx", response.status_code() / 100),
+        };
+        self.registry.record(key, start.elapsed().as_secs_f64());
+        response
+    }
+}
+
+/// A handler which serves a set of [`Metrics`] for Prometheus to scrape.
+#[derive(Debug, Clone)]
+pub struct MetricsHandler {
+    registry: Arc<Registry>,
+}
+
+impl Handler<()> for MetricsHandler {
+    fn call_handler(&self, req: Request) -> Response {
+        // The server attaches its thread pool's statistics to each request it reads.
+        let pool = req.extensions().get::<Arc<PoolStats>>();
+        let body = self.registry.render(pool.map(Arc::as_ref));
+        Response::new(200, HeaderMap::from([("Content-Type", CONTENT_TYPE)]), body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{
+        http::Method,
+        routing::{get, post},
+        Router,
+    };
+
+    #[test]
+    fn test_metrics_records_requests() {
+        let metrics = Metrics::new();
+        let router = Router::new()
+            .route("/users/:id", get(|| "A user"))
+            .route("/users", post(|| (400, "Bad")))
+            .layer(metrics.layer());
+
+        router.call(Request::new(Method::Get, "/users/1"));
+        router.call(Request::new(Method::Get, "/users/2"));
+        router.call(Request::new(Method::Post, "/users"));
+        router.call(Request::new(Method::Get, "/missing"));
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "cairo_http_requests_total{method=\"GET\",route=\"/users/:id\",status=\"2xx\"} 2\n"
+        ));
+        assert!(text.contains(
+            "cairo_http_requests_total{method=\"POST\",route=\"/users\",status=\"4xx\"} 1\n"
+        ));
+        assert!(text.contains(
+            "cairo_http_requests_total{method=\"GET\",route=\"unmatched\",status=\"4xx\"} 1\n"
+        ));
+        assert!(text.contains(
+            "cairo_http_request_duration_seconds_bucket{method=\"GET\",route=\"/users/:id\",\
+             status=\"2xx\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(text.contains(
+            "cairo_http_request_duration_seconds_count{method=\"GET\",route=\"/users/:id\",\
+             status=\"2xx\"} 2\n"
+        ));
+        assert!(text.contains("cairo_http_requests_in_flight 0\n"));
+        assert!(!text.contains("cairo_thread_pool"));
+    }
+
+    #[test]
+    fn test_metrics_histogram_is_cumulative() {
+        let registry = Registry::default();
+        let key = Key {
+            method: "GET",
+            route: "/".to_string(),
+            status: "2xx".to_string(),
+        };
+        for secs in [0.001, 0.02, 0.02, 3.0, 60.0] {
+            registry.record(key.clone(), secs);
+        }
+
+        let text = registry.render(None);
+        let bucket = |le: &str| {
+            let prefix = format!(
+                "cairo_http_request_duration_seconds_bucket{{{},le=\"{}\"}} ",
+                labels(&key),
+                le
+            );
+            let line = text.lines().find(|line| line.starts_with(&prefix)).unwrap();
+            line[prefix.len()..].parse::<u64>().unwrap()
+        };
+        assert_eq!(bucket("0.005"), 1);
+        assert_eq!(bucket("0.01"), 1);
+        assert_eq!(bucket("0.025"), 3);
+        assert_eq!(bucket("5"), 4);
+        assert_eq!(bucket("10"), 4);
+        assert_eq!(bucket("+Inf"), 5);
+        let sum_prefix = format!(
+            "cairo_http_request_duration_seconds_sum{{{}}} ",
+            labels(&key)
+        );
+        let sum = text
+            .lines()
+            .find(|line| line.starts_with(&sum_prefix))
+            .unwrap();
+        let sum: f64 = sum[sum_prefix.len()..].parse().unwrap();
+        assert!((sum - 63.041).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_metrics_handler() {
+        let metrics = Metrics::new();
+        let router = Router::new()
+            .route("/metrics", get(metrics.handler()))
+            .layer(metrics.layer());
+
+        let mut req = Request::new(Method::Get, "/metrics");
+        let pool = PoolStats {
+            size: 4,
+            queued: AtomicUsize::new(2),
+            busy: AtomicUsize::new(3),
+        };
+        req.extensions_mut().insert(Arc::new(pool));
+
+        let response = router.call(req);
+        assert_eq!(response.headers().get("Content-Type"), Some(CONTENT_TYPE));
+        let text = response.text();
+        // The scrape itself is still being handled when the metrics are rendered.
+        assert!(text.contains("cairo_http_requests_in_flight 1\n"));
+        assert!(
+            text.contains("# TYPE cairo_thread_pool_workers gauge\ncairo_thread_pool_workers 4\n")
+        );
+        assert!(text.contains("cairo_thread_pool_busy_workers 3\n"));
+        assert!(text.contains("cairo_thread_pool_queued_jobs 2\n"));
+    }
+
+    #[test]
+    fn test_metrics_escapes_labels() {
+        let key = Key {
+            method: "GET",
+            route: "/a\"b\\c".to_string(),
+            status: "2xx".to_string(),
+        };
+        assert_eq!(
+            labels(&key),
+            "method=\"GET\",route=\"/a\\\"b\\\\c\",status=\"2xx\""
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    http::{HeaderMap, Parts, Request, Response},
+    middleware::{client_ip, Layer, Next},
+};
+
+/// The most keys a [`RateLimitLayer`] tracks at once unless told otherwise.
+const DEFAULT_MAX_KEYS: usize = 10_000;
+
+/// A function which picks the key for a request.
+type KeyFn = Arc<dyn Fn(&Parts) -> Option<String> + Send + Sync>;
+
+/// How a request is mapped to the bucket it draws from.
+#[derive(Clone)]
+enum KeyBy {
+    Ip,
+    Header(String),
+    Custom(KeyFn),
+}
+
+impl fmt::Debug for KeyBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyBy::Ip => write!(f, "Ip"),
+            KeyBy::Header(name) => f.debug_tuple("Header").field(name).finish(),
+            KeyBy::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// The tokens left for one key. Tokens are added back continuously rather than all at once, so
+/// a client cannot spend a whole period's allowance at the end of one period and again at the
+/// start of the next.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The outcome of taking a token, with what the `RateLimit-*` headers report.
+#[derive(Debug, PartialEq)]
+struct Decision {
+    allowed: bool,
+    remaining: u32,
+    /// Seconds until the bucket is full again.
+    reset: u64,
+    /// Seconds until a token is available, if there is none now.
+    retry_after: u64,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    map: HashMap<String, Bucket>,
+    last_sweep: Instant,
+}
+
+/// A [`Layer`] which limits how many requests each client can make, answering with
+/// `429 Too Many Requests` once a client has used up its allowance.
+///
+/// Each client has a bucket of `limit` tokens which refills at a steady rate of `limit` per
+/// `period`, and each request takes one token. This allows short bursts of up to `limit` requests
+/// while keeping the long-term rate to the limit. Clients are told where they stand with the
+/// `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers on every response, and
+/// with `Retry-After` when they are refused.
+///
+/// Clients are told apart by their IP address by default. Behind a reverse proxy every request
+/// comes from the proxy, so add a [`TrustedProxyLayer`](super::TrustedProxyLayer) after this one
+/// to find the client's address, or key by an API key instead. Requests without a key share one
+/// bucket.
+///
+/// Buckets are kept in memory, so each process counts separately. Buckets which have refilled are
+/// dropped once per period, and at most [`with_max_keys`](Self::with_max_keys) buckets are kept
+/// at once, so that clients sending many distinct keys cannot exhaust memory.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use cairo::{middleware::RateLimitLayer, routing::get, Router};
+///
+/// let router = Router::new()
+///     .route("/", get(|| "Hello, world!"))
+///     .layer(RateLimitLayer::new(100, Duration::from_secs(60)).by_header("X-Api-Key"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limit: u32,
+    period: Duration,
+    key_by: KeyBy,
+    max_keys: usize,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimitLayer {
+    /// Create a `RateLimitLayer` which allows each client `limit` requests per `period`.
+    pub fn new(limit: u32, period: Duration) -> Self {
+        assert!(limit > 0, "Rate limit must be greater than 0.");
+        assert!(!period.is_zero(), "Rate limit period must not be zero.");
+        Self {
+            limit,
+            period,
+            key_by: KeyBy::Ip,
+            max_keys: DEFAULT_MAX_KEYS,
+            buckets: Arc::new(Mutex::new(Buckets {
+                map: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+
+    /// Tell clients apart by their IP address. This is the default.
+    pub fn by_ip(mut self) -> Self {
+        self.key_by = KeyBy::Ip;
+        self
+    }
+
+    /// Tell clients apart by the value of a request header, such as an API key.
+    ///
+    /// The header is whatever the client sends, so a client can get a fresh allowance with every
+    /// new value. Only use this behind a trusted proxy which sets the header itself, for example
+    /// after checking the API key.
+    pub fn by_header(mut self, name: impl Into<String>) -> Self {
+        self.key_by = KeyBy::Header(name.into());
+        self
+    }
+
+    /// Tell clients apart by a key taken from the request, such as the user ID from an
+    /// extractor. Returning `None` puts the request in the shared bucket.
+    pub fn by_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Parts) -> Option<String> + Send + Sync + 'static,
+    {
+        self.key_by = KeyBy::Custom(Arc::new(key));
+        self
+    }
+
+    /// Track at most `max_keys` clients at once. When a new client arrives and there is no room,
+    /// the bucket closest to full is dropped, which loses the least. The default is 10,000.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `max_keys` is zero.
+    pub fn with_max_keys(mut self, max_keys: usize) -> Self {
+        assert!(max_keys > 0, "Rate limit max keys must be greater than 0.");
+        self.max_keys = max_keys;
+        self
+    }
+
+    fn key(&self, parts: &Parts) -> Option<String> {
+        match &self.key_by {
+            KeyBy::Ip => client_ip(&parts.extensions).map(|ip| ip.to_string()),
+            KeyBy::Header(name) => parts.headers.get(name).map(str::to_string),
+            KeyBy::Custom(key) => key(parts),
+        }
+    }
+
+    /// Take a token from the bucket for `key` at `now`.
+    fn take(&self, key: String, now: Instant) -> Decision {
+        let limit = f64::from(self.limit);
+        let per_sec = limit / self.period.as_secs_f64();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let is_new = !buckets.map.contains_key(&key);
+        if now.saturating_duration_since(buckets.last_sweep) >= self.period
+            || (is_new && buckets.map.len() >= self.max_keys)
+        {
+            // A bucket untouched for a whole period is full, which is the same as having none.
+            let period = self.period;
+            buckets
+                .map
+                .retain(|_, bucket| now.saturating_duration_since(bucket.updated) < period);
+            buckets.last_sweep = now;
+        }
+        if is_new && buckets.map.len() >= self.max_keys {
+            // Dropping the bucket with the most tokens loses the least, since a client without a
+            // bucket starts over with a full one.
+            let refilled = |bucket: &Bucket| {
+                let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+                (bucket.tokens + elapsed * per_sec).min(limit)
+            };
+            let fullest = buckets
+                .map
+                .iter()
+                .max_by(|(_, a), (_, b)| refilled(a).total_cmp(&refilled(b)))
+                .map(|(key, _)| key.clone());
+            if let Some(fullest) = fullest {
+                buckets.map.remove(&fullest);
+            }
+        }
+
+        let bucket = buckets.map.entry(key).or_insert(Bucket {
+            tokens: limit,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(limit);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Decision {
+            allowed,
+            remaining: bucket.tokens as u32,
+            reset: ((limit - bucket.tokens) / per_sec).ceil() as u64,
+            retry_after: if allowed {
+                0
+            } else {
+                ((1.0 - bucket.tokens) / per_sec).ceil() as u64
+            },
+        }
+    }
+}
+
+impl Layer for RateLimitLayer {
+    fn call(&self, req: Request, next: Next) -> Response {
+        let key = self.key(req.into_parts()).unwrap_or_default();
+        let decision = self.take(key, Instant::now());
+
+        let mut response = if decision.allowed {
+            next.run(req)
+        } else {
+            let headers = HeaderMap::from([("Retry-After", decision.retry_after.to_string())]);
+            Response::new(429, headers, "Too Many Requests")
+        };
+        let headers = response.headers_mut();
+        headers.insert("RateLimit-Limit", self.limit.to_string());
+        headers.insert("RateLimit-Remaining", decision.remaining.to_string());
+        headers.insert("RateLimit-Reset", decision.reset.to_string());
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract::ConnectInfo, handler::BoxedHandler, http::Method, middleware::run_layer};
+
+    fn request(ip: &str, headers: &[(&str, &str)]) -> Request {
+        let headers = headers.iter().copied().collect::<HeaderMap>();
+        let mut req = Request::with_headers(Method::Get, "/", headers);
+        req.extensions_mut().insert(ConnectInfo {
+            peer_addr: format!("{}:4000", ip).parse().unwrap(),
+            local_addr: "127.0.0.1:8080".parse().unwrap(),
+        });
+        req
+    }
+
+    fn run(layer: &RateLimitLayer, req: Request) -> Response {
+        run_layer(layer.clone(), req, BoxedHandler::from_handler(|| "Hello"))
+    }
+
+    #[test]
+    fn test_rate_limit_by_ip() {
+        let layer = RateLimitLayer::new(2, Duration::from_secs(60));
+
+        let response = run(&layer, request("192.0.2.1", &[]));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.headers().get("RateLimit-Limit"), Some("2"));
+        assert_eq!(response.headers().get("RateLimit-Remaining"), Some("1"));
+        assert_eq!(response.headers().get("RateLimit-Reset"), Some("30"));
+
+        let response = run(&layer, request("192.0.2.1", &[]));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.headers().get("RateLimit-Remaining"), Some("0"));
+
+        let response = run(&layer, request("192.0.2.1", &[]));
+        assert_eq!(response.status_code(), 429);
+        assert_eq!(response.text(), "Too Many Requests");
+        assert_eq!(response.headers().get("Retry-After"), Some("30"));
+        assert_eq!(response.headers().get("RateLimit-Remaining"), Some("0"));
+
+        // Another client has its own allowance.
+        let response = run(&layer, request("192.0.2.2", &[]));
+        assert_eq!(response.status_code(), 200);
+    }
+
+    #[test]
+    fn test_rate_limit_by_header_and_key() {
+        let layer = RateLimitLayer::new(1, Duration::from_secs(60)).by_header("X-Api-Key");
+        let req = || request("192.0.2.1", &[("X-Api-Key", "a")]);
+        assert_eq!(run(&layer, req()).status_code(), 200);
+        assert_eq!(run(&layer, req()).status_code(), 429);
+        let other = request("192.0.2.1", &[("X-Api-Key", "b")]);
+        assert_eq!(run(&layer, other).status_code(), 200);
+
+        let layer = RateLimitLayer::new(1, Duration::from_secs(60)).by_key(|parts| {
+            parts
+                .path_without_query()
+                .strip_prefix('/')
+                .map(str::to_string)
+        });
+        assert_eq!(run(&layer, request("192.0.2.1", &[])).status_code(), 200);
+        assert_eq!(run(&layer, request("192.0.2.2", &[])).status_code(), 429);
+    }
+
+    #[test]
+    fn test_rate_limit_refills() {
+        let layer = RateLimitLayer::new(10, Duration::from_secs(10));
+        let start = Instant::now();
+        for _ in 0..10 {
+            assert!(layer.take("a".to_string(), start).allowed);
+        }
+        let refused = layer.take("a".to_string(), start);
+        assert!(!refused.allowed);
+        assert_eq!(refused.retry_after, 1);
+        assert_eq!(refused.reset, 10);
+
+        // One token comes back each second, up to the limit.
+        let later = start + Duration::from_millis(2500);
+        let decision = layer.take("a".to_string(), later);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+
+        let much_later = later + Duration::from_secs(3600);
+        assert_eq!(layer.take("a".to_string(), much_later).remaining, 9);
+    }
+
+    #[test]
+    fn test_rate_limit_max_keys() {
+        let layer = RateLimitLayer::new(5, Duration::from_secs(60)).with_max_keys(2);
+        let start = Instant::now();
+        for _ in 0..5 {
+            layer.take("a".to_string(), start);
+        }
+        layer.take("b".to_string(), start);
+
+        // "b" has the most tokens left, so it makes room, and "a" is still limited.
+        layer.take("c".to_string(), start + Duration::from_secs(1));
+        let buckets = layer.buckets.lock().unwrap();
+        let mut keys: Vec<_> = buckets.map.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["a", "c"]);
+        drop(buckets);
+        assert!(
+            !layer
+                .take("a".to_string(), start + Duration::from_secs(2))
+                .allowed
+        );
+
+        // Many distinct keys never grow the map past the cap.
+        for i in 0..100 {
+            layer.take(i.to_string(), start + Duration::from_secs(3));
+        }
+        assert_eq!(layer.buckets.lock().unwrap().map.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Rate limit max keys must be greater than 0.")]
+    fn test_rate_limit_zero_max_keys() {
+        RateLimitLayer::new(5, Duration::from_secs(60)).with_max_keys(0);
+    }
+
+    #[test]
+    fn test_rate_limit_evicts_full_buckets() {
+        let layer = RateLimitLayer::new(5, Duration::from_secs(60));
+        let start = Instant::now();
+        layer.take("a".to_string(), start);
+        layer.take("b".to_string(), start + Duration::from_secs(30));
+        assert_eq!(layer.buckets.lock().unwrap().map.len(), 2);
+
+        // Only "a" has gone a whole period without a request.
+        layer.take("c".to_string(), start + Duration::from_secs(61));
+        let buckets = layer.buckets.lock().unwrap();
+        let mut keys: Vec<_> = buckets.map.keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["b", "c"]);
+    }
+}
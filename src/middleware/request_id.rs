@@ -0,0 +1,251 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    core::fill_random,
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    http::{is_valid_name, Parts, Request, Response},
+    middleware::{Layer, Next},
+};
+
+/// The header the ID is read from and echoed in by default.
+const DEFAULT_HEADER: &str = "X-Request-Id";
+
+/// The longest ID accepted from a client. Anything longer is replaced.
+const MAX_LEN: usize = 128;
+
+/// The ID of a request, taken from the client or generated by the [`RequestIdLayer`].
+///
+/// It can be extracted by handlers, and is included in the lines written by the
+/// [`AccessLogLayer`](super::AccessLogLayer).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Return the ID.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromRequestParts for RequestId {
+    /// Take the ID which the [`RequestIdLayer`] added to the request extensions.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .extensions
+            .get::<RequestId>()
+            .cloned()
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl FromRequest for RequestId {
+    /// When a `RequestId` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// Return a new ID which no other request to this process has had.
+///
+/// IDs are a random prefix chosen once per process followed by a shared counter, as 32 hex digits.
+/// The counter makes IDs unique across worker threads without a trip to the operating system for
+/// each request, and the prefix makes IDs from different processes differ.
+fn generate_id() -> RequestId {
+    static PREFIX: OnceLock<u64> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let prefix = *PREFIX.get_or_init(|| {
+        let mut bytes = [0; 8];
+        match fill_random(&mut bytes) {
+            Ok(()) => u64::from_le_bytes(bytes),
+            // Without randomness the time and process ID still separate most processes.
+            Err(_) => {
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_nanos() as u64)
+                    .unwrap_or_default();
+                nanos ^ u64::from(std::process::id()).rotate_left(32)
+            }
+        }
+    });
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    RequestId(format!("{:016x}{:016x}", prefix, count))
+}
+
+/// Return `true` if an ID sent by a client is safe to log and echo: not empty, not too long, and
+/// only letters, digits and the punctuation found in UUIDs and trace IDs, so it cannot break a
+/// log line or a header.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:+/=@".contains(&b))
+}
+
+/// A [`Layer`] which gives every request an ID, so that it can be followed through logs and
+/// across services.
+///
+/// The ID comes from the request's `X-Request-Id` header if it has a valid one, otherwise a new
+/// one is generated. It is stored in the request extensions, where handlers can extract it as a
+/// [`RequestId`], and set on the response's `X-Request-Id` header.
+///
+/// To have the [`AccessLogLayer`](super::AccessLogLayer) log the ID, add this layer after it, so
+/// that it runs first.
+///
+/// ```
+/// use cairo::{
+///     middleware::{AccessLogLayer, LogFormat, RequestId, RequestIdLayer},
+///     routing::get,
+///     Router,
+/// };
+///
+/// let router = Router::new()
+///     .route("/", get(|id: RequestId| format!("Request {}", id)))
+///     .layer(AccessLogLayer::new(LogFormat::Json))
+///     .layer(RequestIdLayer::new());
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequestIdLayer {
+    header: String,
+}
+
+impl RequestIdLayer {
+    /// Create a `RequestIdLayer` which uses the `X-Request-Id` header.
+    pub fn new() -> Self {
+        Self {
+            header: DEFAULT_HEADER.to_string(),
+        }
+    }
+
+    /// Use a different header, such as `X-Correlation-Id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `header` is not a valid header name.
+    pub fn with_header(mut self, header: impl Into<String>) -> Self {
+        let header = header.into();
+        assert!(
+            is_valid_name(&header),
+            "Invalid request ID header name: {:?}",
+            header
+        );
+        self.header = header;
+        self
+    }
+}
+
+impl Default for RequestIdLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layer for RequestIdLayer {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let id = match req.headers().get(&self.header) {
+            Some(id) if is_valid_id(id) => RequestId(id.to_string()),
+            _ => generate_id(),
+        };
+        req.headers_mut().insert(self.header.as_str(), id.as_str());
+        req.extensions_mut().insert(id.clone());
+
+        let mut response = next.run(req);
+        response.headers_mut().insert(self.header.as_str(), id.0);
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, thread};
+
+    use super::*;
+    use crate::{
+        handler::BoxedHandler,
+        http::{HeaderMap, Method},
+        middleware::run_layer,
+    };
+
+    fn call(layer: RequestIdLayer, headers: HeaderMap) -> Response {
+        let handler = BoxedHandler::from_handler(|id: RequestId| id.to_string());
+        let req = Request::with_headers(Method::Get, "/", headers);
+        run_layer(layer, req, handler)
+    }
+
+    #[test]
+    fn test_request_id_generated() {
+        let response = call(RequestIdLayer::new(), HeaderMap::new());
+        let id = response.headers().get("X-Request-Id").unwrap().to_string();
+        assert_eq!(id.len(), 32);
+        assert_eq!(response.text(), id);
+    }
+
+    #[test]
+    fn test_request_id_from_client() {
+        let headers = HeaderMap::from([("X-Request-Id", "abc-123")]);
+        let response = call(RequestIdLayer::new(), headers);
+        assert_eq!(response.headers().get("X-Request-Id"), Some("abc-123"));
+        assert_eq!(response.text(), "abc-123");
+
+        let headers = HeaderMap::from([("X-Correlation-Id", "trace-7")]);
+        let response = call(
+            RequestIdLayer::new().with_header("X-Correlation-Id"),
+            headers,
+        );
+        assert_eq!(response.headers().get("X-Correlation-Id"), Some("trace-7"));
+        assert_eq!(response.headers().get("X-Request-Id"), None);
+    }
+
+    #[test]
+    fn test_request_id_replaces_invalid() {
+        let long = "a".repeat(MAX_LEN + 1);
+        for invalid in ["", "has space", "quote\"d", long.as_str()] {
+            let headers = HeaderMap::from([("X-Request-Id", invalid)]);
+            let response = call(RequestIdLayer::new(), headers);
+            let id = response.headers().get("X-Request-Id").unwrap();
+            assert_ne!(id, invalid);
+            assert_eq!(id.len(), 32);
+        }
+    }
+
+    #[test]
+    fn test_request_id_unique_across_threads() {
+        let threads: Vec<_> = (0..8)
+            .map(|_| thread::spawn(|| (0..1000).map(|_| generate_id()).collect::<Vec<_>>()))
+            .collect();
+        let mut ids = HashSet::new();
+        for thread in threads {
+            for id in thread.join().unwrap() {
+                assert!(ids.insert(id));
+            }
+        }
+        assert_eq!(ids.len(), 8000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid request ID header name: \"X Request Id\"")]
+    fn test_request_id_invalid_header_name() {
+        RequestIdLayer::new().with_header("X Request Id");
+    }
+
+    #[test]
+    fn test_request_id_extractor_without_layer() {
+        let req = Request::new(Method::Get, "/");
+        assert!(RequestId::from_request(req).is_err());
+    }
+}
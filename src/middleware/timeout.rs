@@ -0,0 +1,342 @@
+use std::{
+    panic,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    http::{HeaderMap, Parts, Request, Response},
+    log::{log, Level},
+    middleware::{Layer, Next},
+};
+
+/// How many handlers may be running on their own threads at once by default, counting those which
+/// have already timed out.
+const DEFAULT_MAX_THREADS: usize = 64;
+
+/// When the handler for a request must finish, set by a [`TimeoutLayer`].
+///
+/// A handler which runs past its deadline keeps running, since a thread cannot be stopped from
+/// outside, but its response is thrown away. Long-running handlers can extract the `Deadline` and
+/// check [`is_expired`](Deadline::is_expired) to stop early.
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    at: Instant,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    /// Return how long is left before the deadline, or zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Return `true` once the deadline has passed or the layer has answered the request.
+    pub fn is_expired(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed) || Instant::now() >= self.at
+    }
+}
+
+impl FromRequestParts for Deadline {
+    /// Take the deadline which the [`TimeoutLayer`] added to the request extensions.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .extensions
+            .get::<Deadline>()
+            .cloned()
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl FromRequest for Deadline {
+    /// When a `Deadline` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// A [`Layer`] which answers with `503 Service Unavailable` if the rest of the chain takes longer
+/// than a time limit, so a slow handler cannot keep a client waiting forever.
+///
+/// The layers and handler after this one run on their own thread while the worker waits for
+/// them. When the time runs out the worker sends the error response, closes the connection and
+/// moves on to the next one. The handler's [`Deadline`] is marked as expired, and whatever it
+/// returns later is dropped instead of being written as a second response.
+///
+/// Handlers which ignore their deadline keep their threads until they return, so the number of
+/// threads is limited. Once [`with_max_threads`](TimeoutLayer::with_max_threads) are busy, further
+/// requests are answered with `503 Service Unavailable` straight away.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use cairo::{middleware::TimeoutLayer, routing::get, Router};
+///
+/// let router = Router::new()
+///     .route("/", get(|| "Hello, world!"))
+///     .layer(TimeoutLayer::new(Duration::from_secs(30)).with_status_code(504));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimeoutLayer {
+    duration: Duration,
+    status_code: u16,
+    max_threads: usize,
+    /// The number of threads running a handler for this layer, shared between its clones.
+    threads: Arc<AtomicUsize>,
+}
+
+/// A place among a [`TimeoutLayer`]'s threads, given back when the thread is done with it.
+struct ThreadSlot(Arc<AtomicUsize>);
+
+impl ThreadSlot {
+    /// Take a slot, unless `max` threads already have one.
+    fn acquire(threads: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        threads
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(threads)))
+    }
+}
+
+impl Drop for ThreadSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl TimeoutLayer {
+    /// Create a `TimeoutLayer` which allows each request `duration` to be answered.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            status_code: 503,
+            max_threads: DEFAULT_MAX_THREADS,
+            threads: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Answer timed out requests with `status_code` instead, which must be 503 or 504. A
+    /// `504 Gateway Timeout` suits a handler which is waiting on another service.
+    pub fn with_status_code(mut self, status_code: u16) -> Self {
+        assert!(
+            matches!(status_code, 503 | 504),
+            "Timeout status code must be 503 or 504."
+        );
+        self.status_code = status_code;
+        self
+    }
+
+    /// Limit how many handlers may run at once, which is 64 by default. A handler which has timed
+    /// out still counts until it returns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_threads` is zero.
+    pub fn with_max_threads(mut self, max_threads: usize) -> Self {
+        assert!(max_threads > 0, "A TimeoutLayer needs at least one thread.");
+        self.max_threads = max_threads;
+        self
+    }
+
+    fn timed_out(&self) -> Response {
+        error_response(self.status_code)
+    }
+}
+
+/// Build the response for a request the handler did not answer. It is given a `Content-Length`, so
+/// the client knows it is complete without waiting for the connection to close.
+fn error_response(status_code: u16) -> Response {
+    let reason = match status_code {
+        504 => "Gateway Timeout",
+        _ => "Service Unavailable",
+    };
+    let headers = HeaderMap::from([
+        ("Content-Type", "text/plain".to_string()),
+        ("Content-Length", reason.len().to_string()),
+    ]);
+    Response::new(status_code, headers, reason)
+}
+
+impl Layer for TimeoutLayer {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let timed_out = Arc::new(AtomicBool::new(false));
+        req.extensions_mut().insert(Deadline {
+            at: Instant::now() + self.duration,
+            timed_out: Arc::clone(&timed_out),
+        });
+
+        let Some(slot) = ThreadSlot::acquire(&self.threads, self.max_threads) else {
+            log!(Level::Warn, "Too many handlers running, refusing a request");
+            return error_response(503);
+        };
+
+        // The channel has room for the response, so a handler which finishes late does not block
+        // on sending it.
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let handle = thread::spawn(move || {
+            let _slot = slot;
+            let _ = sender.send(next.run(req));
+        });
+
+        match receiver.recv_timeout(self.duration) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                timed_out.store(true, Ordering::Relaxed);
+                log!(Level::Warn, "Request timed out after {:?}", self.duration);
+                self.timed_out()
+            }
+            // The thread ended without a response, so the handler panicked. Carry on unwinding
+            // here, as if it had run on this thread.
+            Err(RecvTimeoutError::Disconnected) => match handle.join() {
+                Err(payload) => panic::resume_unwind(payload),
+                Ok(()) => self.timed_out(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        extract::BodyStream, handler::BoxedHandler, http::Method, middleware::run_layer,
+        routing::post, Router,
+    };
+
+    fn call(layer: TimeoutLayer, handler: BoxedHandler) -> Response {
+        run_layer(layer, Request::new(Method::Get, "/"), handler)
+    }
+
+    #[test]
+    fn test_timeout_fast_handler() {
+        let layer = TimeoutLayer::new(Duration::from_secs(5));
+        let response = call(layer, BoxedHandler::from_handler(|| "Quick"));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.text(), "Quick");
+    }
+
+    #[test]
+    fn test_timeout_slow_handler() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = std::sync::Mutex::new(sender);
+        let handler = BoxedHandler::from_handler(move |deadline: Deadline| {
+            while !deadline.is_expired() {
+                thread::sleep(Duration::from_millis(5));
+            }
+            sender.lock().unwrap().send(deadline.remaining()).unwrap();
+            // Stay late, so the layer is sure to answer first even on a busy machine.
+            thread::sleep(Duration::from_millis(200));
+            "Too late"
+        });
+
+        let start = Instant::now();
+        let response = call(TimeoutLayer::new(Duration::from_millis(50)), handler);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(response.status_code(), 503);
+        assert_eq!(response.text(), "Service Unavailable");
+        assert_eq!(response.headers().get("Content-Length"), Some("19"));
+
+        // The handler saw its deadline expire, and its response went nowhere.
+        let remaining = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(remaining, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_timeout_gateway_timeout() {
+        let layer = TimeoutLayer::new(Duration::from_millis(10)).with_status_code(504);
+        let handler = BoxedHandler::from_handler(|| {
+            thread::sleep(Duration::from_millis(200));
+            "Too late"
+        });
+        let response = call(layer, handler);
+        assert_eq!(response.status_code(), 504);
+        assert_eq!(response.text(), "Gateway Timeout");
+    }
+
+    #[test]
+    fn test_timeout_response_arrives_before_slow_handler() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = Router::new()
+            .route(
+                "/",
+                post(|_body: BodyStream| {
+                    thread::sleep(Duration::from_secs(5));
+                    "Too late"
+                }),
+            )
+            .layer(TimeoutLayer::new(Duration::from_millis(200)));
+        thread::spawn(move || crate::serve(listener, router));
+
+        let start = Instant::now();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(3)))
+            .unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nHello")
+            .unwrap();
+
+        // The connection is closed after the response, even though the handler still holds the
+        // body, so reading to the end does not wait for the handler.
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(response.starts_with("HTTP/1.1 503 "));
+        assert!(response.contains("Content-Length: 19\r\n"));
+        assert!(response.ends_with("\r\n\r\nService Unavailable"));
+    }
+
+    #[test]
+    fn test_timeout_limits_threads() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let calls = Arc::clone(&calls);
+            BoxedHandler::from_handler(move || {
+                calls.fetch_add(1, Ordering::Relaxed);
+                thread::sleep(Duration::from_millis(300));
+                "Too late"
+            })
+        };
+        let layer = TimeoutLayer::new(Duration::from_millis(20)).with_max_threads(1);
+
+        // The first handler times out but keeps its thread, so the next request is refused
+        // without running the handler.
+        assert_eq!(call(layer.clone(), handler.clone()).status_code(), 503);
+        assert_eq!(call(layer.clone(), handler.clone()).status_code(), 503);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Once it returns, its thread is free again.
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(call(layer, handler).status_code(), 503);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timeout status code must be 503 or 504.")]
+    fn test_timeout_invalid_status_code() {
+        TimeoutLayer::new(Duration::from_secs(1)).with_status_code(408);
+    }
+
+    #[test]
+    #[should_panic(expected = "Handler failed")]
+    fn test_timeout_propagates_panics() {
+        let handler = BoxedHandler::from_handler(|| -> &'static str { panic!("Handler failed") });
+        call(TimeoutLayer::new(Duration::from_secs(5)), handler);
+    }
+}
@@ -0,0 +1,341 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+};
+
+use crate::{
+    extract::{ConnectInfo, ExtractError, FromRequest, FromRequestParts},
+    http::{Extensions, HeaderMap, Parts, Request, Response},
+    middleware::{Layer, Next},
+};
+
+/// The IP address of the client which made a request.
+///
+/// This is the connection's peer address unless a [`TrustedProxyLayer`] has found the client's
+/// address in the headers set by a trusted reverse proxy. The request is rejected if neither is
+/// known, which only happens when a request did not come through [`serve`](crate::serve).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientIp(pub IpAddr);
+
+impl fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Return the IP address of the client, as [`ClientIp`] finds it.
+pub(crate) fn client_ip(extensions: &Extensions) -> Option<IpAddr> {
+    match extensions.get::<ClientIp>() {
+        Some(ClientIp(ip)) => Some(*ip),
+        None => extensions
+            .get::<ConnectInfo>()
+            .map(|info| info.peer_addr.ip().to_canonical()),
+    }
+}
+
+impl FromRequestParts for ClientIp {
+    /// Take the address found by the [`TrustedProxyLayer`], or else the peer address.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        client_ip(&parts.extensions)
+            .map(Self)
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl FromRequest for ClientIp {
+    /// When a `ClientIp` is requested as the last parameter, we pull it from the parts like
+    /// normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// A range of IP addresses in CIDR notation, such as `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Parse a range such as `10.0.0.0/8` or `fd00::/8`. A lone address is a range of one.
+    fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+            None => (s.parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Self { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse a node from the `for` parameter of a `Forwarded` header or an entry of
+/// `X-Forwarded-For`, which may have a port, and brackets around an IPv6 address. Hidden nodes
+/// such as `unknown` or `_proxy1` have no address.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        let (addr, _) = rest.split_once(']')?;
+        return addr.parse::<Ipv6Addr>().ok().map(IpAddr::V6);
+    }
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .map(|ip| ip.to_canonical())
+}
+
+/// Return the `for` nodes of the `Forwarded` headers in the order the proxies added them.
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all("Forwarded")
+        .flat_map(|value| value.split(','))
+        .map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| parse_node(value))
+            })
+        })
+        .map(Option::flatten)
+        .collect()
+}
+
+/// Return the entries of the `X-Forwarded-For` headers in the order the proxies added them.
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all("X-Forwarded-For")
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect()
+}
+
+/// The header in which a [`TrustedProxyLayer`]'s proxies record the addresses they forward for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardedHeader {
+    /// The standard `Forwarded` header from RFC 7239, read from its `for` parameters.
+    Forwarded,
+    /// The `X-Forwarded-For` header, which most proxies add by default.
+    XForwardedFor,
+}
+
+/// A [`Layer`] which finds the IP address of the client behind a reverse proxy, for the
+/// [`ClientIp`] extractor and for the [`AccessLogLayer`](super::AccessLogLayer) and
+/// [`RateLimitLayer`](super::RateLimitLayer). Add it after those layers, so that it runs first.
+///
+/// Anyone can send a `Forwarded` or `X-Forwarded-For` header, so only the header chosen with
+/// [`ForwardedHeader`] is read, and only when the request comes from one of the trusted proxies.
+/// The addresses in it are read from the last added backwards, and the client is the first one
+/// which is not another trusted proxy. This way a client cannot pretend to be someone else by
+/// sending its own header, since the proxy adds the real address after it.
+///
+/// Choose the header your proxies set. The other one is never read, since a client could fill it
+/// with any address and the proxies would pass it along untouched.
+///
+/// ```
+/// use cairo::{
+///     middleware::{ClientIp, ForwardedHeader, TrustedProxyLayer},
+///     routing::get,
+///     Router,
+/// };
+///
+/// let router = Router::new()
+///     .route("/", get(|ip: ClientIp| format!("Hello, {}", ip)))
+///     .layer(
+///         TrustedProxyLayer::new(ForwardedHeader::XForwardedFor).with_trusted_proxy("10.0.0.0/8"),
+///     );
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrustedProxyLayer {
+    header: ForwardedHeader,
+    trusted: Vec<Cidr>,
+}
+
+impl TrustedProxyLayer {
+    /// Create a `TrustedProxyLayer` which reads `header`, and trusts no proxies yet.
+    pub fn new(header: ForwardedHeader) -> Self {
+        Self {
+            header,
+            trusted: vec![],
+        }
+    }
+
+    /// Trust the proxies in `cidr`, such as `10.0.0.0/8`, `fd00::/8` or a single address.
+    pub fn with_trusted_proxy(mut self, cidr: &str) -> Self {
+        let cidr = Cidr::parse(cidr)
+            .unwrap_or_else(|| panic!("Invalid trusted proxy address range: {}", cidr));
+        self.trusted.push(cidr);
+        self
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// Find the client's address given the address of the peer which sent the request.
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.is_trusted(peer) {
+            return peer;
+        }
+        let nodes = match self.header {
+            ForwardedHeader::Forwarded => forwarded_for(headers),
+            ForwardedHeader::XForwardedFor => x_forwarded_for(headers),
+        };
+
+        let mut client = peer;
+        for node in nodes.into_iter().rev() {
+            match node {
+                Some(ip) => {
+                    client = ip;
+                    if !self.is_trusted(ip) {
+                        break;
+                    }
+                }
+                // A trusted proxy forwarded a hidden or malformed address, so the last address we
+                // can vouch for is the closest we can get to the client.
+                None => break,
+            }
+        }
+        client
+    }
+}
+
+impl Layer for TrustedProxyLayer {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        if let Some(info) = req.extensions().get::<ConnectInfo>() {
+            let peer = info.peer_addr.ip().to_canonical();
+            let client = self.client_ip(peer, req.headers());
+            req.extensions_mut().insert(ClientIp(client));
+        }
+        next.run(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler::BoxedHandler, http::Method, middleware::run_layer};
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn call(layer: TrustedProxyLayer, peer: &str, headers: &[(&str, &str)]) -> String {
+        let headers = headers.iter().copied().collect::<HeaderMap>();
+        let mut req = Request::with_headers(Method::Get, "/", headers);
+        req.extensions_mut().insert(ConnectInfo {
+            peer_addr: SocketAddr::new(ip(peer), 4000),
+            local_addr: "127.0.0.1:8080".parse().unwrap(),
+        });
+        let handler = BoxedHandler::from_handler(|ip: ClientIp| ip.to_string());
+        run_layer(layer, req, handler).text()
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let cidr = Cidr::parse("10.0.0.0/8").unwrap();
+        assert!(cidr.contains(ip("10.1.2.3")));
+        assert!(cidr.contains(ip("::ffff:10.1.2.3")));
+        assert!(!cidr.contains(ip("11.0.0.1")));
+
+        let cidr = Cidr::parse("fd00::/8").unwrap();
+        assert!(cidr.contains(ip("fd12::1")));
+        assert!(!cidr.contains(ip("fe80::1")));
+
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(ip("192.0.2.1")));
+        assert!(Cidr::parse("192.0.2.1").unwrap().contains(ip("192.0.2.1")));
+        assert!(!Cidr::parse("192.0.2.1").unwrap().contains(ip("192.0.2.2")));
+
+        assert_eq!(Cidr::parse("10.0.0.0/33"), None);
+        assert_eq!(Cidr::parse("proxy"), None);
+    }
+
+    #[test]
+    fn test_trusted_proxy_x_forwarded_for() {
+        let layer = || {
+            TrustedProxyLayer::new(ForwardedHeader::XForwardedFor).with_trusted_proxy("10.0.0.0/8")
+        };
+        let forwarded = [("X-Forwarded-For", "198.51.100.1, 203.0.113.9, 10.0.0.2")];
+
+        // The client's own header is ignored, and so is the second trusted proxy.
+        assert_eq!(call(layer(), "10.0.0.1", &forwarded), "203.0.113.9");
+        // Headers from anyone else are not believed.
+        assert_eq!(call(layer(), "192.0.2.1", &forwarded), "192.0.2.1");
+        // Without a header the proxy is the client.
+        assert_eq!(call(layer(), "10.0.0.1", &[]), "10.0.0.1");
+        // A malformed entry stops the search at the last trusted address.
+        let garbled = [("X-Forwarded-For", "203.0.113.9, garbage")];
+        assert_eq!(call(layer(), "10.0.0.1", &garbled), "10.0.0.1");
+    }
+
+    #[test]
+    fn test_trusted_proxy_forwarded() {
+        let layer =
+            || TrustedProxyLayer::new(ForwardedHeader::Forwarded).with_trusted_proxy("10.0.0.0/8");
+        let forwarded = [
+            ("Forwarded", r#"for="[2001:db8::1]:4711";proto=https"#),
+            ("Forwarded", "for=10.0.0.5:80, For=10.0.0.4"),
+            ("X-Forwarded-For", "198.51.100.1"),
+        ];
+        assert_eq!(call(layer(), "10.0.0.1", &forwarded), "2001:db8::1");
+
+        let hidden = [("Forwarded", "for=_hidden, for=10.0.0.4")];
+        assert_eq!(call(layer(), "10.0.0.1", &hidden), "10.0.0.4");
+    }
+
+    #[test]
+    fn test_trusted_proxy_ignores_other_header() {
+        // The proxy only appends `X-Forwarded-For`, so a `Forwarded` header came from the client.
+        let layer =
+            TrustedProxyLayer::new(ForwardedHeader::XForwardedFor).with_trusted_proxy("10.0.0.0/8");
+        let spoofed = [
+            ("Forwarded", "for=1.2.3.4"),
+            ("X-Forwarded-For", "203.0.113.7"),
+        ];
+        assert_eq!(call(layer, "10.0.0.1", &spoofed), "203.0.113.7");
+
+        // And the other way around.
+        let layer =
+            TrustedProxyLayer::new(ForwardedHeader::Forwarded).with_trusted_proxy("10.0.0.0/8");
+        let spoofed = [("X-Forwarded-For", "1.2.3.4")];
+        assert_eq!(call(layer, "10.0.0.1", &spoofed), "10.0.0.1");
+    }
+
+    #[test]
+    fn test_client_ip_without_layer() {
+        let mut req = Request::new(Method::Get, "/");
+        assert!(ClientIp::from_request_parts(req.into_parts()).is_err());
+
+        req.extensions_mut().insert(ConnectInfo {
+            peer_addr: "[::ffff:192.0.2.1]:4000".parse().unwrap(),
+            local_addr: "127.0.0.1:8080".parse().unwrap(),
+        });
+        let ClientIp(client) = ClientIp::from_request(req).unwrap();
+        assert_eq!(client, ip("192.0.2.1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid trusted proxy address range: 10.0.0.0/40")]
+    fn test_trusted_proxy_invalid_range() {
+        TrustedProxyLayer::new(ForwardedHeader::Forwarded).with_trusted_proxy("10.0.0.0/40");
+    }
+}
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
     handler::{BoxedHandler, Handler},
     http::Method,
+    middleware::{Layer, Layered},
 };
 
 /// A function called by [`add_http_function`] to start a chain of method/handler pairs by creating
@@ -10,7 +11,7 @@ use crate::{
 fn on<H, T>(method: Method, handler: H) -> PathRouter
 where
     H: Handler<T> + Send + Sync + 'static,
-    T: Send + Sync + 'static,
+    T: 'static,
 {
     PathRouter::new().on(method, handler)
 }
@@ -24,7 +25,7 @@ macro_rules! add_http_function {
         pub fn $name<H, T>(handler: H) -> PathRouter
         where
             H: Handler<T> + Send + Sync + 'static,
-            T: Send + Sync + 'static,
+            T: 'static,
         {
             on(Method::$method, handler)
         }
@@ -39,7 +40,7 @@ macro_rules! add_http_method {
         pub fn $name<H, T>(self, handler: H) -> Self
         where
             H: Handler<T> + Send + Sync + 'static,
-            T: Send + Sync + 'static,
+            T: 'static,
         {
             self.on(Method::$method, handler)
         }
@@ -80,11 +81,19 @@ impl PathRouter {
     add_http_method!(delete, Delete);
     add_http_method!(patch, Patch);
 
+    /// Wrap the handler for every method in `layer`.
+    pub(crate) fn wrap(&mut self, layer: &Arc<dyn Layer>) {
+        for handler in self.routes.values_mut() {
+            let layered = Layered::new(Arc::clone(layer), handler.clone());
+            *handler = BoxedHandler::from_handler(layered);
+        }
+    }
+
     /// A private method which is called from [`chained_handler`] to register a [`Handler`].
     fn on<H, T>(mut self, method: Method, handler: H) -> Self
     where
         H: Handler<T> + Send + Sync + 'static,
-        T: Send + Sync + 'static,
+        T: 'static,
     {
         self.routes
             .insert(method, BoxedHandler::from_handler(handler));
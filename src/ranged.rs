@@ -0,0 +1,404 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+};
+
+use crate::{
+    core::fill_random,
+    headers::{
+        AcceptRanges, ContentRange, ContentType, ETag, EntityTag, IfRange, LastModified, Range,
+    },
+    http::{Body, HeaderMap, HttpDate, Response},
+    response::IntoResponse,
+};
+
+/// The most ranges we will answer in one response. A request for more is sent the whole
+/// representation instead, since a long list of tiny ranges is a cheap way to make a server do a
+/// lot of work.
+const MAX_RANGES: usize = 32;
+
+/// A response of seekable content which honours the `Range` and `If-Range` headers of the request,
+/// so clients can resume downloads and seek through media.
+///
+/// With no `Range`, or an `If-Range` which no longer matches the validators, the whole content is
+/// sent with a 200. A single range is sent as a 206 with a `Content-Range`, and several ranges as a
+/// 206 `multipart/byteranges` body. If none of the ranges overlap the content, the response is a
+/// 416. The content is always read from the start of `reader`, whatever its current position.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use cairo::{http::HeaderMap, response::Ranged, routing::get, Router};
+///
+/// const REPORT: &[u8] = b"id,total\n1,10\n2,20\n";
+///
+/// fn report(headers: HeaderMap) -> Ranged<Cursor<&'static [u8]>> {
+///     Ranged::new(&headers, Cursor::new(REPORT), REPORT.len() as u64)
+///         .with_content_type("text/csv; charset=utf-8")
+/// }
+///
+/// let router = Router::new().route("/report.csv", get(report));
+/// ```
+pub struct Ranged<R> {
+    reader: R,
+    len: u64,
+    range: Option<Range>,
+    if_range: Option<IfRange>,
+    content_type: String,
+    etag: Option<EntityTag>,
+    last_modified: Option<HttpDate>,
+}
+
+impl<R: Read + Seek + Send + 'static> Ranged<R> {
+    /// Answer a request with the given headers using the `len` bytes of `reader`.
+    pub fn new(request_headers: &HeaderMap, reader: R, len: u64) -> Self {
+        Self {
+            reader,
+            len,
+            range: request_headers.typed_get(),
+            if_range: request_headers.typed_get(),
+            content_type: "application/octet-stream".to_string(),
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// Set the `Content-Type` of the content, which defaults to `application/octet-stream`.
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    /// Set the `ETag` of the content. An `If-Range` with the same strong tag lets the range
+    /// through.
+    pub fn with_etag(mut self, etag: EntityTag) -> Self {
+        self.etag = Some(etag);
+        self
+    }
+
+    /// Set the `Last-Modified` date of the content. An `If-Range` with exactly this date lets the
+    /// range through.
+    pub fn with_last_modified(mut self, last_modified: HttpDate) -> Self {
+        self.last_modified = Some(last_modified);
+        self
+    }
+
+    /// Return the sorted, merged bounds of the ranges to send, or `None` if the whole content
+    /// should be sent instead. An empty list means none of the ranges could be satisfied.
+    fn selected_ranges(&self) -> Option<Vec<(u64, u64)>> {
+        let range = self.range.as_ref()?;
+        if let Some(if_range) = &self.if_range {
+            if !if_range.matches(self.etag.as_ref(), self.last_modified) {
+                return None;
+            }
+        }
+        if range.0.len() > MAX_RANGES {
+            return None;
+        }
+
+        let mut bounds: Vec<(u64, u64)> = range
+            .0
+            .iter()
+            .filter_map(|range| range.to_bounds(self.len))
+            .collect();
+        bounds.sort_unstable();
+
+        // Overlapping and adjacent ranges are sent as one, so no byte is sent twice.
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(bounds.len());
+        for (first, last) in bounds {
+            match merged.last_mut() {
+                Some(previous) if first <= previous.1.saturating_add(1) => {
+                    previous.1 = previous.1.max(last);
+                }
+                _ => merged.push((first, last)),
+            }
+        }
+        Some(merged)
+    }
+}
+
+impl<R: Read + Seek + Send + 'static> IntoResponse for Ranged<R> {
+    fn into_response(self) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.typed_insert(AcceptRanges("bytes".to_string()));
+        if let Some(etag) = &self.etag {
+            headers.typed_insert(ETag(etag.clone()));
+        }
+        if let Some(last_modified) = self.last_modified {
+            headers.typed_insert(LastModified(last_modified));
+        }
+
+        let ranges = self.selected_ranges();
+        match ranges.as_deref() {
+            Some([]) => {
+                headers.typed_insert(ContentRange {
+                    range: None,
+                    complete_length: self.len,
+                });
+                Response::new(416, headers, Body::empty())
+            }
+            Some(&[(first, last)]) => {
+                headers.typed_insert(ContentType(self.content_type));
+                headers.typed_insert(ContentRange {
+                    range: Some((first, last)),
+                    complete_length: self.len,
+                });
+                let len = last - first + 1;
+                let reader = RangeReader::new(self.reader, vec![Part::new(vec![], first, len)]);
+                Response::new(206, headers, Body::sized_reader(reader, len))
+            }
+            Some(ranges) => match multipart_parts(ranges, &self.content_type, self.len) {
+                Some((boundary, parts)) => {
+                    let content_type = format!("multipart/byteranges; boundary={}", boundary);
+                    headers.typed_insert(ContentType(content_type));
+                    let len = parts
+                        .iter()
+                        .map(|part| part.head.len() as u64 + part.len)
+                        .sum();
+                    let reader = RangeReader::new(self.reader, parts);
+                    Response::new(206, headers, Body::sized_reader(reader, len))
+                }
+                None => whole(self.reader, self.len, self.content_type, headers),
+            },
+            None => whole(self.reader, self.len, self.content_type, headers),
+        }
+    }
+}
+
+/// Send all of the content with a 200.
+fn whole<R>(reader: R, len: u64, content_type: String, mut headers: HeaderMap) -> Response
+where
+    R: Read + Seek + Send + 'static,
+{
+    headers.typed_insert(ContentType(content_type));
+    let reader = RangeReader::new(reader, vec![Part::new(vec![], 0, len)]);
+    Response::new(200, headers, Body::sized_reader(reader, len))
+}
+
+/// Split the content into the parts of a `multipart/byteranges` body, returning the boundary along
+/// with them. Returns `None` if a random boundary could not be generated.
+fn multipart_parts(
+    ranges: &[(u64, u64)],
+    content_type: &str,
+    complete_length: u64,
+) -> Option<(String, Vec<Part>)> {
+    let mut random = [0; 16];
+    fill_random(&mut random).ok()?;
+    let boundary: String = random.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let mut parts: Vec<Part> = ranges
+        .iter()
+        .map(|&(first, last)| {
+            let head = format!(
+                "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                boundary, content_type, first, last, complete_length
+            );
+            Part::new(head.into_bytes(), first, last - first + 1)
+        })
+        .collect();
+    let trailer = format!("\r\n--{}--\r\n", boundary);
+    parts.push(Part::new(trailer.into_bytes(), 0, 0));
+    Some((boundary, parts))
+}
+
+/// One piece of a ranged body: some bytes to send as they are, followed by `len` bytes of the
+/// content starting at `start`.
+struct Part {
+    head: Vec<u8>,
+    start: u64,
+    len: u64,
+}
+
+impl Part {
+    fn new(head: Vec<u8>, start: u64, len: u64) -> Self {
+        Self { head, start, len }
+    }
+}
+
+/// A reader which sends each [`Part`] in turn, seeking the content only when it gets to a part.
+struct RangeReader<R> {
+    reader: R,
+    parts: VecDeque<Part>,
+    /// The rest of the head of the current part.
+    head: Cursor<Vec<u8>>,
+    /// How many bytes of the content are left in the current part.
+    remaining: u64,
+}
+
+impl<R> RangeReader<R> {
+    fn new(reader: R, parts: Vec<Part>) -> Self {
+        Self {
+            reader,
+            parts: parts.into(),
+            head: Cursor::new(vec![]),
+            remaining: 0,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for RangeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let num_bytes = self.head.read(buf)?;
+            if num_bytes > 0 {
+                return Ok(num_bytes);
+            }
+
+            if self.remaining > 0 {
+                let max = usize::try_from(self.remaining).map_or(buf.len(), |n| n.min(buf.len()));
+                let num_bytes = self.reader.read(&mut buf[..max])?;
+                if num_bytes == 0 {
+                    // The content was shorter than we were told, so the length we sent is wrong.
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                self.remaining -= num_bytes as u64;
+                return Ok(num_bytes);
+            }
+
+            let Some(part) = self.parts.pop_front() else {
+                return Ok(0);
+            };
+            if part.len > 0 {
+                self.reader.seek(SeekFrom::Start(part.start))?;
+            }
+            self.head = Cursor::new(part.head);
+            self.remaining = part.len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &[u8] = b"0123456789abcdefghij";
+
+    fn ranged(headers: &[(&str, &str)]) -> Response {
+        let headers: HeaderMap = headers.iter().copied().collect();
+        let mut reader = Cursor::new(CONTENT);
+        // The content is read from the start, whatever the position of the reader.
+        reader.seek(SeekFrom::End(0)).unwrap();
+        Ranged::new(&headers, reader, CONTENT.len() as u64)
+            .with_content_type("text/plain")
+            .with_etag(EntityTag::strong("v1"))
+            .with_last_modified(HttpDate::from_unix_secs(784_111_777))
+            .into_response()
+    }
+
+    fn body(response: Response) -> Vec<u8> {
+        let expected_len = response.body().len();
+        let bytes = response.into_body().into_bytes().unwrap();
+        assert_eq!(Some(bytes.len() as u64), expected_len);
+        bytes
+    }
+
+    #[test]
+    fn test_ranged_without_range() {
+        let response = ranged(&[]);
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.headers().get("Accept-Ranges"), Some("bytes"));
+        assert_eq!(response.headers().get("Content-Type"), Some("text/plain"));
+        assert_eq!(response.headers().get("ETag"), Some("\"v1\""));
+        assert_eq!(body(response), CONTENT);
+
+        // A malformed or unknown range is ignored.
+        assert_eq!(ranged(&[("Range", "bytes=9-2")]).status_code(), 200);
+        assert_eq!(ranged(&[("Range", "lines=1-2")]).status_code(), 200);
+    }
+
+    #[test]
+    fn test_ranged_single_range() {
+        let response = ranged(&[("Range", "bytes=5-9")]);
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(
+            response.headers().get("Content-Range"),
+            Some("bytes 5-9/20")
+        );
+        assert_eq!(response.headers().get("Content-Type"), Some("text/plain"));
+        assert_eq!(body(response), b"56789");
+
+        let response = ranged(&[("Range", "bytes=-3")]);
+        assert_eq!(
+            response.headers().get("Content-Range"),
+            Some("bytes 17-19/20")
+        );
+        assert_eq!(body(response), b"hij");
+
+        let response = ranged(&[("Range", "bytes=15-100")]);
+        assert_eq!(
+            response.headers().get("Content-Range"),
+            Some("bytes 15-19/20")
+        );
+        assert_eq!(body(response), b"fghij");
+
+        // Overlapping ranges are merged into one.
+        let response = ranged(&[("Range", "bytes=4-6, 0-2, 2-3")]);
+        assert_eq!(
+            response.headers().get("Content-Range"),
+            Some("bytes 0-6/20")
+        );
+        assert_eq!(body(response), b"0123456");
+    }
+
+    #[test]
+    fn test_ranged_multiple_ranges() {
+        let response = ranged(&[("Range", "bytes=10-11, 0-1")]);
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(response.headers().get("Content-Range"), None);
+        let content_type = response.headers().get("Content-Type").unwrap().to_string();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap();
+        assert_eq!(boundary.len(), 32);
+
+        let expected = format!(
+            "\r\n--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/20\r\n\r\n01\
+             \r\n--{0}\r\nContent-Type: text/plain\r\nContent-Range: bytes 10-11/20\r\n\r\nab\
+             \r\n--{0}--\r\n",
+            boundary
+        );
+        assert_eq!(String::from_utf8(body(response)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_ranged_unsatisfiable() {
+        let response = ranged(&[("Range", "bytes=20-30, -0")]);
+        assert_eq!(response.status_code(), 416);
+        assert_eq!(response.headers().get("Content-Range"), Some("bytes */20"));
+        assert!(body(response).is_empty());
+    }
+
+    #[test]
+    fn test_ranged_too_many_ranges() {
+        let ranges: Vec<String> = (0..=MAX_RANGES).map(|i| format!("{0}-{0}", i)).collect();
+        let range = format!("bytes={}", ranges.join(","));
+        assert_eq!(ranged(&[("Range", &range)]).status_code(), 200);
+    }
+
+    #[test]
+    fn test_ranged_if_range() {
+        let date = "Sun, 06 Nov 1994 08:49:37 GMT";
+        for (if_range, expected_status) in [
+            ("\"v1\"", 206),
+            ("\"v0\"", 200),
+            ("W/\"v1\"", 200),
+            (date, 206),
+            ("Mon, 07 Nov 1994 08:49:37 GMT", 200),
+        ] {
+            let response = ranged(&[("Range", "bytes=0-1"), ("If-Range", if_range)]);
+            assert_eq!(response.status_code(), expected_status, "{}", if_range);
+        }
+    }
+
+    #[test]
+    fn test_range_reader_short_content() {
+        let mut reader = RangeReader::new(Cursor::new(b"abc"), vec![Part::new(vec![], 1, 5)]);
+        let mut output = vec![];
+        let error = reader.read_to_end(&mut output).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(output, b"bc");
+    }
+}
@@ -1,29 +1,31 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    http::{PathParams, Request, Response},
+    http::{Extensions, PathParams, Request, Response},
+    middleware::{Layer, Layers, Next},
     path_router::PathRouter,
-    response::IntoResponse,
 };
 
-/// Function to match a route pattern with an actual path
+/// Function to match a route pattern with an actual path. A segment starting with `:` captures a
+/// single segment of the path, and a final segment starting with `*` captures the rest of it,
+/// slashes included.
 fn match_route(route_pattern: &str, path: &str) -> Option<PathParams> {
-    let extract_segments = |path: &str| {
-        path.trim_start_matches('/')
-            .split('/')
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>()
-    };
-    let route_segments = extract_segments(route_pattern);
-    let path_segments = extract_segments(path);
-
-    if route_segments.len() != path_segments.len() {
-        return None;
-    }
+    let route_segments: Vec<&str> = route_pattern.trim_start_matches('/').split('/').collect();
+    let path_segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
 
     // Collect parameters from the path if the segments match
     let mut params = vec![];
-    for (route_segment, path_segment) in route_segments.iter().zip(path_segments.iter()) {
+    for (index, route_segment) in route_segments.iter().enumerate() {
+        if route_segment.starts_with('*') {
+            // A wildcard must be the last segment, and matches at least one path segment.
+            if index + 1 != route_segments.len() || index >= path_segments.len() {
+                return None;
+            }
+            params.push(path_segments[index..].join("/"));
+            return Some(params);
+        }
+
+        let path_segment = path_segments.get(index)?;
         if route_segment.starts_with(':') {
             params.push(path_segment.to_string());
         } else if route_segment != path_segment {
@@ -31,12 +33,37 @@ fn match_route(route_pattern: &str, path: &str) -> Option<PathParams> {
         }
     }
 
-    Some(params)
+    (route_segments.len() == path_segments.len()).then_some(params)
 }
 
+/// Rank how specific a route pattern is, so that when several patterns match a path we can pick
+/// the best one. Segments are compared from left to right, and a static segment beats a `:`
+/// parameter, which beats a `*` wildcard.
+fn specificity(route_pattern: &str) -> Vec<u8> {
+    route_pattern
+        .trim_start_matches('/')
+        .split('/')
+        .map(|segment| match segment.chars().next() {
+            Some('*') => 0,
+            Some(':') => 1,
+            _ => 2,
+        })
+        .collect()
+}
+
+/// The route pattern which matched a request, such as `/users/:id`, stored in the request's
+/// extensions before the layers run. Unlike the path, it does not vary with IDs in the URL, so
+/// it is safe to group requests by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MatchedPath(pub(crate) String);
+
 /// Router struct to manage routes and handlers
 pub struct Router {
     routes: HashMap<String, PathRouter>,
+    /// Values which are added to the extensions of every request.
+    state: Extensions,
+    /// Middleware which wraps every request, in the order it was added.
+    layers: Layers,
 }
 
 impl Router {
@@ -44,6 +71,8 @@ impl Router {
     pub fn new() -> Self {
         Self {
             routes: HashMap::default(),
+            state: Extensions::new(),
+            layers: Layers::default(),
         }
     }
 
@@ -53,27 +82,68 @@ impl Router {
         self
     }
 
+    /// Share `state` with every handler, which can read it with the
+    /// [`State`](crate::extract::State) extractor. This can be called more than once with values
+    /// of different types, such as an application config and a cookie [`Key`](crate::cookie::Key).
+    /// A second value of the same type replaces the first.
+    pub fn with_state<S: Clone + Send + Sync + 'static>(mut self, state: S) -> Self {
+        self.state.insert(state);
+        self
+    }
+
+    /// Wrap every request in `layer`, including requests which do not match a route. Layers added
+    /// later wrap the ones added before them, so the last layer added sees the request first and
+    /// the response last.
+    pub fn layer(mut self, layer: impl Layer) -> Self {
+        Arc::make_mut(&mut self.layers).push(Arc::new(layer));
+        self
+    }
+
+    /// Wrap the routes added so far in `layer`. Unlike [`layer`](Self::layer), it only runs for
+    /// requests which match one of those routes, so an authentication layer added here leaves
+    /// unknown paths to be answered with `404 Not Found` rather than `401 Unauthorized`. Routes
+    /// added afterwards are not wrapped, which lets public and protected routes share a router.
+    ///
+    /// ```
+    /// use cairo::{middleware::BearerAuthLayer, routing::get, Router};
+    ///
+    /// let router = Router::new()
+    ///     .route("/admin", get(|| "Welcome"))
+    ///     .route_layer(BearerAuthLayer::new(|token| (token == "secret-token").then_some(())))
+    ///     .route("/health", get(|| "OK"));
+    /// ```
+    pub fn route_layer(mut self, layer: impl Layer) -> Self {
+        let layer: Arc<dyn Layer> = Arc::new(layer);
+        for path_router in self.routes.values_mut() {
+            path_router.wrap(&layer);
+        }
+        self
+    }
+
     /// Call the appropriate handler based on the request
     pub(crate) fn call(&self, mut request: Request) -> Response {
-        let mut found_path_params = None;
-        let handler = self.routes.iter().find_map(|(pattern, path_router)| {
-            match match_route(pattern, request.path()) {
-                Some(path_params) => {
-                    found_path_params = Some(path_params);
-                    path_router.find(request.method())
-                }
-                None => None,
-            }
-        });
+        request.extensions_mut().extend(&self.state);
+
+        let path = request.into_parts().path_without_query();
+        let found = self
+            .routes
+            .iter()
+            .filter_map(|(pattern, path_router)| {
+                let handler = path_router.find(request.method())?;
+                let path_params = match_route(pattern, path)?;
+                Some((specificity(pattern), pattern, path_params, handler))
+            })
+            .max_by(|a, b| a.0.cmp(&b.0));
 
-        if let Some(path_params) = found_path_params {
+        let handler = found.map(|(_, pattern, path_params, handler)| {
             request.set_path_params(path_params);
-        }
+            request
+                .extensions_mut()
+                .insert(MatchedPath(pattern.clone()));
+            handler
+        });
 
-        match handler {
-            Some(handler) => handler.call_handler(request),
-            None => (404, "Not Found").into_response(),
-        }
+        Next::new(Arc::clone(&self.layers), handler).run(request)
     }
 }
 
@@ -85,7 +155,7 @@ impl Default for Router {
 
 #[cfg(test)]
 mod tests {
-    use crate::{extract::Path, http::Method, routing::get};
+    use crate::{extract::Path, http::Method, response::IntoResponse, routing::get};
 
     use super::*;
 
@@ -167,6 +237,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_router_route_ignores_query() {
+        let router = Router::new().route("/hello/:id", get(hello_world_index));
+
+        let req = Request::new(Method::Get, "/hello/5?greeting=hi");
+        let response = router.call(req);
+        assert_eq!(response.text(), "Hello, world: 5!");
+    }
+
     #[test]
     fn test_router_route_with_multiple_routes() {
         let router = Router::new()
@@ -190,6 +269,141 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_router_with_state() {
+        use crate::extract::State;
+
+        #[derive(Clone)]
+        struct Greeting(&'static str);
+
+        fn greet(State(greeting): State<Greeting>, Path(id): Path<usize>) -> String {
+            format!("{}, {}!", greeting.0, id)
+        }
+
+        let router = Router::new()
+            .route("/greet/:id", get(greet))
+            .with_state(Greeting("Hi"));
+        let response = router.call(Request::new(Method::Get, "/greet/7"));
+        assert_eq!(response.text(), "Hi, 7!");
+
+        let router = Router::new().route("/greet/:id", get(greet));
+        let response = router.call(Request::new(Method::Get, "/greet/7"));
+        assert_eq!(response.status_code(), 400);
+    }
+
+    #[test]
+    fn test_router_layer() {
+        fn auth(req: Request, next: Next) -> Response {
+            if req.headers().contains_key("Authorization") {
+                next.run(req)
+            } else {
+                (400, "Denied").into_response()
+            }
+        }
+
+        let router = Router::new()
+            .route("/hello", get(hello_world))
+            .layer(auth)
+            .layer(|req: Request, next: Next| {
+                let mut response = next.run(req);
+                response.headers_mut().insert("X-Layer", "outer");
+                response
+            });
+
+        let response = router.call(Request::new(Method::Get, "/hello"));
+        assert_eq!(response.text(), "Denied");
+        assert_eq!(response.headers().get("X-Layer"), Some("outer"));
+
+        let headers = crate::http::HeaderMap::from([("Authorization", "yes")]);
+        let response = router.call(Request::with_headers(Method::Get, "/hello", headers));
+        assert_eq!(response.text(), "Hello, world!");
+
+        let headers = crate::http::HeaderMap::from([("Authorization", "yes")]);
+        let response = router.call(Request::with_headers(Method::Get, "/missing", headers));
+        assert_eq!(response.status_code(), 404);
+        assert_eq!(response.headers().get("X-Layer"), Some("outer"));
+    }
+
+    #[test]
+    fn test_router_route_layer() {
+        fn tag(name: &'static str) -> impl Layer {
+            move |req: Request, next: Next| {
+                let mut response = next.run(req);
+                response.headers_mut().append("X-Layer", name);
+                response
+            }
+        }
+
+        let router = Router::new()
+            .route("/hello", get(hello_world))
+            .route_layer(tag("inner"))
+            .route_layer(tag("route"))
+            .route("/hello/:id", get(hello_world_index))
+            .layer(tag("outer"));
+
+        let response = router.call(Request::new(Method::Get, "/hello"));
+        assert_eq!(response.text(), "Hello, world!");
+        assert_eq!(
+            response.headers().get_all("X-Layer").collect::<Vec<_>>(),
+            vec!["inner", "route", "outer"]
+        );
+
+        // Routes added later, and requests which match no route, skip the route layers.
+        let response = router.call(Request::new(Method::Get, "/hello/7"));
+        assert_eq!(
+            response.headers().get_all("X-Layer").collect::<Vec<_>>(),
+            vec!["outer"]
+        );
+        let response = router.call(Request::new(Method::Post, "/hello"));
+        assert_eq!(response.status_code(), 404);
+        assert_eq!(
+            response.headers().get_all("X-Layer").collect::<Vec<_>>(),
+            vec!["outer"]
+        );
+    }
+
+    #[test]
+    fn test_match_route() {
+        assert_eq!(match_route("/a/:id", "/a/5"), Some(vec!["5".to_string()]));
+        assert_eq!(match_route("/a/:id", "/a/5/6"), None);
+        assert_eq!(
+            match_route("/a/*rest", "/a/b/c.txt"),
+            Some(vec!["b/c.txt".to_string()])
+        );
+        assert_eq!(match_route("/a/*rest", "/a/"), Some(vec!["".to_string()]));
+        assert_eq!(match_route("/a/*rest", "/a"), None);
+        assert_eq!(
+            match_route("/:user/*rest", "/ferris/x/y"),
+            Some(vec!["ferris".to_string(), "x/y".to_string()])
+        );
+        assert_eq!(match_route("/*rest/a", "/x/a"), None);
+    }
+
+    #[test]
+    fn test_router_prefers_specific_routes() {
+        fn wildcard() -> &'static str {
+            "wildcard"
+        }
+        fn param() -> &'static str {
+            "param"
+        }
+        fn fixed() -> &'static str {
+            "fixed"
+        }
+
+        let router = Router::new()
+            .route("/files/*path", get(wildcard).post(wildcard))
+            .route("/files/:name", get(param))
+            .route("/files/special", get(fixed));
+
+        let text = |method, path| router.call(Request::new(method, path)).text();
+        assert_eq!(text(Method::Get, "/files/special"), "fixed");
+        assert_eq!(text(Method::Get, "/files/other"), "param");
+        assert_eq!(text(Method::Get, "/files/a/b"), "wildcard");
+        // A less specific route is used when the better one has no handler for the method.
+        assert_eq!(text(Method::Post, "/files/special"), "wildcard");
+    }
+
     #[test]
     fn test_router_default() {
         let router: Router = Default::default();
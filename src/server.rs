@@ -1,102 +1,340 @@
 use std::{
-    io::{self, ErrorKind, Read, Write},
-    net::TcpListener,
+    io::{self, BufRead, ErrorKind, Read, Write},
+    net::{Shutdown, TcpListener},
     str,
     sync::Arc,
 };
 
 use crate::{
     core::ThreadPool,
-    http::{Request, Response},
+    extract::{ConnectInfo, ExtractError},
+    http::{Body, HeaderMap, Request, Response},
+    log::{log, Level},
+    response::IntoResponse,
     Router,
 };
 
-/// An HTTP request may require multiple reads from a stream. Here we read from a stream until we
-/// have read the entirety of the HTTP headers and body and return the resulting buffer.
-fn fill_buffer<T: Read>(stream: &mut T) -> io::Result<Vec<u8>> {
+/// When a handler does not read the whole body, we read and discard up to this many bytes before
+/// the connection is closed. Closing a socket with unread data causes the client to see a reset,
+/// which can prevent it from reading our response.
+const MAX_DRAIN_BYTES: u64 = 64 * 1024;
+
+/// The largest request head, the request line and headers together, which we will buffer. Without
+/// a limit one client could make us allocate until we run out of memory.
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// The error behind a request head larger than [`MAX_HEAD_BYTES`], so that it can be answered with
+/// `431 Request Header Fields Too Large` rather than a plain `400 Bad Request`.
+#[derive(Debug)]
+struct HeadTooLarge;
+
+impl std::fmt::Display for HeadTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request head is larger than {} bytes", MAX_HEAD_BYTES)
+    }
+}
+
+impl std::error::Error for HeadTooLarge {}
+
+/// The head of an HTTP request may require multiple reads from a stream. Here we read from a
+/// stream until we have read the entirety of the HTTP headers and return the resulting buffer
+/// along with the position at which the body starts.
+///
+/// The buffer may already contain the beginning of the body if it arrived in the same read as the
+/// headers, but we never wait for the rest of it here. It is only read once a handler asks for it.
+///
+/// A head longer than [`MAX_HEAD_BYTES`] fails with a [`HeadTooLarge`] error.
+fn fill_buffer<T: Read>(stream: &mut T) -> io::Result<(Vec<u8>, usize)> {
     let mut buffer = vec![];
     let mut temp_buffer = [0; 512];
-    let mut headers_complete = false;
-    let mut content_length = 0;
 
-    while !headers_complete {
+    loop {
         let num_bytes_read = stream.read(&mut temp_buffer)?;
-        println!("Read {} bytes.\n--", num_bytes_read);
+        log!(Level::Trace, "Read {} bytes", num_bytes_read);
         if num_bytes_read == 0 {
             return Err(io::Error::new(ErrorKind::UnexpectedEof, "Zero bytes read."));
         }
         buffer.extend_from_slice(&temp_buffer[..num_bytes_read]);
 
-        // Check if we've read the headers completely
+        // Check if we've read the headers completely. The body starts after "\r\n\r\n", which is
+        // 4 bytes.
         if let Some(headers_end_pos) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
-            headers_complete = true;
-            let headers_str = str::from_utf8(&buffer[..headers_end_pos])
-                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
-
-            // Parse headers to get the Content-Length. If we do not find one, we use the default
-            // defined above of 0.
-            for line in headers_str.split("\r\n") {
-                if line.starts_with("Content-Length:") {
-                    let length_str = line
-                        .split(':')
-                        .nth(1)
-                        .ok_or_else(|| {
-                            io::Error::new(ErrorKind::InvalidData, "Invalid Content-Length header")
-                        })?
-                        .trim();
-                    content_length = length_str
-                        .parse::<usize>()
-                        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
-                    break;
-                }
+            if headers_end_pos + 4 <= MAX_HEAD_BYTES {
+                return Ok((buffer, headers_end_pos + 4));
             }
+        }
+        if buffer.len() >= MAX_HEAD_BYTES {
+            return Err(io::Error::new(ErrorKind::InvalidData, HeadTooLarge));
+        }
+    }
+}
 
-            // If there is a body, calculate remaining bytes to read, given that "\r\n\r\n" is 4
-            // bytes. We only need this clause for when the body is split across streams. When the
-            // body is entirely in the next stream read, then `buffer.len() == body_start_pos`.
-            let body_start_pos = headers_end_pos + 4;
-            if buffer.len() > body_start_pos {
-                content_length -= buffer.len() - body_start_pos;
-            }
+/// Find the length of the body from the `Content-Length` header. If there is not one, there is no
+/// body. A request with several conflicting lengths is rejected, since we cannot know which one
+/// the client meant.
+fn content_length(headers: &HeaderMap) -> io::Result<u64> {
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "Invalid Content-Length header");
+
+    let mut content_length = None;
+    for value in headers.get_all("Content-Length") {
+        let length = value.parse::<u64>().map_err(|_| invalid())?;
+        if content_length.is_some_and(|existing| existing != length) {
+            return Err(invalid());
         }
+        content_length = Some(length);
     }
 
-    // If there is a body, read the remaining bytes. We do this in a loop in case it is sent across
-    // multiple streams.
-    while content_length > 0 {
-        let num_bytes_read = stream.read(&mut temp_buffer)?;
-        println!("Read {} bytes.\n--", num_bytes_read);
+    Ok(content_length.unwrap_or(0))
+}
+
+/// The longest chunk-size line or trailer line we accept in a chunked body. Real ones are a few
+/// bytes, so this only stops a client from making us buffer an endless line.
+const MAX_CHUNK_LINE: u64 = 4096;
+
+/// How the end of a request body is marked.
+#[derive(Debug, PartialEq)]
+enum Framing {
+    Length(u64),
+    Chunked,
+}
+
+/// Work out how the body of a request is framed from its `Transfer-Encoding` and
+/// `Content-Length` headers.
+///
+/// A request with both headers is rejected rather than letting one win, since a proxy in front of
+/// us might have picked the other and so disagree with us about where this request ends. That
+/// disagreement is what request smuggling relies on. `chunked` is the only transfer coding we
+/// decode, so anything else is rejected too.
+fn framing(headers: &HeaderMap) -> io::Result<Framing> {
+    if !headers.contains_key("Transfer-Encoding") {
+        return content_length(headers).map(Framing::Length);
+    }
+    if headers.contains_key("Content-Length") {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Request has both Transfer-Encoding and Content-Length headers",
+        ));
+    }
+
+    let codings: Vec<_> = headers
+        .get_all("Transfer-Encoding")
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    match codings[..] {
+        [coding] if coding.eq_ignore_ascii_case("chunked") => Ok(Framing::Chunked),
+        _ => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Unsupported Transfer-Encoding header",
+        )),
+    }
+}
+
+/// A reader over the body of a request which is still on the connection.
+///
+/// It yields exactly `Content-Length` bytes, first from whatever was read alongside the headers
+/// and then from the stream itself. If the connection closes before the full body arrives, reading
+/// fails with `UnexpectedEof` rather than quietly returning a truncated body.
+struct SocketBody<T: Read> {
+    inner: io::Chain<io::Cursor<Vec<u8>>, T>,
+    remaining: u64,
+}
+
+impl<T: Read> SocketBody<T> {
+    fn new(already_read: Vec<u8>, stream: T, content_length: u64) -> Self {
+        Self {
+            inner: io::Cursor::new(already_read).chain(stream),
+            remaining: content_length,
+        }
+    }
+}
+
+impl<T: Read> Read for SocketBody<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+
+        let max = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let num_bytes_read = self.inner.read(&mut buf[..max])?;
+        if num_bytes_read == 0 && max > 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed before the full body was read.",
+            ));
+        }
+        self.remaining -= num_bytes_read as u64;
+        Ok(num_bytes_read)
+    }
+}
+
+impl<T: Read> Drop for SocketBody<T> {
+    /// Discard a small unread remainder of the body so the connection can be closed cleanly. Larger
+    /// bodies are left alone, since reading them would tie up a worker for no benefit.
+    fn drop(&mut self) {
+        if self.remaining > 0 && self.remaining <= MAX_DRAIN_BYTES {
+            let _ = io::copy(self, &mut io::sink());
+        }
+    }
+}
+
+/// A reader over a body sent with `Transfer-Encoding: chunked`, which yields the data of each
+/// chunk without the framing around it.
+///
+/// Each chunk is a hex size line, that many bytes and a CRLF. A chunk of size zero ends the body,
+/// followed by optional trailer fields, which we read and discard, and a blank line.
+struct ChunkedBody<T: Read> {
+    inner: io::BufReader<io::Chain<io::Cursor<Vec<u8>>, T>>,
+    /// The bytes left in the current chunk.
+    remaining: u64,
+    done: bool,
+}
+
+impl<T: Read> ChunkedBody<T> {
+    fn new(already_read: Vec<u8>, stream: T) -> Self {
+        Self {
+            inner: io::BufReader::new(io::Cursor::new(already_read).chain(stream)),
+            remaining: 0,
+            done: false,
+        }
+    }
+
+    /// Read one line, without its CRLF.
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        (&mut self.inner)
+            .take(MAX_CHUNK_LINE)
+            .read_until(b'\n', &mut line)?;
+        match line.strip_suffix(b"\r\n") {
+            Some(line) => String::from_utf8(line.to_vec())
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e)),
+            None if line.len() as u64 == MAX_CHUNK_LINE => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Chunk line too long",
+            )),
+            None => Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed before the full body was read.",
+            )),
+        }
+    }
+
+    /// Read the size line of the next chunk. Chunk extensions after a `;` are ignored.
+    fn read_chunk_size(&mut self) -> io::Result<u64> {
+        let line = self.read_line()?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Invalid chunk size"));
+        }
+        u64::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid chunk size"))
+    }
+}
+
+impl<T: Read> Read for ChunkedBody<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            self.remaining = self.read_chunk_size()?;
+            if self.remaining == 0 {
+                while !self.read_line()?.is_empty() {}
+                self.done = true;
+                return Ok(0);
+            }
+        }
+
+        let max = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let num_bytes_read = self.inner.read(&mut buf[..max])?;
         if num_bytes_read == 0 {
-            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Zero bytes read."));
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed before the full body was read.",
+            ));
         }
-        buffer.extend_from_slice(&temp_buffer[..num_bytes_read]);
-        content_length -= num_bytes_read;
+        self.remaining -= num_bytes_read as u64;
+        if self.remaining == 0 && !self.read_line()?.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Chunk data longer than its size",
+            ));
+        }
+        Ok(num_bytes_read)
     }
+}
 
-    Ok(buffer)
+impl<T: Read> Drop for ChunkedBody<T> {
+    /// Discard a small unread remainder of the body, as [`SocketBody`] does.
+    fn drop(&mut self) {
+        let _ = io::copy(&mut self.take(MAX_DRAIN_BYTES), &mut io::sink());
+    }
 }
 
 /// Read from a `TcpStream` (or any type that implements `Read`) and attempt to get an HTTP
 /// `Request`.
-fn parse_request<T: Read>(stream: &mut T) -> io::Result<Request> {
-    let buffer = fill_buffer(stream)?;
-
-    // By this point, we know we have read our headers and body into the `buffer`.
-    let request = str::from_utf8(&buffer).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
-    println!("Request: {}", request);
+///
+/// Only the request head is read here. The body is attached to the `Request` as a stream which
+/// takes ownership of `stream`, so it is not read until a handler asks for it.
+fn parse_request<T: Read + Send + 'static>(mut stream: T) -> io::Result<Request> {
+    let (mut buffer, body_start_pos) = fill_buffer(&mut stream)?;
+    let already_read = buffer.split_off(body_start_pos);
+
+    // By this point, we know we have read our headers into the `buffer`.
+    let head = str::from_utf8(&buffer).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    log!(
+        Level::Debug,
+        "Request: {}",
+        head.lines().next().unwrap_or_default()
+    );
+
+    let mut request = Request::try_from(head)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Unexpected request format."))?;
+
+    match framing(request.headers())? {
+        Framing::Length(0) => {}
+        Framing::Length(content_length) => {
+            let reader = SocketBody::new(already_read, stream, content_length);
+            request.set_body(Body::sized_reader(reader, content_length));
+        }
+        // The length is unknown until the last chunk, so extractors read up to their limit.
+        Framing::Chunked => {
+            request.set_body(Body::from_reader(ChunkedBody::new(already_read, stream)))
+        }
+    }
 
-    Request::try_from(request)
-        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Unexpected request format."))
+    Ok(request)
 }
 
-/// Write an HTTP `Response` to a `TcpStream` (or any type that implements `Write`).
+/// Write an HTTP `Response` to a `TcpStream` (or any type that implements `Write`), returning the
+/// total number of bytes written. The whole response is written even if the stream only accepts
+/// part of it at a time.
 fn send_response<T: Write>(stream: &mut T, response: Response) -> io::Result<usize> {
-    let num_bytes_written = stream.write(&response.as_bytes())?;
+    let num_bytes_written = response.write_to(stream)?;
     stream.flush()?;
 
     Ok(num_bytes_written)
 }
 
+/// The response to a request we could not parse. Most are a `400 Bad Request`, but a head which
+/// was too large to buffer gets a `431 Request Header Fields Too Large`.
+fn bad_request_response(error: &io::Error) -> Response {
+    if error.get_ref().is_some_and(|e| e.is::<HeadTooLarge>()) {
+        Response::new(
+            431,
+            HeaderMap::from([("Content-Type", "text/plain")]),
+            "Request header fields too large".to_string(),
+        )
+    } else {
+        ExtractError::BadRequest.into_response()
+    }
+}
+
 /// Serve incoming TCP connections using the provided `Router`.
 ///
 /// This function listens for incoming TCP connections on the given `TcpListener` and uses a
@@ -107,27 +345,57 @@ pub fn serve(listener: TcpListener, router: Router) {
     // We create an `Arc` so we can share the `Router` between threads.
     let router = Arc::new(router);
     let pool = ThreadPool::new(4);
+    let stats = pool.stats();
 
     for stream in listener.incoming() {
         match stream {
             Ok(mut stream) => {
                 let router = router.clone();
+                let stats = stats.clone();
                 // We must `move` the `Arc<Router>` into the closure since it could outlive this
                 // function.
                 pool.execute(move || {
-                    let request = match parse_request(&mut stream) {
+                    // The request body holds its own handle to the connection so that it can be
+                    // read lazily by the handler, while we keep this one to write the response.
+                    let reader = match stream.try_clone() {
+                        Ok(reader) => reader,
+                        Err(e) => {
+                            log!(Level::Error, "Failed to clone the connection: {}", e);
+                            return;
+                        }
+                    };
+                    let connect_info = match (stream.peer_addr(), stream.local_addr()) {
+                        (Ok(peer_addr), Ok(local_addr)) => Some(ConnectInfo {
+                            peer_addr,
+                            local_addr,
+                        }),
+                        _ => None,
+                    };
+
+                    let mut request = match parse_request(reader) {
                         Ok(request) => request,
                         Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => {
                             // Ignoring UnexpectedEof error, this occurs when we read zero bytes,
                             // which indicates the client has closed a connection.
-                            println!("Client closed connection.");
+                            log!(Level::Debug, "Client closed connection");
                             return;
                         }
                         Err(e) => {
-                            eprintln!("An error occurred: {}", e);
+                            log!(Level::Warn, "Failed to read a request: {}", e);
+                            // A malformed request still gets an answer, so the client is not left
+                            // waiting for one.
+                            if e.kind() == ErrorKind::InvalidData {
+                                let response = bad_request_response(&e);
+                                let _ = send_response(&mut stream, response);
+                            }
                             return;
                         }
                     };
+                    if let Some(connect_info) = connect_info {
+                        request.extensions_mut().insert(connect_info);
+                    }
+                    // Shared so that a metrics handler can report how busy the pool is.
+                    request.extensions_mut().insert(stats);
 
                     // Turn the HTTP `Request` into the `Response` using the `Router` which will
                     // call the appropriate handler.
@@ -135,15 +403,19 @@ pub fn serve(listener: TcpListener, router: Router) {
 
                     match send_response(&mut stream, response) {
                         Ok(num_bytes_written) => {
-                            println!("--\nSent {} bytes.\n--", num_bytes_written);
+                            log!(Level::Trace, "Sent {} bytes", num_bytes_written);
                         }
                         Err(e) => {
-                            eprintln!("An error occurred: {}", e);
+                            log!(Level::Warn, "Failed to send a response: {}", e);
                         }
                     }
+                    // Tell the client the response is over now, rather than when the last handle to
+                    // the connection is dropped. A handler which outlived its `TimeoutLayer` may
+                    // still hold one through the request body.
+                    let _ = stream.shutdown(Shutdown::Write);
                 });
             }
-            Err(e) => eprintln!("Failed to accept connection: {}", e),
+            Err(e) => log!(Level::Error, "Failed to accept connection: {}", e),
         }
     }
 }
@@ -153,73 +425,197 @@ mod tests {
     use std::io::Cursor;
 
     use super::*;
-    use crate::http::Method;
+    use crate::{
+        core::TrickleWriter,
+        http::{HeaderMap, Method},
+    };
 
     #[test]
     fn test_parse_request_valid_root() {
-        let mut stream = Cursor::new(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
-        let request = parse_request(&mut stream).unwrap();
+        let stream = Cursor::new(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let request = parse_request(stream).unwrap();
         assert_eq!(
             request,
-            Request::with_headers(
-                Method::Get,
-                "/",
-                vec![("Host".to_string(), "localhost".to_string())]
-            )
+            Request::with_headers(Method::Get, "/", HeaderMap::from([("Host", "localhost")]))
         );
     }
 
     #[test]
     fn test_parse_request_invalid_utf8() {
-        let mut stream = Cursor::new(b"\x80\x81\x82\x83");
-        let result = parse_request(&mut stream);
+        let stream = Cursor::new(b"\x80\x81\x82\x83");
+        let result = parse_request(stream);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
 
-        let mut stream = Cursor::new(b"\x80\x81\x82\x83\r\n\r\n");
-        let result = parse_request(&mut stream);
+        let stream = Cursor::new(b"\x80\x81\x82\x83\r\n\r\n");
+        let result = parse_request(stream);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
     }
 
     #[test]
     fn test_parse_request_empty() {
-        let mut stream = Cursor::new(b"");
-        let result = parse_request(&mut stream);
+        let stream = Cursor::new(b"");
+        let result = parse_request(stream);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
     }
 
+    #[test]
+    fn test_parse_request_control_byte_in_target() {
+        let stream = Cursor::new(b"GET /a/sub?\x01 HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let result = parse_request(stream);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_parse_request_head_too_large() {
+        let mut head = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        head.resize(MAX_HEAD_BYTES, b'a');
+        head.extend_from_slice(b"\r\n\r\n");
+        let error = parse_request(Cursor::new(head)).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(bad_request_response(&error).status_code(), 431);
+
+        let mut head = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        head.resize(MAX_HEAD_BYTES - 4, b'a');
+        head.extend_from_slice(b"\r\n\r\n");
+        assert!(parse_request(Cursor::new(head)).is_ok());
+    }
+
     #[test]
     fn test_parse_request_invalid_format() {
-        let mut stream = Cursor::new(b"INVALID REQUEST\r\n");
-        let result = parse_request(&mut stream);
+        let stream = Cursor::new(b"INVALID REQUEST\r\n");
+        let result = parse_request(stream);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
 
-        let mut stream = Cursor::new(b"INVALID REQUEST\r\n\r\n");
-        let result = parse_request(&mut stream);
+        let stream = Cursor::new(b"INVALID REQUEST\r\n\r\n");
+        let result = parse_request(stream);
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
     }
 
+    #[test]
+    fn test_parse_request_deferred_body() {
+        let stream =
+            Cursor::new(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nHello RustTrailing bytes");
+        let request = parse_request(stream).unwrap();
+        assert!(request.body().is_streaming());
+        assert_eq!(request.body().len(), Some(10));
+        assert_eq!(request.into_body().into_bytes().unwrap(), b"Hello Rust");
+    }
+
+    #[test]
+    fn test_parse_request_content_length_any_case() {
+        let stream = Cursor::new(b"POST / HTTP/1.1\r\ncontent-length:5\r\n\r\nHello");
+        let request = parse_request(stream).unwrap();
+        assert_eq!(request.into_body().into_bytes().unwrap(), b"Hello");
+    }
+
+    #[test]
+    fn test_parse_request_conflicting_content_length() {
+        let stream =
+            Cursor::new(b"POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\nHello!");
+        let result = parse_request(stream);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let stream = Cursor::new(b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n");
+        let result = parse_request(stream);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_parse_request_chunked_body() {
+        let stream = Cursor::new(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5;name=value\r\nHello\r\n5\r\n Rust\r\n0\r\nExpires: never\r\n\r\nTrailing bytes",
+        );
+        let request = parse_request(stream).unwrap();
+        assert!(request.body().is_streaming());
+        assert_eq!(request.body().len(), None);
+        assert_eq!(request.into_body().into_bytes().unwrap(), b"Hello Rust");
+
+        for chunks in [
+            &b"5\r\nHello"[..],
+            b"5\r\nHello Rust\r\n0\r\n\r\n",
+            b"x\r\n",
+        ] {
+            let head = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+            let stream = Cursor::new([head, chunks.to_vec()].concat());
+            let request = parse_request(stream).unwrap();
+            assert!(request.into_body().into_bytes().is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_request_rejects_ambiguous_framing() {
+        for head in [
+            &b"POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n"[..],
+            b"POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\ncontent-length: 5\r\n\r\n",
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n",
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n\r\n",
+        ] {
+            let stream = Cursor::new([head, b"0\r\n\r\n"].concat());
+            let result = parse_request(stream);
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_parse_request_body_split_across_reads() {
+        let head = b"POST / HTTP/1.1\r\nContent-Length: 700\r\n\r\n".to_vec();
+        let body = vec![b'a'; 700];
+        let stream = Cursor::new([head, body.clone()].concat());
+        let request = parse_request(stream).unwrap();
+        assert_eq!(request.into_body().into_bytes().unwrap(), body);
+    }
+
+    #[test]
+    fn test_parse_request_truncated_body() {
+        let stream = Cursor::new(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nHello");
+        let request = parse_request(stream).unwrap();
+        let result = request.into_body().into_bytes();
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_parse_request_non_utf8_body() {
+        let stream = Cursor::new(b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n\x80\x81");
+        let request = parse_request(stream).unwrap();
+        assert_eq!(request.into_body().into_bytes().unwrap(), b"\x80\x81");
+    }
+
     #[test]
     fn test_send_response_ok() {
         let response = Response::new(
             200,
-            vec![("Content-Type".to_string(), "text/plain".to_string())],
+            HeaderMap::from([("Content-Type", "text/plain")]),
             "Hello, World!".to_string(),
         );
         let mut cursor = Cursor::new(vec![]);
 
         let result = send_response(&mut cursor, response);
 
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), 58);
-        assert_eq!(
-            cursor.into_inner(),
-            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nHello, World!"
+        let expected = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nHello, World!";
+        assert_eq!(result.unwrap(), expected.len());
+        assert_eq!(cursor.into_inner(), expected);
+    }
+
+    #[test]
+    fn test_send_response_partial_writes() {
+        let response = Response::new(
+            200,
+            HeaderMap::from([("Content-Type", "text/plain")]),
+            "Hello, World!".to_string(),
         );
+        let mut writer = TrickleWriter::new();
+
+        let result = send_response(&mut writer, response);
+
+        let expected = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nHello, World!";
+        assert_eq!(result.unwrap(), expected.len());
+        assert_eq!(writer.output, expected);
     }
 
     #[test]
@@ -228,15 +624,15 @@ mod tests {
 
         impl Write for FailingWriter {
             fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
-                Err(io::Error::new(io::ErrorKind::Other, "write failed"))
+                Err(io::Error::other("write failed"))
             }
 
             fn flush(&mut self) -> io::Result<()> {
-                Err(io::Error::new(io::ErrorKind::Other, "flush failed"))
+                Err(io::Error::other("flush failed"))
             }
         }
 
-        let response = Response::new(200, vec![], "Hello, World!".to_string());
+        let response = Response::new(200, HeaderMap::new(), "Hello, World!".to_string());
         let mut failing_writer = FailingWriter;
 
         let result = send_response(&mut failing_writer, response);
@@ -0,0 +1,23 @@
+//! Ready-made handlers which can be mounted on a [`Router`](crate::Router).
+//!
+//! [`ServeDir`] serves the files under a directory, and [`ServeFile`] serves a single file. They are
+//! registered like any other handler, with a `*` wildcard selecting the file within the directory.
+//!
+//! ```no_run
+//! use cairo::{
+//!     routing::get,
+//!     services::{ServeDir, ServeFile},
+//!     Router,
+//! };
+//!
+//! let router = Router::new()
+//!     .route("/", get(ServeFile::new("public/index.html")))
+//!     .route(
+//!         "/assets/*path",
+//!         get(ServeDir::new("public/assets")).head(ServeDir::new("public/assets")),
+//!     );
+//! ```
+mod mime;
+mod serve_dir;
+
+pub use serve_dir::{ServeDir, ServeFile};
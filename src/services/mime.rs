@@ -0,0 +1,61 @@
+use std::path::Path;
+
+/// Guess the `Content-Type` of a file from its extension, falling back to
+/// `application/octet-stream` so that browsers do not try to sniff unknown files.
+pub(crate) fn guess(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess() {
+        assert_eq!(guess(Path::new("index.html")), "text/html; charset=utf-8");
+        assert_eq!(
+            guess(Path::new("app.min.JS")),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(guess(Path::new("logo.svg")), "image/svg+xml");
+        assert_eq!(guess(Path::new("archive.tar.gz")), "application/gzip");
+        assert_eq!(guess(Path::new("README")), "application/octet-stream");
+        assert_eq!(guess(Path::new("data.unknown")), "application/octet-stream");
+    }
+}
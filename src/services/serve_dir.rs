@@ -0,0 +1,475 @@
+use std::{
+    fmt::Write,
+    fs::{self, File, Metadata},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use super::mime;
+use crate::{
+    extract::percent_decode,
+    handler::Handler,
+    headers::EntityTag,
+    http::{Body, HeaderMap, HttpDate, Method, Request, Response},
+    response::{IntoResponse, Ranged},
+};
+
+/// The file served in place of a directory, if the directory contains one.
+const INDEX_FILE: &str = "index.html";
+
+/// A handler which serves the files under a directory.
+///
+/// It should be mounted on a route ending in a `*` wildcard, such as `/assets/*path`, and the
+/// wildcard selects the file relative to the directory. Without a wildcard the directory itself is
+/// served. A request for a directory is answered with its `index.html` if it has one, or else a
+/// listing of its contents if [`with_directory_listing`](Self::with_directory_listing) is on.
+///
+/// Paths which would leave the directory, such as `../secret`, are answered with a 404. So are
+/// symlinks which point outside of it, since the check is made after they are resolved.
+#[derive(Debug, Clone)]
+pub struct ServeDir {
+    root: PathBuf,
+    listing: bool,
+}
+
+impl ServeDir {
+    /// Serve the files under `root`, which may be relative to the working directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            listing: false,
+        }
+    }
+
+    /// Choose whether to list the contents of a directory which has no `index.html`. This is off
+    /// by default, and such a directory is answered with a 404.
+    pub fn with_directory_listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    /// Resolve the path the client asked for to a file or directory under the root, or `None` if
+    /// it does not exist or would leave the root.
+    fn resolve(&self, relative: &str) -> Option<PathBuf> {
+        let decoded = percent_decode(relative).ok()?;
+        let mut path = self.root.clone();
+        for segment in decoded.split('/') {
+            match segment {
+                "" | "." => continue,
+                ".." => return None,
+                // A backslash is a separator on Windows, and a NUL cannot be in a path at all.
+                segment if segment.contains(['\\', '\0']) => return None,
+                segment => path.push(segment),
+            }
+        }
+
+        // Every component was a plain name, but a symlink could still point anywhere.
+        let root = self.root.canonicalize().ok()?;
+        let path = path.canonicalize().ok()?;
+        path.starts_with(&root).then_some(path)
+    }
+}
+
+impl Handler<()> for ServeDir {
+    fn call_handler(&self, req: Request) -> Response {
+        let relative = req.path_params().last().map_or("", String::as_str);
+        let Some(path) = self.resolve(relative) else {
+            return not_found();
+        };
+        if !path.is_dir() {
+            return serve_file(&req, &path);
+        }
+
+        // Relative links in a directory's page only work if its URL ends with a slash.
+        let parts = req.into_parts();
+        let request_path = parts.path_without_query();
+        if !request_path.ends_with('/') {
+            return redirect_to_directory(request_path, parts.query());
+        }
+
+        let index = path.join(INDEX_FILE);
+        if index.is_file() {
+            serve_file(&req, &index)
+        } else if self.listing {
+            list_directory(&path, request_path, !relative.is_empty())
+        } else {
+            not_found()
+        }
+    }
+}
+
+/// A handler which serves a single file, whatever the path of the request.
+#[derive(Debug, Clone)]
+pub struct ServeFile {
+    path: PathBuf,
+}
+
+impl ServeFile {
+    /// Serve the file at `path`, which may be relative to the working directory.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Handler<()> for ServeFile {
+    fn call_handler(&self, req: Request) -> Response {
+        if self.path.is_file() {
+            serve_file(&req, &self.path)
+        } else {
+            not_found()
+        }
+    }
+}
+
+fn not_found() -> Response {
+    (404, "Not Found").into_response()
+}
+
+/// Compute a strong entity tag from the size and modification time of a file, so that it changes
+/// whenever the file is rewritten without having to read it.
+fn file_etag(metadata: &Metadata) -> EntityTag {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    EntityTag::strong(&format!(
+        "{:x}.{:x}-{:x}",
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        metadata.len()
+    ))
+}
+
+/// Stream the file at `path` from disk, honouring any `Range` header, or send only its headers for
+/// a `HEAD` request.
+fn serve_file(req: &Request, path: &Path) -> Response {
+    let Ok(file) = File::open(path) else {
+        return not_found();
+    };
+    let Ok(metadata) = file.metadata() else {
+        return not_found();
+    };
+
+    let len = metadata.len();
+    let is_head = *req.method() == Method::Head;
+    // A range only applies to `GET`, so a `HEAD` is answered as if for the whole file.
+    let request_headers = if is_head {
+        HeaderMap::new()
+    } else {
+        req.headers().clone()
+    };
+    let mut ranged = Ranged::new(&request_headers, file, len)
+        .with_content_type(mime::guess(path))
+        .with_etag(file_etag(&metadata));
+    if let Ok(modified) = metadata.modified() {
+        ranged = ranged.with_last_modified(HttpDate::from(modified));
+    }
+
+    let response = ranged.into_response();
+    if is_head {
+        let mut headers = response.headers().clone();
+        headers.insert("Content-Length", len.to_string());
+        return Response::new(200, headers, Body::empty());
+    }
+    response
+}
+
+/// Redirect a request for a directory to the same path with a trailing slash.
+fn redirect_to_directory(request_path: &str, query: Option<&str>) -> Response {
+    // Collapse leading slashes, since `//example.com/` would send the client to another host.
+    let mut location = format!(
+        "/{}/",
+        encode_location(request_path.trim_start_matches('/'))
+    );
+    if let Some(query) = query {
+        location.push('?');
+        location.push_str(&encode_location(query));
+    }
+    let headers = HeaderMap::from([("Location", location.as_str())]);
+    Response::new(301, headers, Body::empty())
+}
+
+/// Render an HTML page linking to each entry of the directory at `path`, with directories first.
+fn list_directory(path: &Path, request_path: &str, has_parent: bool) -> Response {
+    let Ok(entries) = fs::read_dir(path) else {
+        return not_found();
+    };
+    let mut names: Vec<(bool, String)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let is_dir = entry.file_type().ok()?.is_dir();
+            Some((!is_dir, entry.file_name().into_string().ok()?))
+        })
+        .collect();
+    names.sort();
+
+    let title = escape_html(request_path);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n\
+         <body>\n<h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    if has_parent {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (is_file, name) in names {
+        let slash = if is_file { "" } else { "/" };
+        let _ = writeln!(
+            html,
+            "<li><a href=\"{}{}\">{}{}</a></li>",
+            encode_path_segment(&name),
+            slash,
+            escape_html(&name),
+            slash
+        );
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+
+    let headers = HeaderMap::from([("Content-Type", "text/html; charset=utf-8")]);
+    Response::new(200, headers, html)
+}
+
+/// Escape the characters which are special in HTML text and attribute values.
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encode every byte of `s` which is not visible ASCII, so that the client's path and
+/// query are always a valid `Location` header. Bytes which are already escaped are left alone.
+fn encode_location(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_graphic() {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", b);
+        }
+    }
+    encoded
+}
+
+/// Percent-encode a file name so it can be used as one segment of a relative URL.
+fn encode_path_segment(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", b);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::temp_dir,
+        headers::{ETag, LastModified},
+        routing::get,
+        Router,
+    };
+
+    /// Create a directory of files to serve, returning its path.
+    fn site(name: &str) -> PathBuf {
+        let root = temp_dir(name);
+        fs::create_dir_all(root.join("docs/empty")).unwrap();
+        fs::create_dir_all(root.join("with index")).unwrap();
+        fs::write(root.join("style.css"), "body {}").unwrap();
+        fs::write(root.join("docs/a&b <1>.txt"), "Hello").unwrap();
+        fs::write(root.join("with index/index.html"), "<h1>Home</h1>").unwrap();
+        root
+    }
+
+    fn get_path(router: &Router, path: &str) -> Response {
+        router.call(Request::new(Method::Get, path))
+    }
+
+    fn body(response: Response) -> Vec<u8> {
+        response.into_body().into_bytes().unwrap()
+    }
+
+    #[test]
+    fn test_serve_dir_serves_files() {
+        let root = site("serve-dir-files");
+        let router = Router::new().route("/assets/*path", get(ServeDir::new(&root)));
+
+        let response = get_path(&router, "/assets/style.css");
+        assert_eq!(response.status_code(), 200);
+        assert!(response.body().is_streaming());
+        assert_eq!(response.body().len(), Some(7));
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some("text/css; charset=utf-8")
+        );
+        assert!(response.headers().typed_get::<LastModified>().is_some());
+        let etag = response.headers().typed_get::<ETag>().unwrap();
+        assert!(!etag.0.is_weak());
+        assert_eq!(body(response), b"body {}");
+
+        // The path is percent-decoded before it is looked up.
+        let response = get_path(&router, "/assets/docs/a%26b%20%3C1%3E.txt?download=1");
+        assert_eq!(body(response), b"Hello");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_serve_dir_head() {
+        let root = site("serve-dir-head");
+        let router = Router::new().route(
+            "/*path",
+            get(ServeDir::new(&root)).head(ServeDir::new(&root)),
+        );
+
+        let response = router.call(Request::new(Method::Head, "/style.css"));
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.headers().get("Content-Length"), Some("7"));
+        assert_eq!(response.headers().get("Accept-Ranges"), Some("bytes"));
+        assert!(response.body().is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_serve_dir_range() {
+        let root = site("serve-dir-range");
+        let router = Router::new().route("/*path", get(ServeDir::new(&root)));
+
+        let headers = HeaderMap::from([("Range", "bytes=5-")]);
+        let response = router.call(Request::with_headers(Method::Get, "/style.css", headers));
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(response.headers().get("Content-Range"), Some("bytes 5-6/7"));
+        assert_eq!(body(response), b"{}");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_serve_dir_rejects_traversal() {
+        let root = site("serve-dir-traversal");
+        fs::write(root.with_extension("secret"), "Secret").unwrap();
+        let router = Router::new().route("/assets/*path", get(ServeDir::new(root.join("docs"))));
+
+        for path in [
+            "/assets/../style.css",
+            "/assets/%2e%2e/style.css",
+            "/assets/%2E%2E%2Fstyle.css",
+            "/assets/..%5Cstyle.css",
+            "/assets/a%00.txt",
+            "/assets/missing.txt",
+        ] {
+            assert_eq!(get_path(&router, path).status_code(), 404, "{}", path);
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("style.css"), root.join("docs/link.css")).unwrap();
+            assert_eq!(get_path(&router, "/assets/link.css").status_code(), 404);
+        }
+
+        fs::remove_file(root.with_extension("secret")).unwrap();
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_serve_dir_directories() {
+        let root = site("serve-dir-directories");
+        let router = Router::new().route("/assets/*path", get(ServeDir::new(&root)));
+
+        let response = get_path(&router, "/assets/with%20index?x=1");
+        assert_eq!(response.status_code(), 301);
+        assert_eq!(
+            response.headers().get("Location"),
+            Some("/assets/with%20index/?x=1")
+        );
+
+        let response = get_path(&router, "/assets/with%20index/");
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(body(response), b"<h1>Home</h1>");
+
+        // Without listing, a directory with no index is not found.
+        assert_eq!(get_path(&router, "/assets/docs/").status_code(), 404);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_serve_dir_listing() {
+        let root = site("serve-dir-listing");
+        let serve_dir = ServeDir::new(&root).with_directory_listing(true);
+        let router = Router::new().route("/assets/*path", get(serve_dir));
+
+        let response = get_path(&router, "/assets/docs/");
+        assert_eq!(response.status_code(), 200);
+        let html = String::from_utf8(body(response)).unwrap();
+        assert!(html.contains("<a href=\"../\">"));
+        assert!(html.contains("<a href=\"empty/\">empty/</a>"));
+        assert!(html.contains("<a href=\"a%26b%20%3C1%3E.txt\">a&amp;b &lt;1&gt;.txt</a>"));
+        assert!(html.find("empty/").unwrap() < html.find("a%26b").unwrap());
+
+        let response = get_path(&router, "/assets/");
+        let html = String::from_utf8(body(response)).unwrap();
+        assert!(!html.contains("../"));
+        assert!(html.contains("style.css"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_redirect_to_directory_stays_on_host() {
+        let response = redirect_to_directory("//example.com", None);
+        assert_eq!(response.headers().get("Location"), Some("/example.com/"));
+    }
+
+    #[test]
+    fn test_redirect_to_directory_encodes_control_bytes() {
+        let root = site("serve-dir-control-bytes");
+        let router = Router::new().route("/assets/*path", get(ServeDir::new(&root)));
+
+        let response = get_path(&router, "/assets/docs?\x01a b\r\nX: y");
+        assert_eq!(response.status_code(), 301);
+        assert_eq!(
+            response.headers().get("Location"),
+            Some("/assets/docs/?%01a%20b%0D%0AX:%20y")
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_serve_file() {
+        let root = site("serve-file");
+        let router = Router::new()
+            .route("/", get(ServeFile::new(root.join("with index/index.html"))))
+            .route("/missing", get(ServeFile::new(root.join("missing.html"))))
+            .route("/dir", get(ServeFile::new(&root)));
+
+        let response = get_path(&router, "/");
+        assert_eq!(
+            response.headers().get("Content-Type"),
+            Some("text/html; charset=utf-8")
+        );
+        assert_eq!(body(response), b"<h1>Home</h1>");
+        assert_eq!(get_path(&router, "/missing").status_code(), 404);
+        assert_eq!(get_path(&router, "/dir").status_code(), 404);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
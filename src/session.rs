@@ -0,0 +1,580 @@
+//! Server-side sessions.
+//!
+//! The [`SessionLayer`] gives each client a random session ID in a cookie, and keeps the data for
+//! that session in a [`SessionStore`]. Handlers read and change the data through the [`Session`]
+//! extractor, and the layer saves it once the handler returns.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use cairo::{
+//!     routing::{get, post},
+//!     session::{MemoryStore, Session, SessionLayer},
+//!     Router,
+//! };
+//!
+//! fn login(session: Session) -> &'static str {
+//!     // Give the client a new session ID whenever its privileges change.
+//!     session.rotate_id();
+//!     session.insert("user", "ferris");
+//!     "Logged in"
+//! }
+//!
+//! fn whoami(session: Session) -> String {
+//!     session.get("user").unwrap_or_else(|| "Nobody".to_string())
+//! }
+//!
+//! let sessions = SessionLayer::new(MemoryStore::new())
+//!     .with_ttl(Duration::from_secs(60 * 60))
+//!     .with_sweeper(Duration::from_secs(60));
+//! let router = Router::new()
+//!     .route("/login", post(login))
+//!     .route("/whoami", get(whoami))
+//!     .layer(sessions);
+//! ```
+mod store;
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    cookie::{Cookie, CookieJar, SameSite},
+    core::fill_random,
+    extract::{ExtractError, FromRequest, FromRequestParts},
+    http::{Parts, Request, Response},
+    log::{log, Level},
+    middleware::{Layer, Next},
+    response::IntoResponseParts,
+};
+
+pub use store::{FileStore, MemoryStore, SessionRecord, SessionStore};
+
+/// The number of random bytes in a session ID, which is written as twice as many hex digits.
+const ID_BYTES: usize = 32;
+
+/// Generate a new random session ID.
+fn generate_id() -> std::io::Result<String> {
+    let mut bytes = [0; ID_BYTES];
+    fill_random(&mut bytes)?;
+    let mut id = String::with_capacity(ID_BYTES * 2);
+    for byte in bytes {
+        let _ = write!(id, "{:02x}", byte);
+    }
+    Ok(id)
+}
+
+/// Return `true` if `id` could have come from [`generate_id`]. Anything else in the cookie is
+/// ignored without asking the store.
+fn is_valid_id(id: &str) -> bool {
+    id.len() == ID_BYTES * 2 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// The state of a session while a request is being handled.
+#[derive(Debug, Default)]
+struct SessionState {
+    /// The ID the client sent, if it referred to a live session.
+    id: Option<String>,
+    data: HashMap<String, String>,
+    /// When the loaded session was due to expire.
+    expires: Option<SystemTime>,
+    modified: bool,
+    rotate: bool,
+    destroyed: bool,
+}
+
+/// The session for the current request. Changes are saved by the [`SessionLayer`] after the
+/// handler returns, and a `Session` can be cloned cheaply to share it.
+///
+/// The request is rejected if the router has no `SessionLayer`.
+#[derive(Debug, Clone)]
+pub struct Session(Arc<Mutex<SessionState>>);
+
+impl Session {
+    /// Lock the state. Every change is a single assignment, so a poisoned lock is still safe to
+    /// use.
+    fn lock(&self) -> MutexGuard<'_, SessionState> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Return the ID of the session, or `None` if it is new and has not been saved yet.
+    pub fn id(&self) -> Option<String> {
+        self.lock().id.clone()
+    }
+
+    /// Return the value for `key`, if there is one.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lock().data.get(key).cloned()
+    }
+
+    /// Set `key` to `value`, replacing any existing value.
+    pub fn insert(&self, key: impl Into<String>, value: impl Into<String>) {
+        let mut state = self.lock();
+        state.data.insert(key.into(), value.into());
+        state.modified = true;
+    }
+
+    /// Remove `key`, returning its value if there was one.
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut state = self.lock();
+        let removed = state.data.remove(key);
+        state.modified |= removed.is_some();
+        removed
+    }
+
+    /// Remove every value, but keep the session.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.modified |= !state.data.is_empty();
+        state.data.clear();
+    }
+
+    /// Move the data to a new session ID and delete the old one. Call this whenever a client logs
+    /// in or out, so that an ID an attacker planted or saw beforehand is useless afterwards.
+    pub fn rotate_id(&self) {
+        let mut state = self.lock();
+        state.rotate = true;
+        state.modified = true;
+    }
+
+    /// Delete the session from the store and tell the client to delete its cookie.
+    pub fn destroy(&self) {
+        let mut state = self.lock();
+        state.data.clear();
+        state.destroyed = true;
+    }
+}
+
+impl FromRequestParts for Session {
+    /// Take the session which the [`SessionLayer`] added to the request extensions.
+    fn from_request_parts(parts: &Parts) -> Result<Self, ExtractError> {
+        parts
+            .extensions
+            .get::<Session>()
+            .cloned()
+            .ok_or(ExtractError::BadRequest)
+    }
+}
+
+impl FromRequest for Session {
+    /// When a `Session` is requested as the last parameter, we pull it from the parts like normal.
+    fn from_request(req: Request) -> Result<Self, ExtractError> {
+        Self::from_request_parts(req.into_parts())
+    }
+}
+
+/// The thread started by [`SessionLayer::with_sweeper`].
+struct Sweeper {
+    stop: mpsc::Sender<()>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Sweeper {
+    /// Wake the thread and wait for it to exit, so that it never outlives the layer.
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Middleware which loads the [`Session`] for each request from a [`SessionStore`], and saves it
+/// afterwards.
+///
+/// A session expires once it has not been used for the time to live, which is one day by default.
+/// Nothing is stored, and no cookie is sent, until a handler puts something in the session.
+pub struct SessionLayer<S> {
+    store: Arc<S>,
+    cookie_name: String,
+    ttl: Duration,
+    path: String,
+    secure: bool,
+    same_site: SameSite,
+    sweeper: Option<Sweeper>,
+}
+
+impl<S: SessionStore> SessionLayer<S> {
+    /// Create a layer which keeps sessions in `store`.
+    pub fn new(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            cookie_name: "id".to_string(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            path: "/".to_string(),
+            secure: false,
+            same_site: SameSite::Lax,
+            sweeper: None,
+        }
+    }
+
+    /// Set the name of the session cookie, which is `id` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is not a valid cookie name.
+    pub fn with_cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = Cookie::new(name, "").name().to_string();
+        self
+    }
+
+    /// Set how long a session lasts without being used.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set the `Path` of the session cookie, which is `/` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is not a valid cookie attribute.
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        let path = path.into();
+        // Build a cookie now, so that an invalid path panics here rather than on every request.
+        Cookie::new(self.cookie_name.clone(), "").with_path(path.clone());
+        self.path = path;
+        self
+    }
+
+    /// Set whether the session cookie is only sent over HTTPS. This should be enabled whenever the
+    /// site is served over HTTPS.
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Set the `SameSite` attribute of the session cookie, which is `Lax` by default.
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Start a thread which deletes expired sessions from the store every `interval`. Expired
+    /// sessions are never loaded, but without a sweeper they are only deleted when a client
+    /// returns with one.
+    ///
+    /// The thread belongs to the layer. Dropping the layer wakes the thread and waits for it to
+    /// exit, which only takes as long as a sweep already in progress. Calling this again replaces
+    /// the previous thread in the same way.
+    pub fn with_sweeper(mut self, interval: Duration) -> Self {
+        // Drop the old sweeper first, so that two threads never sweep the same store.
+        self.sweeper = None;
+
+        let (stop, stopped) = mpsc::channel();
+        // The thread only holds a weak reference, so it never keeps the store alive by itself.
+        let store = Arc::downgrade(&self.store);
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                let Some(store) = store.upgrade() else {
+                    break;
+                };
+                if let Err(e) = store.delete_expired(SystemTime::now()) {
+                    log!(Level::Error, "Failed to sweep sessions: {}", e);
+                }
+            }
+        });
+        self.sweeper = Some(Sweeper {
+            stop,
+            thread: Some(thread),
+        });
+        self
+    }
+
+    /// Return the store this layer uses.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Load the session named by the request's cookie, if it exists and has not expired.
+    fn load(&self, req: &Request) -> SessionState {
+        let jar = CookieJar::from_headers(req.headers());
+        let Some(id) = jar
+            .get(&self.cookie_name)
+            .map(Cookie::value)
+            .filter(|id| is_valid_id(id))
+        else {
+            return SessionState::default();
+        };
+
+        match self.store.load(id) {
+            Ok(Some(record)) if !record.is_expired(SystemTime::now()) => SessionState {
+                id: Some(id.to_string()),
+                data: record.data,
+                expires: Some(record.expires),
+                ..SessionState::default()
+            },
+            Ok(Some(_)) => {
+                let _ = self.store.delete(id);
+                SessionState::default()
+            }
+            Ok(None) => SessionState::default(),
+            Err(e) => {
+                log!(Level::Error, "Failed to load session: {}", e);
+                SessionState::default()
+            }
+        }
+    }
+
+    /// Create the session cookie with the configured attributes.
+    fn cookie(&self, id: &str) -> Cookie {
+        Cookie::new(self.cookie_name.clone(), id)
+            .with_path(self.path.clone())
+            .with_max_age(self.ttl)
+            .with_http_only(true)
+            .with_secure(self.secure)
+            .with_same_site(self.same_site)
+    }
+
+    /// Save the session after the handler has run, returning the cookie to send if it changed.
+    fn save(&self, state: SessionState) -> Option<Cookie> {
+        let now = SystemTime::now();
+
+        if state.destroyed {
+            let id = state.id?;
+            if let Err(e) = self.store.delete(&id) {
+                log!(Level::Error, "Failed to delete session: {}", e);
+            }
+            return Some(Cookie::removal(self.cookie_name.clone()).with_path(self.path.clone()));
+        }
+
+        // Sessions are extended whenever they are used, but to avoid writing to the store on
+        // every request, an unmodified session is only saved once half of its lifetime is up.
+        let needs_refresh = state
+            .expires
+            .is_some_and(|expires| expires < now + self.ttl / 2);
+        if !state.modified && !needs_refresh {
+            return None;
+        }
+        if state.id.is_none() && state.data.is_empty() {
+            return None;
+        }
+
+        let id = match &state.id {
+            Some(id) if !state.rotate => id.clone(),
+            old_id => {
+                if let Some(old_id) = old_id {
+                    if let Err(e) = self.store.delete(old_id) {
+                        log!(Level::Error, "Failed to delete session: {}", e);
+                    }
+                }
+                match generate_id() {
+                    Ok(id) => id,
+                    Err(e) => {
+                        log!(Level::Error, "Failed to generate a session ID: {}", e);
+                        return None;
+                    }
+                }
+            }
+        };
+
+        let record = SessionRecord {
+            data: state.data,
+            expires: now + self.ttl,
+        };
+        if let Err(e) = self.store.save(&id, &record) {
+            log!(Level::Error, "Failed to save session: {}", e);
+            return None;
+        }
+        Some(self.cookie(&id))
+    }
+}
+
+impl<S: SessionStore> Layer for SessionLayer<S> {
+    fn call(&self, mut req: Request, next: Next) -> Response {
+        let session = Session(Arc::new(Mutex::new(self.load(&req))));
+        req.extensions_mut().insert(session.clone());
+
+        let mut response = next.run(req);
+
+        let state = std::mem::take(&mut *session.lock());
+        if let Some(cookie) = self.save(state) {
+            cookie.into_response_parts(&mut response);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{HeaderMap, Method},
+        routing::{get, post},
+        Router,
+    };
+
+    fn login(session: Session) -> &'static str {
+        session.rotate_id();
+        session.insert("user", "ferris");
+        "Logged in"
+    }
+
+    fn whoami(session: Session) -> String {
+        session.get("user").unwrap_or_else(|| "Nobody".to_string())
+    }
+
+    fn logout(session: Session) -> &'static str {
+        session.destroy();
+        "Logged out"
+    }
+
+    fn router(layer: SessionLayer<MemoryStore>) -> (Router, Arc<MemoryStore>) {
+        let store = Arc::clone(&layer.store);
+        let router = Router::new()
+            .route("/login", post(login))
+            .route("/whoami", get(whoami))
+            .route("/logout", post(logout))
+            .layer(layer);
+        (router, store)
+    }
+
+    fn request(method: Method, path: &str, cookie: Option<&str>) -> Request {
+        let mut headers = HeaderMap::new();
+        if let Some(cookie) = cookie {
+            headers.insert("Cookie", format!("id={}", cookie));
+        }
+        Request::with_headers(method, path, headers)
+    }
+
+    fn set_cookie(response: &Response) -> Option<Cookie> {
+        response
+            .headers()
+            .get("Set-Cookie")
+            .map(|value| value.parse().unwrap())
+    }
+
+    #[test]
+    fn test_session_id() {
+        let id = generate_id().unwrap();
+        assert!(is_valid_id(&id));
+        assert_ne!(id, generate_id().unwrap());
+        assert!(!is_valid_id("../../etc/passwd"));
+        assert!(!is_valid_id(&id.to_uppercase()));
+    }
+
+    #[test]
+    fn test_session_login_flow() {
+        let (router, store) = router(SessionLayer::new(MemoryStore::new()));
+
+        // Reading an empty session stores nothing.
+        let response = router.call(request(Method::Get, "/whoami", None));
+        assert_eq!(response.text(), "Nobody");
+        assert_eq!(set_cookie(&response), None);
+        assert!(store.is_empty());
+
+        let response = router.call(request(Method::Post, "/login", None));
+        let cookie = set_cookie(&response).unwrap();
+        assert_eq!(cookie.name(), "id");
+        assert!(cookie.http_only());
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.max_age(), Some(Duration::from_secs(24 * 60 * 60)));
+        let id = cookie.value().to_string();
+        assert_eq!(store.len(), 1);
+
+        // The session is read back without being saved again.
+        let response = router.call(request(Method::Get, "/whoami", Some(&id)));
+        assert_eq!(response.text(), "ferris");
+        assert_eq!(set_cookie(&response), None);
+
+        let response = router.call(request(Method::Post, "/logout", Some(&id)));
+        assert!(set_cookie(&response).unwrap().is_removal());
+        assert!(store.is_empty());
+
+        let response = router.call(request(Method::Get, "/whoami", Some(&id)));
+        assert_eq!(response.text(), "Nobody");
+    }
+
+    #[test]
+    fn test_session_rotation() {
+        let (router, store) = router(SessionLayer::new(MemoryStore::new()));
+        let response = router.call(request(Method::Post, "/login", None));
+        let first = set_cookie(&response).unwrap().value().to_string();
+
+        let response = router.call(request(Method::Post, "/login", Some(&first)));
+        let second = set_cookie(&response).unwrap().value().to_string();
+        assert_ne!(first, second);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.load(&first).unwrap(), None);
+
+        let response = router.call(request(Method::Get, "/whoami", Some(&first)));
+        assert_eq!(response.text(), "Nobody");
+    }
+
+    #[test]
+    fn test_session_expiry() {
+        let (router, store) = router(SessionLayer::new(MemoryStore::new()));
+        let id = generate_id().unwrap();
+        let record = |expires| SessionRecord {
+            data: HashMap::from([("user".to_string(), "ferris".to_string())]),
+            expires,
+        };
+
+        store
+            .save(&id, &record(SystemTime::now() - Duration::from_secs(1)))
+            .unwrap();
+        let response = router.call(request(Method::Get, "/whoami", Some(&id)));
+        assert_eq!(response.text(), "Nobody");
+        assert!(store.is_empty());
+
+        // A session close to expiring is extended when it is used.
+        let soon = SystemTime::now() + Duration::from_secs(60);
+        store.save(&id, &record(soon)).unwrap();
+        let response = router.call(request(Method::Get, "/whoami", Some(&id)));
+        assert_eq!(response.text(), "ferris");
+        assert_eq!(set_cookie(&response).unwrap().value(), id);
+        assert!(store.load(&id).unwrap().unwrap().expires > soon);
+    }
+
+    #[test]
+    fn test_session_ignores_foreign_ids() {
+        let (router, store) = router(SessionLayer::new(MemoryStore::new()));
+        let response = router.call(request(Method::Post, "/login", Some("attacker-chosen")));
+        let cookie = set_cookie(&response).unwrap();
+        assert!(is_valid_id(cookie.value()));
+        assert!(store.load("attacker-chosen").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_session_sweeper() {
+        let layer = SessionLayer::new(MemoryStore::new()).with_sweeper(Duration::from_millis(10));
+        let expired = SessionRecord {
+            data: HashMap::new(),
+            expires: SystemTime::now(),
+        };
+        layer.store().save("old", &expired).unwrap();
+
+        for _ in 0..100 {
+            if layer.store().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(layer.store().is_empty());
+    }
+
+    #[test]
+    fn test_session_sweeper_stops_with_layer() {
+        let layer = SessionLayer::new(MemoryStore::new()).with_sweeper(Duration::from_secs(3600));
+        let store = Arc::downgrade(&layer.store);
+
+        let start = std::time::Instant::now();
+        drop(layer);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(store.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_session_without_layer_is_rejected() {
+        let router = Router::new().route("/whoami", get(whoami));
+        let response = router.call(request(Method::Get, "/whoami", None));
+        assert_eq!(response.status_code(), 400);
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The data stored for one session, along with when it expires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub data: HashMap<String, String>,
+    pub expires: SystemTime,
+}
+
+impl SessionRecord {
+    /// Return `true` if the session has expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires <= now
+    }
+}
+
+/// Somewhere to keep session data between requests. Implement this to store sessions in a
+/// database or cache shared between servers.
+///
+/// Session IDs are 64 lowercase hex characters. The [`SessionLayer`](super::SessionLayer) only
+/// passes IDs of that form, but a store should not rely on it if it is used directly.
+pub trait SessionStore: Send + Sync + 'static {
+    /// Return the record for `id`, or `None` if there isn't one. A store may return an expired
+    /// record, which the layer will ignore.
+    fn load(&self, id: &str) -> io::Result<Option<SessionRecord>>;
+
+    /// Create or replace the record for `id`.
+    fn save(&self, id: &str, record: &SessionRecord) -> io::Result<()>;
+
+    /// Delete the record for `id`, if there is one.
+    fn delete(&self, id: &str) -> io::Result<()>;
+
+    /// Delete every record which has expired at `now`, returning how many were deleted.
+    fn delete_expired(&self, now: SystemTime) -> io::Result<usize>;
+}
+
+/// A [`SessionStore`] which keeps sessions in memory. They are lost when the server restarts, and
+/// are not shared between processes.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    records: Mutex<HashMap<String, SessionRecord>>,
+}
+
+impl MemoryStore {
+    /// Create an empty `MemoryStore`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of sessions in the store, including expired ones which have not been
+    /// swept yet.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Return `true` if there are no sessions in the store.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Lock the records. A panic while they were locked cannot leave a record half-written, so a
+    /// poisoned lock is still safe to use.
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, SessionRecord>> {
+        self.records.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionRecord>> {
+        Ok(self.lock().get(id).cloned())
+    }
+
+    fn save(&self, id: &str, record: &SessionRecord) -> io::Result<()> {
+        self.lock().insert(id.to_string(), record.clone());
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        self.lock().remove(id);
+        Ok(())
+    }
+
+    fn delete_expired(&self, now: SystemTime) -> io::Result<usize> {
+        let mut records = self.lock();
+        let before = records.len();
+        records.retain(|_, record| !record.is_expired(now));
+        Ok(before - records.len())
+    }
+}
+
+/// A [`SessionStore`] which keeps each session in its own file in a directory, so that sessions
+/// survive a restart.
+///
+/// The first line of each file is the expiry time in seconds since the Unix epoch, followed by one
+/// `key=value` line per entry with `%`, `=` and line breaks percent-encoded. Files are replaced
+/// atomically, so a crash never leaves a session half-written.
+#[derive(Debug)]
+pub struct FileStore {
+    directory: PathBuf,
+}
+
+impl FileStore {
+    /// Create a `FileStore` in `directory`, creating it if it does not exist.
+    pub fn new(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(Self { directory })
+    }
+
+    /// Return the path for the session `id`, or `None` if the ID could be used to reach a file
+    /// outside the directory.
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        let is_safe = !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric());
+        is_safe.then(|| self.directory.join(format!("{}.session", id)))
+    }
+}
+
+/// Percent-encode the characters which would break the line-based file format.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' => escaped.push_str("%25"),
+            '=' => escaped.push_str("%3D"),
+            '\n' => escaped.push_str("%0A"),
+            '\r' => escaped.push_str("%0D"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse [`escape`].
+fn unescape(s: &str) -> io::Result<String> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid session file");
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail.get(..2).ok_or_else(invalid)?;
+            let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+/// Parse the contents of a session file.
+fn parse_record(contents: &str) -> io::Result<SessionRecord> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid session file");
+    let mut lines = contents.lines();
+    let expires: u64 = lines
+        .next()
+        .and_then(|line| line.parse().ok())
+        .ok_or_else(invalid)?;
+
+    let mut data = HashMap::new();
+    for line in lines {
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        data.insert(unescape(key)?, unescape(value)?);
+    }
+
+    Ok(SessionRecord {
+        data,
+        expires: UNIX_EPOCH + Duration::from_secs(expires),
+    })
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> io::Result<Option<SessionRecord>> {
+        let Some(path) = self.path(id) else {
+            return Ok(None);
+        };
+        match fs::read_to_string(path) {
+            Ok(contents) => parse_record(&contents).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, id: &str, record: &SessionRecord) -> io::Result<()> {
+        let path = self
+            .path(id)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid session ID"))?;
+
+        let expires = record
+            .expires
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let mut contents = format!("{}\n", expires);
+        for (key, value) in &record.data {
+            contents.push_str(&format!("{}={}\n", escape(key), escape(value)));
+        }
+
+        // Write to a temporary file first and rename it over the old one, which is atomic.
+        let temp_path = path.with_extension("tmp");
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(temp_path, path)
+    }
+
+    fn delete(&self, id: &str) -> io::Result<()> {
+        let Some(path) = self.path(id) else {
+            return Ok(());
+        };
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn delete_expired(&self, now: SystemTime) -> io::Result<usize> {
+        let mut deleted = 0;
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_none_or(|extension| extension != "session")
+            {
+                continue;
+            }
+            // A file which cannot be read or parsed will never load either, so it is swept too.
+            let expired = fs::read_to_string(&path)
+                .ok()
+                .and_then(|contents| parse_record(&contents).ok())
+                .is_none_or(|record| record.is_expired(now));
+            if expired && fs::remove_file(&path).is_ok() {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::temp_dir;
+
+    fn record(data: &[(&str, &str)], expires_in: i64) -> SessionRecord {
+        let now = SystemTime::now();
+        let offset = Duration::from_secs(expires_in.unsigned_abs());
+        SessionRecord {
+            data: data
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            expires: if expires_in >= 0 {
+                now + offset
+            } else {
+                now - offset
+            },
+        }
+    }
+
+    /// Exercise the parts of the `SessionStore` contract which every store must follow.
+    fn check_store(store: &impl SessionStore) {
+        let live = record(&[("user", "ferris"), ("tricky", "a=b%c\r\nd")], 60);
+        store.save("live", &live).unwrap();
+        store.save("stale", &record(&[], -60)).unwrap();
+
+        let loaded = store.load("live").unwrap().unwrap();
+        assert_eq!(loaded.data, live.data);
+        assert_eq!(store.load("missing").unwrap(), None);
+
+        assert_eq!(store.delete_expired(SystemTime::now()).unwrap(), 1);
+        assert_eq!(store.load("stale").unwrap(), None);
+
+        store.delete("live").unwrap();
+        store.delete("live").unwrap();
+        assert_eq!(store.load("live").unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new();
+        check_store(&store);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let directory = temp_dir("file-store");
+        let store = FileStore::new(&directory).unwrap();
+        check_store(&store);
+
+        // IDs which could escape the directory are never used as paths.
+        assert_eq!(store.load("../etc/passwd").unwrap(), None);
+        assert!(store.save("../escape", &record(&[], 60)).is_err());
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_file_store_survives_reopening() {
+        let directory = temp_dir("file-store-reopen");
+        FileStore::new(&directory)
+            .unwrap()
+            .save("abc", &record(&[("k", "v")], 60))
+            .unwrap();
+
+        let store = FileStore::new(&directory).unwrap();
+        let loaded = store.load("abc").unwrap().unwrap();
+        assert_eq!(loaded.data.get("k").map(String::as_str), Some("v"));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
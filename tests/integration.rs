@@ -1,10 +1,10 @@
 #[cfg(test)]
 mod integration_final {
     mod example {
-        use std::net::TcpListener;
+        use std::{io, net::TcpListener};
 
         use cairo::{
-            extract::Path,
+            extract::{BodyStream, Path},
             routing::{get, post},
             Router,
         };
@@ -17,6 +17,13 @@ mod integration_final {
             format!("ID: {}, Body: {}", id, body)
         }
 
+        fn upload(mut body: BodyStream) -> String {
+            match io::copy(&mut body, &mut io::sink()) {
+                Ok(num_bytes) => format!("Received {} bytes", num_bytes),
+                Err(e) => format!("Error: {}", e),
+            }
+        }
+
         fn cpu_bound_task() -> String {
             let mut total = 0;
             for _ in 0..1_000_000 {
@@ -31,6 +38,7 @@ mod integration_final {
             let router = Router::new()
                 .route("/", get(hello_world))
                 .route("/post/:id", post(post_handler))
+                .route("/upload", post(upload))
                 .route("/cpu", get(cpu_bound_task));
             cairo::serve(listener, router);
         }
@@ -43,7 +51,7 @@ mod integration_final {
         time::Duration,
     };
 
-    const HOST_AND_PORT: &'static str = "localhost:7878";
+    const HOST_AND_PORT: &str = "localhost:7878";
 
     fn curl(method_and_path: &str) -> io::Result<String> {
         let mut stream = TcpStream::connect(HOST_AND_PORT)?;
@@ -108,6 +116,15 @@ mod integration_final {
             panic!("Expected a 400.");
         };
 
+        let response = ureq::post(&format!("{}/upload", url))
+            .send_bytes(&vec![b'x'; 100_000])
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.into_string().unwrap(),
+            "Received 100000 bytes".to_string()
+        );
+
         let response = ureq::get(&format!("{}/cpu", url)).call().unwrap();
         assert_eq!(response.status(), 200);
         assert_eq!(response.headers_names(), vec!["content-type".to_string()]);
@@ -131,7 +148,7 @@ mod integration_final {
     #[test]
     fn test_the_server_works() {
         // We only start a single instance of the server to avoid any port conflicts.
-        spawn(|| example::start());
+        spawn(example::start);
         sleep(Duration::from_millis(100));
 
         assert_tcp_stream();